@@ -0,0 +1,83 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use hq_rs::{parse_filter, query_stages, CompiledFilter, QueryOptions};
+
+const FILTER: &str = r#".resource{"aws_instance"}[.ami == "ami-123"].tags["Name"]"#;
+
+/// a document with `n` resources, like a large generated Terraform file
+fn document(n: usize) -> hcl::Body {
+    let source: String = (0..n)
+        .map(|i| {
+            format!(
+                "resource \"aws_instance\" \"web_{i}\" {{\n  ami = \"ami-{}\"\n  tags = {{ Name = \"web-{i}\" }}\n}}\n",
+                if i % 2 == 0 { "123" } else { "456" }
+            )
+        })
+        .collect();
+    hcl::from_str(&source).expect("hcl error")
+}
+
+/// applying the same filter to many small documents, parsing the filter each
+/// time versus parsing it once
+fn repeated_apply(c: &mut Criterion) {
+    let documents: Vec<_> = (0..100).map(|_| document(5)).collect();
+    let options = QueryOptions::default();
+
+    c.bench_function("parse each time", |b| {
+        b.iter(|| {
+            for body in &documents {
+                let stages = parse_filter(FILTER).expect("parse error");
+                query_stages(stages, body, &options).expect("query error");
+            }
+        })
+    });
+
+    let filter = CompiledFilter::compile(FILTER).expect("parse error");
+    c.bench_function("compiled", |b| {
+        b.iter(|| {
+            for body in &documents {
+                filter.apply(body).expect("query error");
+            }
+        })
+    });
+}
+
+/// a large generated document with `n` resources that each have `attributes`
+/// attributes, and a few other blocks at the end
+fn large_document(n: usize, attributes: usize) -> hcl::Body {
+    let mut source: String = (0..n)
+        .map(|i| {
+            let attributes: String = (0..attributes)
+                .map(|j| format!("  attr_{j} = \"value-{i}-{j}\"\n"))
+                .collect();
+            format!("resource \"aws_instance\" \"web_{i}\" {{\n{attributes}}}\n")
+        })
+        .collect();
+    source.push_str("variable \"region\" {}\noutput \"id\" {}\nlocals {}\n");
+    hcl::from_str(&source).expect("hcl error")
+}
+
+/// several paths looking names up in the same large bodies, each of the
+/// resources' in one case and the whole document's in the other
+fn large_lookups(c: &mut Criterion) {
+    let body = large_document(2_000, 100);
+    let each_block = (0..100)
+        .step_by(10)
+        .map(|j| format!(".attr_{j}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let each_block =
+        CompiledFilter::compile(&format!(".resource | {each_block}")).expect("parse error");
+    c.bench_function("many paths in each block", |b| {
+        b.iter(|| each_block.apply(&body).expect("query error"))
+    });
+
+    let document =
+        CompiledFilter::compile(".variable, .output, .locals, .data, .module, .provider")
+            .expect("parse error");
+    c.bench_function("many paths in the document", |b| {
+        b.iter(|| document.apply(&body).expect("query error"))
+    });
+}
+
+criterion_group!(benches, repeated_apply, large_lookups);
+criterion_main!(benches);
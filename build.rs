@@ -0,0 +1,43 @@
+//! records the versions of the HCL crates that `hq` was built against, for
+//! `hq --version`
+
+use std::{env, fs, path::PathBuf};
+
+fn main() {
+    let lock = lock_file();
+    if let Some(ref lock) = lock {
+        println!("cargo:rerun-if-changed={}", lock.display());
+    }
+    let contents = lock.and_then(|lock| fs::read_to_string(lock).ok());
+    for (package, var) in [
+        ("hcl-rs", "HQ_HCL_RS_VERSION"),
+        ("hcl-edit", "HQ_HCL_EDIT_VERSION"),
+    ] {
+        let version = contents
+            .as_deref()
+            .and_then(|contents| locked_version(contents, package))
+            .unwrap_or("unknown");
+        println!("cargo:rustc-env={var}={version}");
+    }
+}
+
+/// the `Cargo.lock` of the package, or of the workspace it's in
+fn lock_file() -> Option<PathBuf> {
+    let dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR")?);
+    dir.ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|lock| lock.is_file())
+}
+
+/// the version of `package` in the contents of a `Cargo.lock`, where each
+/// package is a `[[package]]` table that starts with its name and version
+fn locked_version<'a>(contents: &'a str, package: &str) -> Option<&'a str> {
+    let name = format!("name = \"{package}\"");
+    let mut lines = contents.lines();
+    lines.find(|line| line.trim() == name)?;
+    lines
+        .next()?
+        .trim()
+        .strip_prefix("version = \"")?
+        .strip_suffix('"')
+}
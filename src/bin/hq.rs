@@ -1,61 +1,328 @@
 use std::{
+    collections::HashMap,
     error::Error,
-    fs,
-    io::{self, Read, Write},
+    fmt, fs,
+    io::{self, IsTerminal, Read, Write},
+    process::ExitCode,
 };
 
-use clap::{Parser, Subcommand};
+use clap::{
+    error::{ContextKind, ContextValue, ErrorKind},
+    ArgAction, CommandFactory, Parser, Subcommand, ValueEnum,
+};
+use clap_complete::Shell;
+use hcl::Expression;
+use hq_rs::{
+    filter::error::FilterError,
+    highlight::highlight,
+    output,
+    parser::{Builtin, Literal},
+    path::Path,
+    query::{QueryError, QueryResult},
+    ErrorReport, HclParseError, HqError,
+};
+
+// the version, and the versions of the HCL crates it was built against, since
+// how HCL is parsed depends on them
+const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (hcl-rs ",
+    env!("HQ_HCL_RS_VERSION"),
+    ", hcl-edit ",
+    env!("HQ_HCL_EDIT_VERSION"),
+    ")"
+);
 
 #[derive(Parser)]
-#[command(version, about)]
+#[command(version = VERSION, about)]
 struct Args {
-    // the `Read` options are duplicated here because when no command is given
+    // the `Read` options are flattened here because when no command is given
     // then the `read` command is the default and its options come from the root
+    #[command(flatten)]
+    read: ReadArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[clap(
+        long = "error-format",
+        value_name = "FORMAT",
+        default_value = "human",
+        global = true,
+        help = "How to print errors on stderr"
+    )]
+    error_format: ErrorFormat,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ErrorFormat {
+    /// a message pointing at where the error is with a caret
+    Human,
+    /// a JSON object on a single line, with `kind`, `message`, `line`, `column`, and `file`
+    Json,
+}
+
+#[derive(clap::Args)]
+struct ReadArgs {
     #[arg(
         value_name = "FILTER",
-        help = "HCL filter expression",
-        long_help = "HCL filter expression\nsee https://docs.rs/hq-rs/latest/hq_rs/ for filter examples"
+        help = "HCL filter expression (defaults to `.`, which prints the whole input)",
+        long_help = "HCL filter expression (defaults to `.`, which prints the whole input)\nan argument that doesn't look like a filter is read as the first of FILES instead, a filter starts with `.` (but not `./` or `../`), `#`, `@`, `[\"`, or a builtin like `keys`\nsee https://docs.rs/hq-rs/latest/hq_rs/ for filter examples"
     )]
     filter: Option<String>,
 
+    #[clap(
+        long = "from-file",
+        value_name = "FILTER_FILE",
+        help = "Read the filter from a file, which may span lines and contain `#` comments (FILTER is then read as a file)"
+    )]
+    from_file: Option<String>,
+
     #[clap(
         short = 'f',
         long = "file",
         value_name = "FILE",
-        help = "HCL file to read from"
+        help = "HCL file to read from (defaults to stdin, or pass `-`)"
     )]
     file: Option<String>,
 
-    #[command(subcommand)]
-    command: Option<Command>,
+    #[arg(
+        value_name = "FILES",
+        help = "More HCL files to read from (the filter is applied to each in turn, and glob patterns like `*.tf` are expanded)"
+    )]
+    files: Vec<String>,
+
+    #[clap(
+        long = "skip-missing",
+        help = "Skip files that don't exist or can't be read (and glob patterns that match nothing), with a warning on stderr, instead of failing"
+    )]
+    skip_missing: bool,
+
+    #[clap(
+        short = 'R',
+        long = "recursive",
+        help = "Also read files in the subdirectories of directories given as FILES (skipping hidden ones like `.terraform`)"
+    )]
+    recursive: bool,
+
+    #[clap(
+        long = "ext",
+        value_name = "EXT",
+        value_delimiter = ',',
+        default_values = ["tf", "tf.json"],
+        help = "Extensions of the files to read from directories given as FILES"
+    )]
+    extensions: Vec<String>,
+
+    #[clap(
+        short = 'v',
+        long = "verbose",
+        help = "Print the name of each file on stderr as it is read"
+    )]
+    verbose: bool,
+
+    #[clap(
+        long = "with-filename",
+        help = "Prefix each line of output with the file it came from"
+    )]
+    with_filename: bool,
+
+    #[clap(
+        short = 'n',
+        long = "null-input",
+        conflicts_with_all = ["file", "files", "from_file", "slurp", "merge"],
+        help = "Apply the filter to null instead of reading any HCL"
+    )]
+    null_input: bool,
+
+    #[clap(
+        short = 's',
+        long = "slurp",
+        conflicts_with = "with_filename",
+        help = "Gather the results of the filter's first stage from every file into one list, and apply the rest of the filter to that"
+    )]
+    slurp: bool,
+
+    #[clap(
+        long = "merge",
+        conflicts_with_all = ["with_filename", "slurp"],
+        help = "Merge every file into one document before applying the filter, with later files overriding earlier ones (the way Terraform applies override files)"
+    )]
+    merge: bool,
+
+    #[clap(
+        long = "show-paths",
+        help = "Prefix each result with the path to it, written as a filter that matches it"
+    )]
+    show_paths: bool,
+
+    #[clap(
+        long = "ignore-case",
+        help = "Match names and labels case-insensitively"
+    )]
+    ignore_case: bool,
+
+    #[clap(
+        long = "strict-labels",
+        help = "Make it an error for labels to match only the first of a block's labels"
+    )]
+    strict_labels: bool,
+
+    #[clap(
+        long = "max-depth",
+        value_name = "N",
+        default_value_t = hq_rs::query::DEFAULT_MAX_DEPTH,
+        help = "Give up with an error when `..` looks more than N levels deep"
+    )]
+    max_depth: usize,
+
+    #[clap(
+        long = "arg",
+        num_args = 2,
+        value_names = ["NAME", "VALUE"],
+        action = ArgAction::Append,
+        help = "Bind the string VALUE to $NAME, which predicates can compare to"
+    )]
+    arg: Vec<String>,
+
+    #[clap(
+        long = "argjson",
+        num_args = 2,
+        value_names = ["NAME", "JSON"],
+        action = ArgAction::Append,
+        help = "Bind a string, number, or bool written as JSON to $NAME, which predicates can compare to"
+    )]
+    argjson: Vec<String>,
+
+    #[clap(
+        short = 'o',
+        long = "output",
+        value_name = "FORMAT",
+        default_value = "hcl",
+        env = DEFAULT_OUTPUT_VAR,
+        help = "Output format"
+    )]
+    output: OutputFormat,
+
+    #[clap(
+        short = 'r',
+        long = "raw-output",
+        help = "Print scalar results without quotes or formatting"
+    )]
+    raw_output: bool,
+
+    #[clap(
+        short = 'a',
+        long = "assignment",
+        help = "Print attribute results as `key = value` (HCL output only)"
+    )]
+    assignment: bool,
+
+    #[clap(
+        long = "standalone",
+        help = "Print each result as a whole HCL document, e.g. attributes as `key = value`, and fail on results that can't be one (HCL output only)"
+    )]
+    standalone: bool,
+
+    #[clap(
+        long = "keep-comments",
+        help = "Keep the comments before and after attributes and blocks in HCL output"
+    )]
+    keep_comments: bool,
+
+    #[clap(
+        short = 'c',
+        long = "compact",
+        help = "Print each result on as few lines as possible, in any output format"
+    )]
+    compact: bool,
+
+    #[clap(
+        long = "indent",
+        value_name = "N",
+        value_parser = clap::value_parser!(u8).range(0..=16),
+        help = "Indent HCL and JSON output with N spaces (at most 16) [default: 2]"
+    )]
+    indent: Option<u8>,
+
+    #[clap(
+        long = "tab",
+        conflicts_with = "indent",
+        help = "Indent HCL and JSON output with tabs"
+    )]
+    tab: bool,
+
+    #[clap(
+        short = 'S',
+        long = "sort-keys",
+        help = "Sort object keys, and the attributes and blocks in a body, by name"
+    )]
+    sort_keys: bool,
+
+    #[clap(
+        long = "count",
+        help = "Print only the number of results instead of the results themselves (with --with-filename, the number in each file and then the total)"
+    )]
+    count: bool,
+
+    #[clap(
+        short = 'e',
+        long = "exit-status",
+        help = "Exit with status 1 if the filter matched nothing"
+    )]
+    exit_status: bool,
+
+    #[clap(
+        long = "color",
+        value_name = "WHEN",
+        default_value = "auto",
+        help = "Color HCL output (auto colors it only when printing to a terminal, and NO_COLOR isn't set)"
+    )]
+    color: Color,
+
+    #[clap(
+        short = 'M',
+        long = "monochrome-output",
+        conflicts_with = "color",
+        help = "Don't color output (the same as --color never)"
+    )]
+    monochrome_output: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// HCL
+    Hcl,
+    /// HCL exactly as it was written in the input, keeping its formatting and comments
+    HclRaw,
+    /// JSON (blocks are converted following the HCL JSON specification)
+    Json,
+    /// JSON with each result on a line of its own (newline-delimited JSON)
+    Ndjson,
+    /// YAML (converted the same way as JSON, with multiple results separated by `---`)
+    Yaml,
+    /// TOML (converted the same way as JSON, objects and blocks become tables)
+    Toml,
 }
 
 #[derive(Subcommand)]
 enum Command {
     #[command(about = "Read value from HCL (default)")]
-    Read {
-        #[clap(
-            short = 'f',
-            long = "file",
-            value_name = "FILE",
-            help = "HCL file to read from"
-        )]
-        file: Option<String>,
-
-        #[arg(
-            value_name = "FILTER",
-            help = "HCL filter expression",
-            long_help = "HCL filter expression\nsee https://docs.rs/hq-rs/latest/hq_rs/ for filter examples"
-        )]
-        filter: Option<String>,
-    },
+    Read(ReadArgs),
     #[command(about = "Write value into HCL")]
     Write {
         #[clap(
             short = 'f',
             long = "file",
             value_name = "FILE",
-            help = "HCL file to read from"
+            help = "HCL file to read from (defaults to stdin, or pass `-`)"
         )]
         file: Option<String>,
 
@@ -80,7 +347,7 @@ enum Command {
             short = 'f',
             long = "file",
             value_name = "FILE",
-            help = "HCL file to read from"
+            help = "HCL file to read from (defaults to stdin, or pass `-`)"
         )]
         file: Option<String>,
 
@@ -99,20 +366,136 @@ enum Command {
         )]
         filter: String,
     },
+    #[command(hide = true, about = "Print a completion script for SHELL to stdout")]
+    Completions {
+        #[arg(value_enum, help = "The shell to complete in")]
+        shell: Shell,
+    },
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+// exit code when `--exit-status` is set and the filter matched nothing
+const EXIT_NO_MATCH: u8 = 1;
+// exit code for any error (this matches clap's exit code for usage errors)
+const EXIT_ERROR: u8 = 2;
 
-    match args.command {
-        None => {
-            read(args.filter, args.file)?;
+// the environment variable that sets the default for `--output`
+const DEFAULT_OUTPUT_VAR: &str = "HQ_DEFAULT_OUTPUT";
+
+fn main() -> ExitCode {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => match invalid_default_output(&e) {
+            Some(value) => {
+                let formats: Vec<_> = OutputFormat::value_variants()
+                    .iter()
+                    .filter_map(|format| format.to_possible_value())
+                    .map(|format| format.get_name().to_string())
+                    .collect();
+                eprintln!(
+                    "Error: {DEFAULT_OUTPUT_VAR} is set to '{value}', which is not an output format (expected one of {})",
+                    formats.join(", ")
+                );
+                return ExitCode::from(EXIT_ERROR);
+            }
+            None => e.exit(),
+        },
+    };
+
+    // errors are colored the same way as results, but for stderr
+    let when = match args.command {
+        None => color_when(&args.read),
+        Some(Command::Read(ref read_args)) => color_when(read_args),
+        Some(_) => Color::Auto,
+    };
+    hq_rs::error::set_colored(use_color(when, no_color(), io::stderr().is_terminal()));
+
+    let error_format = args.error_format;
+    match run(args) {
+        Ok(code) => code,
+        Err(e) => {
+            match error_format {
+                ErrorFormat::Human => eprintln!("Error: {e:?}"),
+                ErrorFormat::Json => eprintln!("{}", error_report(e.as_ref()).to_json()),
+            }
+            ExitCode::from(EXIT_ERROR)
         }
-        Some(Command::Read { file, filter }) => {
-            read(file, filter)?;
+    }
+}
+
+/// `e` described for `--error-format json`
+fn error_report(e: &(dyn Error + 'static)) -> ErrorReport {
+    if let Some(e) = e.downcast_ref::<HqError>() {
+        ErrorReport::from(e)
+    } else if let Some(e) = e.downcast_ref::<HclParseError>() {
+        ErrorReport::from(e)
+    } else if let Some(e) = e.downcast_ref::<InputError>() {
+        ErrorReport {
+            file: Some(e.file.clone()),
+            ..ErrorReport::from(&e.error)
         }
+    } else if let Some(e) = e.downcast_ref::<Box<FilterError>>() {
+        ErrorReport::from(e.as_ref())
+    } else if let Some(e) = e.downcast_ref::<Box<QueryError>>() {
+        ErrorReport::from(e.as_ref())
+    } else {
+        ErrorReport::other(e)
+    }
+}
+
+/// an error reading the HCL in `file`, other than a syntax error (which names
+/// its file itself)
+struct InputError {
+    file: String,
+    error: HqError,
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.error)
+    }
+}
+
+impl fmt::Debug for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl Error for InputError {}
+
+/// write a completion script for `shell` to `w`, generated from [`Args`] so that
+/// it always has the same flags
+fn completions(shell: Shell, w: &mut dyn Write) {
+    clap_complete::generate(shell, &mut Args::command(), "hq", w);
+}
+
+/// the value of `HQ_DEFAULT_OUTPUT`, if `e` is for it being invalid, which
+/// clap reports as if it had been given to `--output`
+fn invalid_default_output(e: &clap::Error) -> Option<String> {
+    if e.kind() != ErrorKind::InvalidValue {
+        return None;
+    }
+    let Some(ContextValue::String(arg)) = e.get(ContextKind::InvalidArg) else {
+        return None;
+    };
+    let Some(ContextValue::String(value)) = e.get(ContextKind::InvalidValue) else {
+        return None;
+    };
+    let default = std::env::var(DEFAULT_OUTPUT_VAR).ok()?;
+    (arg.starts_with("--output") && *value == default).then_some(default)
+}
+
+fn run(args: Args) -> Result<ExitCode, Box<dyn Error>> {
+    match args.command {
+        Some(Command::Completions { shell }) => {
+            completions(shell, &mut io::stdout());
+            Ok(ExitCode::SUCCESS)
+        }
+        None => read(args.read),
+        Some(Command::Read(read_args)) => read(read_args),
         Some(Command::Write { file, inline, expr }) => {
             write(file, inline, expr)?;
+            Ok(ExitCode::SUCCESS)
         }
         Some(Command::Delete {
             file,
@@ -120,12 +503,14 @@ fn main() -> Result<(), Box<dyn Error>> {
             filter,
         }) => {
             delete(file, inline, filter)?;
+            Ok(ExitCode::SUCCESS)
         }
     }
-
-    Ok(())
 }
 
+// the name used for stdin in diagnostics
+const STDIN_NAME: &str = "<stdin>";
+
 fn read_stdin() -> Result<String, Box<dyn Error>> {
     let mut stdin = io::stdin();
     let mut buf = String::new();
@@ -133,40 +518,522 @@ fn read_stdin() -> Result<String, Box<dyn Error>> {
     Ok(buf)
 }
 
-fn read(file: Option<String>, filter: Option<String>) -> Result<(), Box<dyn Error>> {
-    let contents = match file {
-        Some(file) => fs::read_to_string(file)?,
-        None => read_stdin()?,
-    };
-    let body: hcl::Body = hcl::from_str(&contents)?;
-    match filter {
-        Some(filter) => {
-            let mut fields = hq_rs::parse_filter(&filter)?;
-            let query_results = hq_rs::query(&mut fields, &body);
-            for query_result in query_results {
-                let s = query_result.to_string()?;
-                print!("{s}");
-                io::stdout().flush()?;
-                if !s.ends_with('\n') {
-                    println!();
-                }
+fn is_stdin(file: Option<&str>) -> bool {
+    matches!(file, None | Some("-"))
+}
+
+/// read the contents of `file`, or stdin if it is not set or is `-`
+///
+/// returns the contents along with the name to use for the input in diagnostics
+fn read_input(file: Option<&str>) -> Result<(String, &str), Box<dyn Error>> {
+    match file {
+        Some(file) if !is_stdin(Some(file)) => {
+            let contents = fs::read_to_string(file).map_err(|e| format!("{file}: {e}"))?;
+            Ok((contents, file))
+        }
+        _ => {
+            let contents = read_stdin().map_err(|e| format!("{STDIN_NAME}: {e}"))?;
+            Ok((contents, STDIN_NAME))
+        }
+    }
+}
+
+/// an error for HCL in `source` that couldn't be parsed, a syntax error points
+/// at the line and column where it is
+fn hcl_error(source: &str, e: impl Into<HqError>) -> Box<dyn Error> {
+    match e.into() {
+        HqError::HclParse(e) => Box::new(e.with_file(source)),
+        error => Box::new(InputError {
+            file: source.to_string(),
+            error,
+        }),
+    }
+}
+
+fn parse_single_path(filter: &str) -> Result<Vec<hq_rs::parser::Field>, Box<dyn Error>> {
+    let mut stages = hq_rs::parse_filter(filter)?;
+    if stages.len() != 1 || stages[0].paths.len() != 1 {
+        return Err("filter must be a single path (without ',' or '|')".into());
+    }
+    Ok(stages.remove(0).paths.remove(0))
+}
+
+/// expand glob patterns in `paths`, for shells that don't (e.g. on Windows)
+///
+/// a path that exists is used as is, even if it looks like a pattern, and a
+/// pattern that matches nothing is an error unless `skip_missing` is set
+fn expand_globs(paths: &[String], skip_missing: bool) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        if !path.contains(['*', '?', '[']) || std::path::Path::new(path).exists() {
+            expanded.push(path.clone());
+            continue;
+        }
+        let matches = glob::glob(path).map_err(|e| format!("invalid pattern '{path}': {e}"))?;
+        let before = expanded.len();
+        for m in matches {
+            expanded.push(m?.to_string_lossy().into_owned());
+        }
+        if expanded.len() == before {
+            if skip_missing {
+                eprintln!("warning: no files matched pattern '{path}', skipping it");
+                continue;
             }
+            return Err(format!("no files matched pattern '{path}'").into());
         }
-        None => {
-            println!("HCL from stdin contained:");
-            println!(" * {} top-level attribute(s)", body.attributes().count());
-            println!(" * {} top-level block(s)", body.blocks().count());
+    }
+    Ok(expanded)
+}
+
+/// replace the directories in `paths` with the files in them that have one of
+/// `extensions`, in order of their paths
+///
+/// only the files directly in a directory are read unless `recursive` is set,
+/// and hidden files and directories (like `.terraform`) are always skipped
+fn expand_dirs(
+    paths: Vec<String>,
+    recursive: bool,
+    extensions: &[String],
+    skip_missing: bool,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        if !std::path::Path::new(&path).is_dir() {
+            expanded.push(path);
+            continue;
+        }
+        let mut files = Vec::new();
+        dir_files(
+            std::path::Path::new(&path),
+            recursive,
+            extensions,
+            &mut files,
+        )
+        .map_err(|e| format!("{path}: {e}"))?;
+        if files.is_empty() {
+            let message = format!(
+                "no files with extension {} in directory '{path}'",
+                extensions.join(" or ")
+            );
+            if skip_missing {
+                eprintln!("warning: {message}, skipping it");
+                continue;
+            }
+            return Err(message.into());
+        }
+        files.sort();
+        expanded.extend(files);
+    }
+    Ok(expanded)
+}
+
+fn dir_files(
+    dir: &std::path::Path,
+    recursive: bool,
+    extensions: &[String],
+    files: &mut Vec<String>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        // symlinked directories aren't followed, so there are no cycles
+        if entry.file_type()?.is_dir() {
+            if recursive {
+                dir_files(&path, recursive, extensions, files)?;
+            }
+        } else if path.is_file()
+            && extensions
+                .iter()
+                .any(|ext| name.ends_with(&format!(".{}", ext.trim_start_matches('.'))))
+        {
+            files.push(path.to_string_lossy().into_owned());
         }
     }
     Ok(())
 }
 
-fn write(file: Option<String>, inline: bool, expr: String) -> Result<(), Box<dyn Error>> {
-    let contents = match file {
-        Some(ref file) => fs::read_to_string(file)?,
-        None => read_stdin()?,
+/// whether the first positional argument is a filter rather than a file: a
+/// filter starts with `.` (except for `./` and `../`, which start a path), a
+/// `#` comment, `@`, `["` (or `['`), or the name of a builtin (like `keys` or
+/// `length | ...`)
+fn is_filter(arg: &str) -> bool {
+    let arg = arg.trim_start();
+    if arg.starts_with("./") || arg.starts_with("../") {
+        return false;
+    }
+    if arg.starts_with(['.', '#', '@']) || arg.starts_with("[\"") || arg.starts_with("['") {
+        return true;
+    }
+    let word = arg
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+        .next()
+        .unwrap_or_default();
+    Builtin::ALL.iter().any(|builtin| builtin.name() == word)
+}
+
+fn read(mut args: ReadArgs) -> Result<ExitCode, Box<dyn Error>> {
+    if let Some(ref filter_file) = args.from_file {
+        // there's no filter argument, so the first positional argument is a file
+        let filter = fs::read_to_string(filter_file).map_err(|e| format!("{filter_file}: {e}"))?;
+        if let Some(file) = args.filter.replace(filter) {
+            args.files.insert(0, file);
+        }
+    } else if let Some(file) = args.filter.take_if(|filter| !is_filter(filter)) {
+        // e.g. `hq main.tf -o json` converts the whole file
+        args.files.insert(0, file);
+    }
+    let paths: Vec<String> = args.file.iter().chain(args.files.iter()).cloned().collect();
+    let paths = expand_globs(&paths, args.skip_missing)?;
+    let paths = expand_dirs(paths, args.recursive, &args.extensions, args.skip_missing)?;
+    let mut files: Vec<Option<&str>> = paths.iter().map(|file| Some(file.as_str())).collect();
+    // every file given was skipped, so there's nothing to read (not even stdin)
+    let all_skipped = files.is_empty() && (args.file.is_some() || !args.files.is_empty());
+    if files.is_empty() && !all_skipped {
+        files.push(None);
+    }
+    let stages = hq_rs::parse_filter(args.filter.as_deref().unwrap_or("."))?;
+    let options = hq_rs::QueryOptions {
+        ignore_case: args.ignore_case,
+        variables: variables(&args.arg, &args.argjson)?,
+        max_depth: args.max_depth,
+        strict_labels: args.strict_labels,
+    };
+    if args.assignment
+        && (args.raw_output
+            || matches!(
+                args.output,
+                OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Yaml | OutputFormat::Toml
+            ))
+    {
+        eprintln!("note: --assignment only applies to HCL output, ignoring it");
+    }
+    if args.standalone
+        && (args.raw_output
+            || matches!(
+                args.output,
+                OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Yaml | OutputFormat::Toml
+            ))
+    {
+        eprintln!("note: --standalone only applies to HCL output, ignoring it");
+    }
+    if args.keep_comments
+        && (args.raw_output || args.compact || !matches!(args.output, OutputFormat::Hcl))
+    {
+        eprintln!(
+            "note: --keep-comments only applies to HCL output that isn't compact, ignoring it"
+        );
+    }
+
+    let mut printer = Printer {
+        args: &args,
+        matched: false,
+        count: 0,
+        printed: 0,
+        warned_unevaluated: false,
     };
-    let mut body: hcl_edit::structure::Body = contents.parse()?;
+    if args.null_input {
+        let input = QueryResult::Expr(Expression::Null);
+        hq_rs::query_stages_each_on(
+            &stages,
+            Path::default(),
+            input,
+            &options,
+            |path, query_result| printer.print(&path, &query_result, None, ""),
+        )?;
+        // stdin isn't read either
+        files.clear();
+    }
+    // with `--slurp` the results of the first stage from every file are
+    // gathered here, and the rest of the stages are applied to them at the end
+    let mut slurped = Vec::new();
+    // with `--merge` every file is read before the filter is applied once, to
+    // all of them merged together
+    let mut merged = Vec::new();
+    for file in files {
+        let (contents, source) = match read_input(file) {
+            Ok(input) => input,
+            Err(e) if args.skip_missing => {
+                eprintln!("warning: {e}, skipping it");
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if args.verbose {
+            eprintln!("reading {source}");
+        }
+        // a `.json` file is always in the JSON syntax, otherwise it's told apart by
+        // its contents
+        let json = source.ends_with(".json") || hq_rs::json::is_json(&contents);
+        let body = match json {
+            true => hq_rs::json::from_str(&contents),
+            false => hq_rs::parse_hcl(&contents),
+        }
+        .map_err(|e| hcl_error(source, e))?;
+        if args.slurp {
+            hq_rs::query_stages_each(&stages[..1], &body, &options, |_, query_result| {
+                slurped.push(Expression::from(query_result.to_value()));
+                Ok::<(), Box<dyn Error>>(())
+            })?;
+            continue;
+        }
+        if args.merge {
+            merged.push(body);
+            continue;
+        }
+        // the original source is only needed to print results verbatim, or
+        // with their comments
+        let needs_source = match args.output {
+            OutputFormat::HclRaw => true,
+            OutputFormat::Hcl => args.keep_comments && !args.compact,
+            _ => false,
+        };
+        let edit_body = match needs_source {
+            // JSON has no HCL source to print, so results are formatted instead
+            true if !args.count && !json => Some(
+                contents
+                    .parse::<hcl_edit::structure::Body>()
+                    .map_err(|e| hcl_error(source, e))?,
+            ),
+            _ => None,
+        };
+        let counted = printer.count;
+        // results are printed as they are found rather than all at the end
+        hq_rs::query_stages_each(&stages, &body, &options, |path, query_result| {
+            let original = edit_body
+                .as_ref()
+                .map(|edit_body| (contents.as_str(), edit_body));
+            printer.print(&path, &query_result, original, source)
+        })?;
+        if args.count && args.with_filename {
+            println!("{source}: {}", printer.count - counted);
+        }
+    }
+    if args.merge && !merged.is_empty() {
+        let body = hq_rs::merge(merged);
+        hq_rs::query_stages_each(&stages, &body, &options, |path, query_result| {
+            printer.print(&path, &query_result, None, "")
+        })?;
+    }
+    if args.slurp {
+        let input = QueryResult::Expr(Expression::Array(slurped));
+        if stages.len() == 1 {
+            printer.print(&Path::default(), &input, None, "")?;
+        } else {
+            hq_rs::query_stages_each_on(
+                &stages[1..],
+                Path::default(),
+                input,
+                &options,
+                |path, query_result| printer.print(&path, &query_result, None, ""),
+            )?;
+        }
+    }
+    if args.count && args.with_filename {
+        println!("total: {}", printer.count);
+    } else if args.count {
+        println!("{}", printer.count);
+    }
+    if args.exit_status && !printer.matched {
+        return Ok(ExitCode::from(EXIT_NO_MATCH));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// the variables bound with `--arg` and `--argjson`, each of which is a list
+/// of name and value pairs
+fn variables(
+    arg: &[String],
+    argjson: &[String],
+) -> Result<HashMap<String, Literal>, Box<dyn Error>> {
+    let mut variables = HashMap::new();
+    for pair in arg.chunks(2) {
+        variables.insert(pair[0].clone(), Literal::String(pair[1].clone()));
+    }
+    for pair in argjson.chunks(2) {
+        let (name, json) = (&pair[0], &pair[1]);
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| format!("invalid JSON for --argjson {name}: {e}"))?;
+        let literal = match value {
+            serde_json::Value::String(s) => Literal::String(s),
+            serde_json::Value::Bool(b) => Literal::Bool(b),
+            // every JSON number can be read as a float
+            serde_json::Value::Number(n) => Literal::Number(n.as_f64().unwrap_or_default()),
+            _ => {
+                return Err(format!(
+                    "--argjson {name} must be a string, number, or bool, not {json}"
+                )
+                .into())
+            }
+        };
+        variables.insert(name.clone(), literal);
+    }
+    Ok(variables)
+}
+
+/// prints results as they are found, keeping track of how many there were
+struct Printer<'a> {
+    args: &'a ReadArgs,
+    matched: bool,
+    count: usize,
+    printed: usize,
+    warned_unevaluated: bool,
+}
+
+impl Printer<'_> {
+    /// `path` is where the result was found, and `source` is the name of the
+    /// input it came from
+    fn print(
+        &mut self,
+        path: &Path,
+        query_result: &QueryResult,
+        original: Option<Original>,
+        source: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.matched = true;
+        if self.args.count {
+            // the count is the same regardless of the output format
+            self.count += 1;
+            return Ok(());
+        }
+        // HCL output is the source anyway, but other formats can only show
+        // what couldn't be evaluated as a string of its source
+        let converted = self.args.raw_output
+            || matches!(
+                self.args.output,
+                OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Yaml | OutputFormat::Toml
+            );
+        if converted && !self.warned_unevaluated && !query_result.is_literal() {
+            eprintln!("warning: results contain expressions that can't be evaluated (e.g. references or `for` expressions), they are printed as their source");
+            self.warned_unevaluated = true;
+        }
+        if self.printed > 0 && matches!(self.args.output, OutputFormat::Yaml) {
+            // each result is a separate YAML document
+            println!("---");
+        }
+        let hcl = !self.args.raw_output
+            && matches!(self.args.output, OutputFormat::Hcl | OutputFormat::HclRaw);
+        let key = match (self.args.standalone, self.args.assignment) {
+            (true, _) if hcl => output::standalone_key(query_result, path)?,
+            (_, true) => output::attribute_key(query_result, path),
+            _ => None,
+        };
+        let mut s = render(query_result, path, key, original, self.args)?;
+        if !s.ends_with('\n') {
+            s.push('\n');
+        }
+        if self.args.show_paths {
+            s = format!("{path}: {s}");
+        }
+        if self.args.with_filename {
+            // like grep, every line is prefixed so multi-line results stay attributed
+            s = s.lines().map(|line| format!("{source}:{line}\n")).collect();
+        }
+        print!("{s}");
+        io::stdout().flush()?;
+        self.printed += 1;
+        Ok(())
+    }
+}
+
+/// the source text of an input, and that text parsed with [`hcl_edit`], for
+/// printing results as they were written or with their comments
+type Original<'a> = (&'a str, &'a hcl_edit::structure::Body);
+
+/// `path` is where the result was found, and `key` is the attribute key to
+/// print an HCL result with (`key = value`)
+fn render(
+    query_result: &QueryResult,
+    path: &Path,
+    key: Option<&str>,
+    original: Option<Original>,
+    args: &ReadArgs,
+) -> Result<String, Box<dyn Error>> {
+    if args.raw_output {
+        match output::to_raw(query_result) {
+            // always end with a newline, even if the string already does
+            Some(s) => return Ok(format!("{s}\n")),
+            None => eprintln!("warning: result is not a scalar, ignoring --raw-output"),
+        }
+    }
+    let sorted = args.sort_keys.then(|| output::sort_keys(query_result));
+    let query_result = sorted.as_ref().unwrap_or(query_result);
+    let indent = match (args.tab, args.indent) {
+        (true, _) => output::Indent::Tab,
+        (false, Some(n)) => output::Indent::Spaces(n.into()),
+        (false, None) => output::Indent::default(),
+    };
+    let hcl = match args.output {
+        OutputFormat::Hcl if args.compact => output::to_hcl_compact(query_result)?,
+        OutputFormat::Hcl => {
+            let hcl = output::to_hcl_indented(query_result, indent)?;
+            match original {
+                Some((_, edit_body)) if args.keep_comments => {
+                    output::keep_comments(&hcl, edit_body, path).unwrap_or(hcl)
+                }
+                _ => hcl,
+            }
+        }
+        OutputFormat::HclRaw => match original
+            .and_then(|(contents, edit_body)| output::to_hcl_raw(contents, edit_body, path))
+        {
+            Some(s) => s,
+            None => {
+                eprintln!("warning: result has no source text, printing it as formatted HCL");
+                output::to_hcl_indented(query_result, indent)?
+            }
+        },
+        OutputFormat::Json if args.compact => return output::to_json_compact(query_result),
+        OutputFormat::Json => return output::to_json_indented(query_result, indent),
+        // compact JSON never spans lines, strings with newlines have them escaped
+        OutputFormat::Ndjson => return output::to_json_compact(query_result),
+        OutputFormat::Yaml if args.compact => return output::to_yaml_compact(query_result),
+        OutputFormat::Yaml => return output::to_yaml(query_result),
+        OutputFormat::Toml if args.compact => return output::to_toml_compact(query_result, path),
+        OutputFormat::Toml => return output::to_toml(query_result, path),
+    };
+    let hcl = match key {
+        Some(key) => format!("{key} = {hcl}"),
+        None => hcl,
+    };
+    // only HCL is ever colored, JSON and YAML are left for other tools to read
+    let color = use_color(color_when(args), no_color(), io::stdout().is_terminal());
+    Ok(if color { highlight(&hcl) } else { hcl })
+}
+
+/// `--color`, which `-M` is the same as `--color never` for
+fn color_when(args: &ReadArgs) -> Color {
+    match args.monochrome_output {
+        true => Color::Never,
+        false => args.color,
+    }
+}
+
+/// whether `NO_COLOR` is set to something
+fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// whether to color output for `--color when`, where `NO_COLOR` (see
+/// https://no-color.org) only turns off the colors `auto` would add, so an
+/// explicit `--color always` still wins
+fn use_color(when: Color, no_color: bool, is_terminal: bool) -> bool {
+    match when {
+        Color::Auto => is_terminal && !no_color,
+        Color::Always => true,
+        Color::Never => false,
+    }
+}
+
+fn write(file: Option<String>, inline: bool, expr: String) -> Result<(), Box<dyn Error>> {
+    if inline && is_stdin(file.as_deref()) {
+        return Err("--inline cannot be used when reading from stdin".into());
+    }
+    let (contents, source) = read_input(file.as_deref())?;
+    let mut body: hcl_edit::structure::Body = contents.parse().map_err(|e| hcl_error(source, e))?;
     if !expr.contains('=') {
         return Err("write expression should be <FILTER>=<VALUE>".into());
     }
@@ -177,7 +1044,7 @@ fn write(file: Option<String>, inline: bool, expr: String) -> Result<(), Box<dyn
     let filter = parts[0];
     let new_value = parts[1];
     let expr: hcl_edit::expr::Expression = new_value.parse()?;
-    let fields = hq_rs::parse_filter(filter)?;
+    let fields = parse_single_path(filter)?;
     hq_rs::write(fields, &mut body, &expr)?;
 
     if inline {
@@ -194,12 +1061,12 @@ fn write(file: Option<String>, inline: bool, expr: String) -> Result<(), Box<dyn
 }
 
 fn delete(file: Option<String>, inline: bool, filter: String) -> Result<(), Box<dyn Error>> {
-    let contents = match file {
-        Some(ref file) => fs::read_to_string(file)?,
-        None => read_stdin()?,
-    };
-    let mut body: hcl_edit::structure::Body = contents.parse()?;
-    let fields = hq_rs::parse_filter(&filter)?;
+    if inline && is_stdin(file.as_deref()) {
+        return Err("--inline cannot be used when reading from stdin".into());
+    }
+    let (contents, source) = read_input(file.as_deref())?;
+    let mut body: hcl_edit::structure::Body = contents.parse().map_err(|e| hcl_error(source, e))?;
+    let fields = parse_single_path(&filter)?;
     hq_rs::delete(fields, &mut body)?;
 
     if inline {
@@ -214,3 +1081,103 @@ fn delete(file: Option<String>, inline: bool, filter: String) -> Result<(), Box<
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_precedence() {
+        // (when, NO_COLOR is set, stdout is a terminal, colored)
+        let matrix = [
+            (Color::Auto, false, true, true),
+            (Color::Auto, false, false, false),
+            (Color::Auto, true, true, false),
+            (Color::Auto, true, false, false),
+            (Color::Always, false, true, true),
+            (Color::Always, false, false, true),
+            (Color::Always, true, true, true),
+            (Color::Always, true, false, true),
+            (Color::Never, false, true, false),
+            (Color::Never, false, false, false),
+            (Color::Never, true, true, false),
+            (Color::Never, true, false, false),
+        ];
+        for (when, no_color, is_terminal, colored) in matrix {
+            assert_eq!(colored, use_color(when, no_color, is_terminal));
+        }
+
+        // errors go by the same flags, with whether stderr is a terminal
+        // (flags, NO_COLOR is set, stderr is a terminal, colored)
+        let matrix: [(&[&str], bool, bool, bool); 6] = [
+            (&[], false, true, true),
+            (&[], false, false, false),
+            (&[], true, true, false),
+            (&["-M"], false, true, false),
+            (&["--color", "never"], false, true, false),
+            (&["--color", "always"], true, false, true),
+        ];
+        for (flags, no_color, is_terminal, colored) in matrix {
+            let args = ["hq"].iter().chain(flags).chain(&["."]);
+            let args = Args::try_parse_from(args).expect("args should parse");
+            assert_eq!(
+                colored,
+                use_color(color_when(&args.read), no_color, is_terminal),
+                "{flags:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn null_input_without_filter() {
+        // `-n` uses the default filter, like reading a file does
+        let args = Args::try_parse_from(["hq", "-n"]).expect("args should parse");
+        assert!(args.read.null_input);
+        assert_eq!(None, args.read.filter);
+    }
+
+    #[test]
+    fn plain_errors() {
+        let e = hq_rs::parse_filter(".foo[").expect_err("filter should not parse");
+        let rendered = e.render(false);
+        assert!(rendered.contains("failed to parse filter"));
+        assert!(!rendered.contains('\x1b'), "{rendered}");
+        assert!(e.render(true).contains('\x1b'));
+    }
+
+    #[test]
+    fn monochrome_output() {
+        let args = Args::try_parse_from(["hq", "-M", "."]).expect("args should parse");
+        assert!(args.read.monochrome_output);
+        // -M is the same as `--color never`, so giving both is a conflict
+        assert!(Args::try_parse_from(["hq", "-M", "--color", "always", "."]).is_err());
+    }
+
+    #[test]
+    fn version_names_hcl_crates() {
+        let version = Args::command().render_version();
+        assert!(version.starts_with(&format!("hq-rs {} (", env!("CARGO_PKG_VERSION"))));
+        assert!(version.contains("hcl-rs 0.18."));
+        assert!(version.contains("hcl-edit 0.8."));
+    }
+
+    #[test]
+    fn completions_cover_flags() {
+        let args = Args::try_parse_from(["hq", "completions", "fish"]).expect("args should parse");
+        assert!(matches!(
+            args.command,
+            Some(Command::Completions { shell: Shell::Fish })
+        ));
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let mut script = Vec::new();
+            completions(shell, &mut script);
+            let script = String::from_utf8(script).expect("script should be UTF-8");
+            for flag in ["output", "raw-output", "slurp", "inline"] {
+                assert!(
+                    script.contains(flag),
+                    "{shell} completions are missing --{flag}"
+                );
+            }
+        }
+    }
+}
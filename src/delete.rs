@@ -7,7 +7,7 @@ use hcl_edit::{
     visit_mut::VisitMut,
 };
 
-use crate::parser::Field;
+use crate::parser::{split_keys, unsupported, Field};
 
 struct HclDeleter {
     fields: Vec<Field>,
@@ -56,16 +56,14 @@ impl VisitMut for HclDeleter {
                     }
                     Structure::Block(block) => {
                         if block.ident.as_str() == curr.name {
-                            if curr.labels.is_empty() {
+                            let labels: Vec<_> =
+                                block.labels.iter().map(|label| label.as_str()).collect();
+                            if curr.labels.len() > labels.len() {
+                                self.error = Some("filter has more labels than the block".into());
+                                return;
+                            }
+                            if curr.matches_labels(&labels) {
                                 matching_block_idents.push(block.ident.to_string());
-                            } else {
-                                for filter_label in &curr.labels {
-                                    for block_label in &block.labels {
-                                        if block_label.as_str() == filter_label {
-                                            matching_block_idents.push(block.ident.to_string());
-                                        }
-                                    }
-                                }
                             }
                         }
                     }
@@ -138,6 +136,10 @@ impl VisitMut for HclDeleter {
 
 /// given a vector of [`Field`]s, delete the [`Expression`] value that matches that filter
 pub fn delete(fields: Vec<Field>, body: &mut Body) -> Result<(), Box<dyn Error>> {
+    if let Some(what) = unsupported(&fields) {
+        return Err(format!("deleting {what} is not supported").into());
+    }
+    let fields = split_keys(fields);
     let mut visitor = HclDeleter::new(fields);
     visitor.visit_body_mut(body);
     if let Some(err) = visitor.error {
@@ -0,0 +1,368 @@
+//! the error type for the top-level [`eval`][crate::eval] API, and a
+//! machine-readable report of it
+
+use std::{
+    error::Error,
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use annotate_snippets::{Level, Renderer, Snippet};
+
+use crate::{
+    filter::error::FilterError,
+    query::{
+        label_on_attribute_reason, type_mismatch_reason, QueryError, QueryErrorKind, TypeMismatch,
+    },
+};
+
+/// whether errors are rendered with colors, see [`set_colored`]
+static COLORED: AtomicBool = AtomicBool::new(true);
+
+/// render errors (e.g. a [`FilterError`] or [`HclParseError`]) with colors,
+/// which is the default, or without them, e.g. when `NO_COLOR` is set or
+/// stderr isn't a terminal
+pub fn set_colored(colored: bool) {
+    COLORED.store(colored, Ordering::Relaxed);
+}
+
+/// whether errors are rendered with colors, unless they were turned off with
+/// [`set_colored`]
+pub(crate) fn colored() -> bool {
+    COLORED.load(Ordering::Relaxed)
+}
+
+/// the renderer for error snippets, with or without colors
+pub(crate) fn renderer(colored: bool) -> Renderer {
+    match colored {
+        true => Renderer::styled(),
+        false => Renderer::plain(),
+    }
+}
+
+#[derive(Debug)]
+pub enum HqError {
+    /// the HCL source has a syntax error
+    HclParse(Box<HclParseError>),
+    /// the HCL source could not be read for some other reason
+    Hcl(hcl::Error),
+    /// the HCL source is in the JSON syntax, but isn't valid JSON
+    Json(serde_json::Error),
+    /// the filter could not be parsed
+    Filter(Box<FilterError>),
+    /// the filter could not be applied to the HCL
+    Query(Box<QueryError>),
+    /// a segment of the filter was applied to the wrong kind of value, e.g.
+    /// `.region[0]` where `region` is a string
+    TypeMismatch {
+        /// where the value is, written as a filter (e.g. `.region`)
+        path: String,
+        /// the segment of the filter that was applied to it (e.g. `[0]`)
+        segment: String,
+        /// the kinds of value the segment applies to (e.g. `a list`)
+        expected: String,
+        /// the kind of value it was applied to (e.g. `a string`)
+        found: String,
+    },
+    /// a filter matched block labels on an attribute, e.g. `.tags{"Env"}`
+    /// where `tags` is an attribute, which was probably meant to be the key
+    /// lookup `.tags["Env"]`
+    LabelOnAttribute {
+        /// where the attribute is, written as a filter (e.g. `.tags`)
+        path: String,
+        /// the first label in the filter (e.g. `Env`)
+        label: String,
+    },
+}
+
+impl fmt::Display for HqError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HclParse(e) => write!(f, "{e}"),
+            Self::Hcl(e) => write!(f, "failed to parse HCL: {e}"),
+            Self::Json(e) => write!(f, "failed to parse HCL JSON: {e}"),
+            Self::Filter(e) => write!(f, "{e}"),
+            Self::Query(e) => write!(f, "{e}"),
+            Self::TypeMismatch {
+                path,
+                segment,
+                expected,
+                found,
+            } => {
+                let mismatch = TypeMismatch {
+                    path: path.clone(),
+                    segment: segment.clone(),
+                    expected: expected.clone(),
+                    found: found.clone(),
+                };
+                write!(f, "{mismatch}")
+            }
+            Self::LabelOnAttribute { path, label } => write!(
+                f,
+                "failed to query HCL: {}",
+                label_on_attribute_reason(path, label)
+            ),
+        }
+    }
+}
+
+impl Error for HqError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::HclParse(e) => Some(e.as_ref()),
+            Self::Hcl(e) => Some(e),
+            Self::Json(e) => Some(e),
+            Self::Filter(e) => Some(e.as_ref()),
+            Self::Query(e) => Some(e.as_ref()),
+            Self::TypeMismatch { .. } | Self::LabelOnAttribute { .. } => None,
+        }
+    }
+}
+
+impl From<hcl::Error> for HqError {
+    fn from(value: hcl::Error) -> Self {
+        match value {
+            hcl::Error::Parse(e) => Self::from(e),
+            e => Self::Hcl(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for HqError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+impl From<hcl_edit::parser::Error> for HqError {
+    fn from(value: hcl_edit::parser::Error) -> Self {
+        Self::HclParse(Box::new(HclParseError::new(&value)))
+    }
+}
+
+impl From<Box<FilterError>> for HqError {
+    fn from(value: Box<FilterError>) -> Self {
+        Self::Filter(value)
+    }
+}
+
+impl From<Box<QueryError>> for HqError {
+    fn from(value: Box<QueryError>) -> Self {
+        match value.into_kind() {
+            Ok(QueryErrorKind::TypeMismatch(mismatch)) => Self::TypeMismatch {
+                path: mismatch.path,
+                segment: mismatch.segment,
+                expected: mismatch.expected,
+                found: mismatch.found,
+            },
+            Ok(QueryErrorKind::LabelOnAttribute { path, label }) => {
+                Self::LabelOnAttribute { path, label }
+            }
+            Err(e) => Self::Query(Box::new(e)),
+        }
+    }
+}
+
+/// a syntax error in HCL source, with where it is
+///
+/// it renders the offending line with a caret under where parsing failed
+pub struct HclParseError {
+    file: Option<String>,
+    line: usize,
+    column: usize,
+    source_line: String,
+    message: String,
+}
+
+impl HclParseError {
+    pub fn new(error: &hcl_edit::parser::Error) -> Self {
+        HclParseError {
+            file: None,
+            line: error.location().line(),
+            column: error.location().column(),
+            source_line: error.line().to_string(),
+            message: error.message().to_string(),
+        }
+    }
+
+    /// name the file the HCL came from when the error is rendered
+    pub fn with_file(mut self, file: &str) -> Self {
+        self.file = Some(file.to_string());
+        self
+    }
+
+    /// the file the HCL came from, if it was named with [`Self::with_file`]
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// the line (starting from 1) where parsing failed
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// the column (starting from 1) where parsing failed
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// what the parser expected to find
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl HclParseError {
+    /// the error with the line it is on, with colors or without them
+    pub fn render(&self, colored: bool) -> String {
+        // the column counts characters, but the annotation is a byte range
+        let pos = self
+            .source_line
+            .char_indices()
+            .nth(self.column.saturating_sub(1))
+            .map_or(self.source_line.len(), |(i, _)| i);
+        let end = self.source_line[pos..]
+            .chars()
+            .next()
+            .map_or(pos, |c| pos + c.len_utf8());
+
+        let mut snippet = Snippet::source(&self.source_line)
+            .line_start(self.line)
+            .annotation(Level::Error.span(pos..end).label(&self.message));
+        if let Some(ref file) = self.file {
+            snippet = snippet.origin(file);
+        }
+        let message = Level::Error.title("failed to parse HCL").snippet(snippet);
+
+        renderer(colored).render(message).to_string()
+    }
+}
+
+/// rendered with colors unless they were turned off with [`set_colored`]
+impl fmt::Debug for HclParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(colored()))
+    }
+}
+
+impl fmt::Display for HclParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for HclParseError {}
+
+/// an error described for tools to read (e.g. an editor pointing at where a
+/// filter or HCL is wrong), with the same fields for every kind of error
+///
+/// [`to_json`][Self::to_json] writes it as a JSON object, e.g.
+/// `{"kind":"filter_parse","message":"unexpected token","line":1,"column":6,"file":null}`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorReport {
+    /// what went wrong: `filter_parse` for a filter that couldn't be parsed,
+    /// `hcl_parse` for HCL that couldn't be read, `query` for a filter that
+    /// couldn't be applied, or `other` for anything else
+    pub kind: &'static str,
+    /// what went wrong, in a sentence
+    pub message: String,
+    /// the line (starting from 1) in the filter or HCL where it went wrong
+    pub line: Option<usize>,
+    /// the column (starting from 1) in the filter or HCL where it went wrong
+    pub column: Option<usize>,
+    /// the file the HCL came from
+    pub file: Option<String>,
+}
+
+impl ErrorReport {
+    /// a report of an error that isn't one of this crate's
+    pub fn other(error: &dyn Error) -> Self {
+        ErrorReport {
+            kind: "other",
+            message: error.to_string(),
+            line: None,
+            column: None,
+            file: None,
+        }
+    }
+
+    /// the report as a JSON object on a single line
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "kind": self.kind,
+            "message": self.message,
+            "line": self.line,
+            "column": self.column,
+            "file": self.file,
+        })
+        .to_string()
+    }
+
+    fn message(kind: &'static str, message: String) -> Self {
+        ErrorReport {
+            kind,
+            message,
+            line: None,
+            column: None,
+            file: None,
+        }
+    }
+}
+
+impl From<&HclParseError> for ErrorReport {
+    fn from(value: &HclParseError) -> Self {
+        ErrorReport {
+            kind: "hcl_parse",
+            message: value.message().to_string(),
+            line: Some(value.line()),
+            column: Some(value.column()),
+            file: value.file().map(str::to_string),
+        }
+    }
+}
+
+impl From<&FilterError> for ErrorReport {
+    fn from(value: &FilterError) -> Self {
+        ErrorReport {
+            kind: "filter_parse",
+            message: value.message().to_string(),
+            line: Some(value.line()),
+            column: Some(value.column()),
+            file: None,
+        }
+    }
+}
+
+impl From<&QueryError> for ErrorReport {
+    fn from(value: &QueryError) -> Self {
+        ErrorReport::message("query", value.reason().to_string())
+    }
+}
+
+impl From<&HqError> for ErrorReport {
+    fn from(value: &HqError) -> Self {
+        match value {
+            HqError::HclParse(e) => Self::from(e.as_ref()),
+            HqError::Hcl(e) => Self::message("hcl_parse", e.to_string()),
+            HqError::Json(e) => ErrorReport {
+                line: Some(e.line()),
+                column: Some(e.column()),
+                ..Self::message("hcl_parse", e.to_string())
+            },
+            HqError::Filter(e) => Self::from(e.as_ref()),
+            HqError::Query(e) => Self::from(e.as_ref()),
+            HqError::TypeMismatch {
+                path,
+                segment,
+                expected,
+                found,
+            } => Self::message(
+                "query",
+                type_mismatch_reason(path, segment, expected, found),
+            ),
+            HqError::LabelOnAttribute { path, label } => {
+                Self::message("query", label_on_attribute_reason(path, label))
+            }
+        }
+    }
+}
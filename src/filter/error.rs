@@ -1,60 +1,146 @@
-use core::fmt;
+use core::{fmt, ops::Range};
 
-use annotate_snippets::{Level, Renderer, Snippet};
-use pest::{error::InputLocation, RuleType};
+use annotate_snippets::{Level, Snippet};
+use pest::{
+    error::{ErrorVariant, InputLocation, LineColLocation},
+    Span,
+};
 
-pub struct FilterError<R> {
-    parsing_error: pest::error::Error<R>,
+use super::parser::Rule;
+
+/// a filter that could not be parsed
+///
+/// this doesn't expose the parser's own types, so it can be matched on (with
+/// [`kind`][Self::kind]) and rendered (with [`span`][Self::span] and
+/// [`message`][Self::message], or its `Display`) without depending on them
+pub struct FilterError {
+    kind: FilterErrorKind,
+    parsing_error: pest::error::Error<Rule>,
 }
 
-impl<R> fmt::Debug for FilterError<R>
-where
-    R: RuleType,
-{
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let input = self.parsing_error.line();
-        let pos = match self.parsing_error.location {
+/// what was wrong with a filter that could not be parsed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FilterErrorKind {
+    /// the filter isn't valid syntax, e.g. `.foo[`
+    UnexpectedToken,
+    /// a name starts with a digit, e.g. `.00asdf`
+    InvalidName,
+    /// a whole filter is followed by something that isn't part of it, e.g. the
+    /// `bar` in `.foo bar`
+    TrailingInput,
+    /// the pattern in `.~"pattern"` isn't a valid regex
+    InvalidRegex,
+    /// a quoted name or label has an invalid escape sequence, e.g. `\uD800`
+    InvalidEscape,
+    /// a number in a predicate or an index can't be represented
+    InvalidNumber,
+}
+
+impl FilterError {
+    /// an error for a filter that isn't valid syntax
+    pub(crate) fn syntax(parsing_error: pest::error::Error<Rule>) -> Box<Self> {
+        Box::new(FilterError {
+            kind: FilterErrorKind::UnexpectedToken,
+            parsing_error,
+        })
+    }
+
+    /// an error of `kind` pointing at `span`
+    pub(crate) fn new(kind: FilterErrorKind, message: String, span: Span<'_>) -> Box<Self> {
+        Box::new(FilterError {
+            kind,
+            parsing_error: pest::error::Error::new_from_span(
+                ErrorVariant::CustomError { message },
+                span,
+            ),
+        })
+    }
+
+    /// what was wrong with the filter
+    pub fn kind(&self) -> FilterErrorKind {
+        self.kind
+    }
+
+    /// the byte range in the filter where parsing failed
+    ///
+    /// the range is empty when the error is at a single position
+    pub fn span(&self) -> Range<usize> {
+        match self.parsing_error.location {
             InputLocation::Pos(pos) => pos..pos,
             InputLocation::Span((start, end)) => start..end,
-        };
+        }
+    }
 
-        let message = Level::Error.title("failed to parse filter").snippet(
-            Snippet::source(input).annotation(Level::Error.span(pos).label("unexpected token")),
-        );
+    /// the line (starting from 1) in the filter where parsing failed
+    pub fn line(&self) -> usize {
+        self.line_col().0
+    }
+
+    /// the column (starting from 1) in the filter where parsing failed
+    pub fn column(&self) -> usize {
+        self.line_col().1
+    }
 
-        let renderer = Renderer::styled();
-        let rendered = renderer.render(message);
+    fn line_col(&self) -> (usize, usize) {
+        match self.parsing_error.line_col {
+            LineColLocation::Pos(pos) | LineColLocation::Span(pos, _) => pos,
+        }
+    }
 
-        write!(f, "{}", rendered)
+    /// [`span`][Self::span] as a range in the line the error is on, cut off
+    /// at the end of the line
+    fn line_span(&self) -> Range<usize> {
+        let line = self.parsing_error.line();
+        let start = line
+            .char_indices()
+            .nth(self.column() - 1)
+            .map_or(line.len(), |(i, _)| i);
+        let end = (start + self.span().len()).min(line.len());
+        start..end.max(start)
+    }
+
+    /// a short description of what went wrong
+    pub fn message(&self) -> &str {
+        match self.parsing_error.variant {
+            ErrorVariant::ParsingError { .. } => "unexpected token",
+            ErrorVariant::CustomError { ref message } => message,
+        }
     }
 }
 
-impl<R> fmt::Display for FilterError<R>
-where
-    R: RuleType,
-{
+/// rendered with colors unless they were turned off with
+/// [`set_colored`][crate::error::set_colored]
+impl fmt::Debug for FilterError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        write!(f, "{}", self.render(crate::error::colored()))
     }
 }
 
-impl<R> std::error::Error for FilterError<R> where R: RuleType {}
-
-impl<R> From<pest::error::Error<R>> for Box<FilterError<R>>
-where
-    R: RuleType,
-{
-    fn from(value: pest::error::Error<R>) -> Self {
-        match value.variant {
-            pest::error::ErrorVariant::ParsingError {
-                positives: _,
-                negatives: _,
-            } => Box::new(FilterError {
-                parsing_error: value.clone(),
-            }),
-            _ => {
-                unimplemented!()
-            }
-        }
+impl FilterError {
+    /// the error with the line of the filter it is on, with colors or without
+    /// them
+    pub fn render(&self, colored: bool) -> String {
+        // only the line the error is on is kept, so the span is made relative
+        // to its start
+        let input = self.parsing_error.line();
+        let pos = self.line_span();
+        let label = self.message();
+
+        let message = Level::Error.title("failed to parse filter").snippet(
+            Snippet::source(input)
+                .line_start(self.line())
+                .annotation(Level::Error.span(pos).label(label)),
+        );
+
+        crate::error::renderer(colored).render(message).to_string()
     }
 }
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for FilterError {}
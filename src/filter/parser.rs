@@ -1,76 +1,968 @@
-use pest::Parser;
+use std::fmt;
+
+use pest::{
+    error::{Error, ErrorVariant, InputLocation},
+    iterators::Pair,
+    Parser, Span,
+};
 use pest_derive::Parser;
+use regex::Regex;
+use unicode_ident::is_xid_continue;
 
-use super::error::FilterError;
+use super::error::{FilterError, FilterErrorKind};
 
 #[derive(Parser)]
 #[grammar = "filter/grammar.pest"]
 pub struct Filter {}
 
-/// one segment of a filter ([`parse_filter`] returns `Vec<Field>`)
+/// how [`Label::Any`] is written in a filter (`.resource{*,"web"}`)
+pub const WILDCARD_LABEL: &str = "*";
+
+/// the name of a [`Field::wildcard`], which is how it is written in a filter
+/// (`.*`)
+pub const WILDCARD_NAME: &str = "*";
+
+/// a block label in a [`Field`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Label {
+    /// matches any label in its position (`*`)
+    Any,
+    /// matches the label that is spelled the same (`"web"`), even if that is
+    /// `"*"`
+    Exact(String),
+}
+
+impl Label {
+    /// whether this label matches a block's `label`
+    pub fn matches(&self, label: &str) -> bool {
+        match self {
+            Label::Any => true,
+            Label::Exact(exact) => exact == label,
+        }
+    }
+}
+
+impl From<&str> for Label {
+    fn from(label: &str) -> Self {
+        Label::Exact(label.to_string())
+    }
+}
+
+/// one segment of a filter path (see [`Stage`])
 ///
-/// e.g. for the filter `'.foo{"bar"}.baz'` there are two segments:
+/// e.g. for the filter `'.foo{"bar"}.baz[-1]'` there are two segments:
 ///
 /// * the name "foo" and the label "bar"
-/// * the name "baz"
-#[derive(Clone, Debug, PartialEq)]
+/// * the name "baz" and the selector for index -1
+#[derive(Clone, Debug)]
 pub struct Field {
     /// an attribute or block name
     pub name: String,
+    /// whether the field matches every name (`.*`), in which case `name` is
+    /// [`WILDCARD_NAME`]
+    pub wildcard: bool,
+    /// a regex that names are matched with instead of `name` (`.~"^aws_"`), in
+    /// which case `name` is the regex as it was written in the filter
+    pub pattern: Option<Regex>,
     /// block labels
-    pub labels: Vec<String>,
+    pub labels: Vec<Label>,
+    /// object key to look up in the matched attribute (`.name["key"]`)
+    pub key: Option<String>,
+    /// list element selector
+    pub selector: Option<Selector>,
+    /// condition that matches must meet to be kept (`.name[.attr == "value"]`)
+    pub predicate: Option<Predicate>,
+    /// function applied to the input instead of matching `name` (`keys`)
+    pub builtin: Option<Builtin>,
+    /// whether to match at any depth (`..name`) instead of only the next level
+    pub recursive: bool,
+    /// whether a missing list element is ignored (`.name[5]?`) instead of an error
+    pub optional: bool,
+    /// whether the name was written in brackets (`["name"]`), in which case the
+    /// field matches the next label of a block the previous field matched, if
+    /// it has labels left (so `["resource"]["aws_instance"]` is the same as
+    /// `.resource{"aws_instance"}`), and a name in it otherwise
+    pub bracketed: bool,
+    /// the kind of value that matches must be (`.*:string`), which only a
+    /// wildcard name has
+    pub value_type: Option<ValueType>,
+    /// the start and end byte offsets of the segment in the filter it was
+    /// parsed from, `(0, 0)` for a field that wasn't parsed
+    pub span: (usize, usize),
+}
+
+/// fields are equal if they match the same things, wherever they were written
+impl PartialEq for Field {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.wildcard == other.wildcard
+            && self.pattern.as_ref().map(Regex::as_str) == other.pattern.as_ref().map(Regex::as_str)
+            && self.labels == other.labels
+            && self.key == other.key
+            && self.selector == other.selector
+            && self.predicate == other.predicate
+            && self.builtin == other.builtin
+            && self.recursive == other.recursive
+            && self.optional == other.optional
+            && self.bracketed == other.bracketed
+            && self.value_type == other.value_type
+    }
 }
 
 impl Field {
     pub fn new(name: &str) -> Self {
         Field {
             name: name.to_string(),
+            wildcard: false,
+            pattern: None,
             labels: Vec::new(),
+            key: None,
+            selector: None,
+            predicate: None,
+            builtin: None,
+            recursive: false,
+            optional: false,
+            bracketed: false,
+            value_type: None,
+            span: (0, 0),
         }
     }
 
     pub fn labeled(name: &str, labels: &[&str]) -> Self {
         Field {
             name: name.to_string(),
-            labels: labels.iter().map(|label| label.to_string()).collect(),
+            wildcard: false,
+            pattern: None,
+            labels: labels.iter().map(|&label| Label::from(label)).collect(),
+            key: None,
+            selector: None,
+            predicate: None,
+            builtin: None,
+            recursive: false,
+            optional: false,
+            bracketed: false,
+            value_type: None,
+            span: (0, 0),
+        }
+    }
+
+    pub fn indexed(name: &str, index: usize) -> Self {
+        Field {
+            name: name.to_string(),
+            wildcard: false,
+            pattern: None,
+            labels: Vec::new(),
+            key: None,
+            selector: Some(Selector::Index(Index::FromStart(index))),
+            predicate: None,
+            builtin: None,
+            recursive: false,
+            optional: false,
+            bracketed: false,
+            value_type: None,
+            span: (0, 0),
+        }
+    }
+
+    pub fn keyed(name: &str, key: &str) -> Self {
+        Field {
+            name: name.to_string(),
+            wildcard: false,
+            pattern: None,
+            labels: Vec::new(),
+            key: Some(key.to_string()),
+            selector: None,
+            predicate: None,
+            builtin: None,
+            recursive: false,
+            optional: false,
+            bracketed: false,
+            value_type: None,
+            span: (0, 0),
+        }
+    }
+
+    /// a field whose name matches `pattern`
+    pub fn matching(pattern: Regex) -> Self {
+        Field {
+            pattern: Some(pattern.clone()),
+            ..Field::new(&pattern_name(&pattern))
+        }
+    }
+
+    /// a field that matches every attribute, block, and object key (`.*`)
+    pub fn wildcard() -> Self {
+        Field {
+            wildcard: true,
+            ..Field::new(WILDCARD_NAME)
+        }
+    }
+
+    pub fn builtin(builtin: Builtin) -> Self {
+        Field {
+            builtin: Some(builtin),
+            ..Field::new(builtin.name())
         }
     }
+
+    /// the field with the byte offsets of where it is in a filter
+    pub fn with_span(self, start: usize, end: usize) -> Self {
+        Field {
+            span: (start, end),
+            ..self
+        }
+    }
+
+    /// whether a block with `labels` matches this field's labels
+    ///
+    /// the block's labels must start with this field's labels, so `{"a"}`
+    /// matches a block labeled `"a" "b"` but `{"b"}` does not (`{*,"b"}`
+    /// does)
+    pub fn matches_labels(&self, labels: &[&str]) -> bool {
+        self.labels.len() <= labels.len()
+            && self
+                .labels
+                .iter()
+                .zip(labels)
+                .all(|(filter_label, label)| filter_label.matches(label))
+    }
 }
 
-/// parse `input` and return a vector of [`Field`]s
+/// what about `fields` can't be written to or deleted, if anything, to follow
+/// "writing" or "deleting" in an error (e.g. "with predicates")
 ///
-/// a valid filter is one or more chained segments
-pub fn parse_filter(input: &str) -> Result<Vec<Field>, Box<FilterError<Rule>>> {
-    let mut fields = Vec::new();
-    let pairs = Filter::parse(Rule::filter, input)?;
+/// this is used when modifying HCL, where only names, labels, and keys can be
+/// looked up
+pub(crate) fn unsupported(fields: &[Field]) -> Option<&'static str> {
+    if fields.is_empty() {
+        return Some("the whole document");
+    }
+    let any = |check: fn(&Field) -> bool| fields.iter().any(check);
+    if any(|field| field.selector.is_some()) {
+        Some("a list index")
+    } else if any(|field| field.predicate.is_some()) {
+        Some("with predicates")
+    } else if any(|field| field.builtin.is_some()) {
+        Some("with builtins")
+    } else if any(|field| field.recursive) {
+        Some("with recursive descent")
+    } else if any(|field| field.wildcard) {
+        Some("with wildcard names")
+    } else if any(|field| field.pattern.is_some()) {
+        Some("with regex names")
+    } else if any(|field| field.value_type.is_some()) {
+        Some("with value types")
+    } else {
+        None
+    }
+}
+
+/// move each [`Field`]'s object key into a [`Field`] of its own, so
+/// `.tags["Env"]` becomes `.tags.Env`
+///
+/// this is used when modifying HCL, where the next field always matches
+/// object keys
+pub(crate) fn split_keys(fields: Vec<Field>) -> Vec<Field> {
+    let mut split = Vec::new();
+    for mut field in fields {
+        let key = field.key.take();
+        split.push(field);
+        if let Some(key) = key {
+            split.push(Field::new(&key));
+        }
+    }
+    split
+}
+
+/// which element(s) of a list to select
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selector {
+    /// a single element, e.g. `[1]` or `[-1]`
+    Index(Index),
+    /// a half-open range of elements, e.g. `[1:3]`, `[:2]` or `[2:]`
+    ///
+    /// the result is itself a list
+    Slice {
+        start: Option<Index>,
+        end: Option<Index>,
+    },
+    /// several elements in the order they're listed, e.g. `[0,2]` or `[-1,0]`
+    ///
+    /// each element is a separate result, and an element can be listed more
+    /// than once
+    Indices(Vec<Index>),
+    /// every element, e.g. `[]`
+    ///
+    /// each element is a separate result
+    Iterate,
+}
+
+/// a function that can start a path, applied to each of its inputs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Builtin {
+    /// a sorted list of the names of a block's (or the document's) attributes
+    /// and blocks, the keys of an object, or the indices of a list
+    Keys,
+    /// each value of an object, element of a list, or attribute value and
+    /// block in a block (or the document), as a separate result
+    Values,
+    /// the number of attributes and blocks in a block, keys in an object,
+    /// elements in a list, or characters in a string (null has a length of 0)
+    Length,
+    /// the name of the kind of value the input is: `"string"`, `"number"`,
+    /// `"bool"`, `"list"`, `"object"` (including blocks), `"null"`, or
+    /// `"expression"` for anything that isn't evaluated (e.g. a reference)
+    Type,
+    /// the identifier and labels of each labeled block in a block (or the
+    /// document) that is defined more than once, e.g.
+    /// `[["resource", "aws_instance", "web"]]`
+    ///
+    /// only the blocks directly in the input are compared, not the blocks
+    /// nested inside of them
+    Duplicates,
+    /// a string encoded as base64 (with the standard alphabet and padding)
+    Base64,
+    /// a base64 string decoded, ignoring any whitespace in it
+    Base64Decode,
+    /// the first result of the stages before it (`.foo[] | first`), or nothing
+    /// if there are none
+    First,
+    /// the last result of the stages before it (`.foo[] | last`), or nothing
+    /// if there are none
+    Last,
+    /// a list of the paths to every attribute, block, object entry, and list
+    /// element in the input, each written as a filter that matches it
+    Paths,
+    /// like [`Builtin::Paths`], but only the paths to values that aren't
+    /// blocks, objects, or lists
+    LeafPaths,
+    /// the elements of a list added together: numbers are summed, strings and
+    /// lists are concatenated, and nulls are skipped (an empty list adds up to
+    /// null)
+    Add,
+    /// the elements of a list, with the elements of any lists in it (this
+    /// many levels deep) in their place, e.g. `flatten` for `[[1], [2, [3]]]`
+    /// is `[1, 2, [3]]` and `flatten(2)` is `[1, 2, 3]`
+    Flatten(usize),
+}
+
+impl Builtin {
+    /// every builtin
+    pub const ALL: [Builtin; 13] = [
+        Builtin::Keys,
+        Builtin::Values,
+        Builtin::Length,
+        Builtin::Type,
+        Builtin::Duplicates,
+        Builtin::Base64,
+        Builtin::Base64Decode,
+        Builtin::First,
+        Builtin::Last,
+        Builtin::Paths,
+        Builtin::LeafPaths,
+        Builtin::Add,
+        Builtin::Flatten(1),
+    ];
+
+    /// the name used to call the builtin in a filter
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::Keys => "keys",
+            Builtin::Values => "values",
+            Builtin::Length => "length",
+            Builtin::Type => "type",
+            Builtin::Duplicates => "duplicates",
+            Builtin::Base64 => "@base64",
+            Builtin::Base64Decode => "@base64d",
+            Builtin::First => "first",
+            Builtin::Last => "last",
+            Builtin::Paths => "paths",
+            Builtin::LeafPaths => "leaf_paths",
+            Builtin::Add => "add",
+            Builtin::Flatten(_) => "flatten",
+        }
+    }
+
+    /// whether the builtin is applied to all of the results of the stages
+    /// before it together, rather than to each of them
+    pub fn is_stream(&self) -> bool {
+        matches!(self, Builtin::First | Builtin::Last)
+    }
+}
+
+/// the builtin as it's written in a filter, with its argument if it has one
+/// that isn't the default (e.g. `flatten(2)`)
+impl fmt::Display for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Builtin::Flatten(depth) if *depth != 1 => write!(f, "flatten({depth})"),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+/// a kind of value, by the name that the `type` builtin gives it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueType {
+    String,
+    Number,
+    Bool,
+    List,
+    /// an object, or a block (or the document)
+    Object,
+    Null,
+    /// anything that isn't evaluated, e.g. a reference
+    Expression,
+}
+
+impl ValueType {
+    /// every kind of value
+    pub const ALL: [ValueType; 7] = [
+        ValueType::String,
+        ValueType::Number,
+        ValueType::Bool,
+        ValueType::List,
+        ValueType::Object,
+        ValueType::Null,
+        ValueType::Expression,
+    ];
+
+    /// the name of the kind of value, as `type` gives it and `.*:type` is
+    /// written
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueType::String => "string",
+            ValueType::Number => "number",
+            ValueType::Bool => "bool",
+            ValueType::List => "list",
+            ValueType::Object => "object",
+            ValueType::Null => "null",
+            ValueType::Expression => "expression",
+        }
+    }
+}
+
+/// a condition on a result, results that don't meet it are dropped
+///
+/// e.g. `.resource[.ami == "ami-123"]` keeps only the `resource` blocks with an
+/// `ami` attribute of `"ami-123"`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Predicate {
+    /// a value found by following `path` from the result compares to `value`
+    ///
+    /// the predicate holds if any value found by `path` does
+    Compare {
+        path: Vec<Field>,
+        comparison: Comparison,
+        value: Literal,
+    },
+    /// the result has an attribute, block, or object key with this name
+    /// (`[has("lifecycle")]`), whatever its value
+    Has(String),
+    /// the predicate does not hold (`[not has("tags")]` or `[!has("tags")]`)
+    ///
+    /// negation applies to the whole condition after it, so
+    /// `[!.port == 80]` is the opposite of `[.port == 80]`
+    Not(Box<Predicate>),
+}
+
+/// how a value is compared to a [`Literal`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    /// `==`
+    Equal,
+    /// `!=`, a value of the same type as the literal that isn't equal to it
+    NotEqual,
+    /// `<`, numbers are ordered by value and strings lexically
+    Less,
+    /// `<=`
+    LessOrEqual,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterOrEqual,
+    /// `contains`, a list has an element equal to the literal, or a string has
+    /// the literal as a substring
+    Contains,
+}
+
+/// a literal value in a filter
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    String(String),
+    Number(f64),
+    Bool(bool),
+    /// `$name`, which is replaced with the value bound to `name` in
+    /// [`QueryOptions::variables`][crate::QueryOptions::variables] when the
+    /// predicate is evaluated
+    Variable(String),
+}
+
+/// a position in a list
+///
+/// e.g. `[0]` is the first element and `[-1]` is the last element
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Index {
+    /// an offset from the start of the list (`[0]` is `FromStart(0)`)
+    FromStart(usize),
+    /// an offset from the end of the list (`[-1]` is `FromEnd(1)`)
+    FromEnd(usize),
+}
+
+impl Index {
+    /// resolve this index against a list of length `len`
+    ///
+    /// returns `None` if the index is out of bounds
+    pub fn resolve(&self, len: usize) -> Option<usize> {
+        match *self {
+            Index::FromStart(i) if i < len => Some(i),
+            Index::FromEnd(i) if (1..=len).contains(&i) => Some(len - i),
+            _ => None,
+        }
+    }
+
+    /// resolve this index as a slice bound against a list of length `len`
+    ///
+    /// bounds past either end of the list are clamped to that end
+    pub fn clamp(&self, len: usize) -> usize {
+        match *self {
+            Index::FromStart(i) => i.min(len),
+            Index::FromEnd(i) => len.saturating_sub(i),
+        }
+    }
+}
+
+/// written the way it is in a filter, e.g. `[1:3]`
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Selector::Index(index) => write!(f, "[{index}]"),
+            Selector::Slice { start, end } => {
+                write!(f, "[")?;
+                if let Some(start) = start {
+                    write!(f, "{start}")?;
+                }
+                write!(f, ":")?;
+                if let Some(end) = end {
+                    write!(f, "{end}")?;
+                }
+                write!(f, "]")
+            }
+            Selector::Indices(indices) => {
+                let indices: Vec<_> = indices.iter().map(Index::to_string).collect();
+                write!(f, "[{}]", indices.join(","))
+            }
+            Selector::Iterate => write!(f, "[]"),
+        }
+    }
+}
+
+impl fmt::Display for Index {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Index::FromStart(i) => write!(f, "{i}"),
+            Index::FromEnd(i) => write!(f, "-{i}"),
+        }
+    }
+}
+
+/// one stage of a filter ([`parse_filter`] returns `Vec<Stage>`)
+///
+/// e.g. for the filter `'.foo, .bar | .baz'` there are two stages:
+///
+/// * the paths `.foo` and `.bar`
+/// * the path `.baz`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stage {
+    /// comma-separated paths, each a vector of [`Field`]s
+    pub paths: Vec<Vec<Field>>,
+}
+
+impl Stage {
+    pub fn new(fields: Vec<Field>) -> Self {
+        Stage {
+            paths: vec![fields],
+        }
+    }
+}
+
+/// parse `input` and return a vector of [`Stage`]s
+///
+/// a valid filter is one or more stages separated by `|` (e.g.
+/// `'.foo | .bar'`), a stage is one or more comma-separated paths (e.g.
+/// `'.foo, .bar'`), and a path is one or more chained segments
+///
+/// all of `input` must be part of the filter, anything left over after it
+/// (e.g. the `bar` in `.foo bar`) is an error pointing at where it starts
+pub fn parse_filter(input: &str) -> Result<Vec<Stage>, Box<FilterError>> {
+    let mut stages = Vec::new();
+    let pairs = Filter::parse(Rule::filter, input).map_err(|e| {
+        digit_name_error(input, &e)
+            .or_else(|| trailing_input_error(input, &e))
+            .unwrap_or_else(|| FilterError::syntax(e))
+    })?;
     for pair in pairs {
+        if pair.as_rule() == Rule::stage {
+            stages.push(parse_stage(pair)?);
+        }
+    }
+    Ok(stages)
+}
+
+/// an error that explains what is wrong with a name that starts with a digit
+/// (e.g. `.00asdf`), in place of the generic pest error `err`
+fn digit_name_error(input: &str, err: &Error<Rule>) -> Option<Box<FilterError>> {
+    let InputLocation::Pos(pos) = err.location else {
+        return None;
+    };
+    let rest = &input[pos..];
+    if !input[..pos].ends_with('.') || !rest.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let name: String = rest
+        .chars()
+        .take_while(|c| is_xid_continue(*c) || *c == '-')
+        .collect();
+    let line_start = input[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let column = input[line_start..pos].chars().count() + 1;
+    // point at the offending character only
+    let span = Span::new(input, pos, pos + 1).expect("digit is a single byte");
+    Some(FilterError::new(
+        FilterErrorKind::InvalidName,
+        format!(
+            "field names must start with a letter or underscore; got '{name}' at column {column} (quote it to use it as is, e.g. .\"{name}\")"
+        ),
+        span,
+    ))
+}
+
+/// an error that points at what was left over after a valid filter followed
+/// by something that isn't part of it (e.g. `.foo bar`), in place of the
+/// generic pest error `err`
+fn trailing_input_error(input: &str, err: &Error<Rule>) -> Option<Box<FilterError>> {
+    let (InputLocation::Pos(pos), ErrorVariant::ParsingError { positives, .. }) =
+        (&err.location, &err.variant)
+    else {
+        return None;
+    };
+    // the end of the input would have been accepted here, so what came before
+    // is a whole filter
+    if !positives.contains(&Rule::EOI) {
+        return None;
+    }
+    let pos = *pos;
+    let rest = input[pos..].lines().next().unwrap_or_default().trim_end();
+    let line_start = input[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let column = input[line_start..pos].chars().count() + 1;
+    let span = Span::new(input, pos, pos + rest.len()).expect("rest is a suffix of the input");
+    Some(FilterError::new(
+        FilterErrorKind::TrailingInput,
+        format!(
+            "unexpected '{rest}' after the end of the filter at column {column} (separate paths with ',' and stages with '|')"
+        ),
+        span,
+    ))
+}
+
+fn parse_stage(pair: Pair<Rule>) -> Result<Stage, Box<FilterError>> {
+    let mut paths = Vec::new();
+    for pair in pair.into_inner() {
+        if pair.as_rule() == Rule::path {
+            paths.push(parse_path(pair)?);
+        }
+    }
+    Ok(Stage { paths })
+}
+
+fn parse_path(pair: Pair<Rule>) -> Result<Vec<Field>, Box<FilterError>> {
+    let mut fields = Vec::new();
+    for pair in pair.into_inner() {
         let mut name = String::new();
+        let mut pattern = None;
         let mut labels = Vec::new();
+        let mut key = None;
+        let mut selector = None;
+        let mut predicate = None;
+        let mut builtin = None;
+        let mut recursive = false;
+        let mut optional = false;
+        let mut bracketed = false;
+        let mut wildcard = false;
+        let mut value_type = None;
 
+        let span = (pair.as_span().start(), pair.as_span().end());
         let inner_pairs = pair.into_inner();
         for inner in inner_pairs {
             match inner.as_rule() {
+                Rule::optional => {
+                    optional = true;
+                }
+                Rule::descent => {
+                    recursive = true;
+                }
                 Rule::name => {
                     // according to clippy, this is a more efficient way of doing
                     // `name = inner.as_str().to_owned()`
                     inner.as_str().clone_into(&mut name);
                 }
+                Rule::index => {
+                    let quoted = inner.into_inner().next().expect("index has a name");
+                    name = unescape(&quoted)?;
+                    bracketed = true;
+                }
                 Rule::quoted_name => {
-                    inner.as_str().clone_into(&mut name);
+                    name = unescape(&inner)?;
+                }
+                Rule::any_name => {
+                    WILDCARD_NAME.clone_into(&mut name);
+                    wildcard = true;
+                }
+                Rule::value_type => {
+                    value_type = ValueType::ALL
+                        .into_iter()
+                        .find(|value_type| value_type.name() == inner.as_str());
+                }
+                Rule::pattern => {
+                    let regex = parse_pattern(&inner)?;
+                    name = pattern_name(&regex);
+                    pattern = Some(regex);
                 }
                 Rule::label => {
-                    labels.push(inner.as_str().to_owned());
+                    labels.push(Label::Exact(unescape(&inner)?));
+                }
+                Rule::wildcard => {
+                    labels.push(Label::Any);
+                }
+                Rule::key => {
+                    key = Some(unescape(&inner)?);
+                }
+                Rule::index_value => {
+                    selector = Some(Selector::Index(parse_index(inner)?));
+                }
+                Rule::index_list => {
+                    let indices = inner
+                        .into_inner()
+                        .map(parse_index)
+                        .collect::<Result<_, _>>()?;
+                    selector = Some(Selector::Indices(indices));
+                }
+                Rule::iterate => {
+                    selector = Some(Selector::Iterate);
+                }
+                Rule::predicate => {
+                    predicate = Some(parse_predicate(inner)?);
+                }
+                Rule::builtin => {
+                    let parsed = match inner.as_str() {
+                        "keys" => Builtin::Keys,
+                        "values" => Builtin::Values,
+                        "length" => Builtin::Length,
+                        "type" => Builtin::Type,
+                        "duplicates" => Builtin::Duplicates,
+                        "first" => Builtin::First,
+                        "last" => Builtin::Last,
+                        "paths" => Builtin::Paths,
+                        "leaf_paths" => Builtin::LeafPaths,
+                        "add" => Builtin::Add,
+                        "@base64" => Builtin::Base64,
+                        "@base64d" => Builtin::Base64Decode,
+                        // the grammar only allows known builtins
+                        _ => unreachable!(),
+                    };
+                    parsed.name().clone_into(&mut name);
+                    builtin = Some(parsed);
+                }
+                Rule::flatten => {
+                    let depth = match inner.into_inner().next() {
+                        Some(depth) => depth.as_str().parse().map_err(|_| {
+                            FilterError::new(
+                                FilterErrorKind::InvalidNumber,
+                                "depth is too large".to_string(),
+                                depth.as_span(),
+                            )
+                        })?,
+                        None => 1,
+                    };
+                    let parsed = Builtin::Flatten(depth);
+                    parsed.name().clone_into(&mut name);
+                    builtin = Some(parsed);
+                }
+                Rule::slice => {
+                    let mut start = None;
+                    let mut end = None;
+                    for bound in inner.into_inner() {
+                        match bound.as_rule() {
+                            Rule::slice_start => start = Some(parse_index(bound)?),
+                            Rule::slice_end => end = Some(parse_index(bound)?),
+                            _ => {}
+                        }
+                    }
+                    selector = Some(Selector::Slice { start, end });
                 }
                 _ => {}
             }
         }
         if !name.is_empty() {
-            fields.push(Field { name, labels });
+            fields.push(Field {
+                name,
+                wildcard,
+                pattern,
+                labels,
+                key,
+                selector,
+                predicate,
+                builtin,
+                recursive,
+                optional,
+                bracketed,
+                value_type,
+                span,
+            });
         }
     }
     Ok(fields)
 }
 
+/// the name of a field that matches `pattern`, written as it is in a filter
+fn pattern_name(pattern: &Regex) -> String {
+    format!("~\"{}\"", pattern.as_str().replace('"', "\\\""))
+}
+
+/// compile the regex in `.~"pattern"`, where `\"` is a quote and any other
+/// backslash is left for the regex
+fn parse_pattern(pair: &Pair<Rule>) -> Result<Regex, Box<FilterError>> {
+    let pattern = pair.as_str().replace("\\\"", "\"");
+    let regex = Regex::new(&pattern).map_err(|e| {
+        FilterError::new(
+            FilterErrorKind::InvalidRegex,
+            format!("invalid regex: {e}"),
+            pair.as_span(),
+        )
+    })?;
+    Ok(regex)
+}
+
+fn parse_predicate(pair: Pair<Rule>) -> Result<Predicate, Box<FilterError>> {
+    // the grammar requires exactly one condition
+    let condition = pair.into_inner().next().expect("predicate has a condition");
+    parse_condition(condition)
+}
+
+fn parse_condition(condition: Pair<Rule>) -> Result<Predicate, Box<FilterError>> {
+    match condition.as_rule() {
+        Rule::not => {
+            let negated = condition.into_inner().next().expect("not has a condition");
+            Ok(Predicate::Not(Box::new(parse_condition(negated)?)))
+        }
+        Rule::has => {
+            let name = condition.into_inner().next().expect("has() has a name");
+            Ok(Predicate::Has(unescape(&name)?))
+        }
+        _ => parse_compare(condition),
+    }
+}
+
+fn parse_compare(pair: Pair<Rule>) -> Result<Predicate, Box<FilterError>> {
+    let mut path = Vec::new();
+    let mut comparison = Comparison::Equal;
+    let mut value = None;
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::path => path = parse_path(inner)?,
+            Rule::comparison => {
+                comparison = match inner.as_str() {
+                    "!=" => Comparison::NotEqual,
+                    "<" => Comparison::Less,
+                    "<=" => Comparison::LessOrEqual,
+                    ">" => Comparison::Greater,
+                    ">=" => Comparison::GreaterOrEqual,
+                    "contains" => Comparison::Contains,
+                    _ => Comparison::Equal,
+                }
+            }
+            Rule::string_value => value = Some(Literal::String(unescape(&inner)?)),
+            Rule::number => {
+                let number = inner.as_str().parse().map_err(|_| {
+                    FilterError::new(
+                        FilterErrorKind::InvalidNumber,
+                        "invalid number".to_string(),
+                        inner.as_span(),
+                    )
+                })?;
+                value = Some(Literal::Number(number));
+            }
+            Rule::boolean => value = Some(Literal::Bool(inner.as_str() == "true")),
+            Rule::variable => {
+                let name = inner.into_inner().next().expect("variable has a name");
+                value = Some(Literal::Variable(name.as_str().to_string()));
+            }
+            _ => {}
+        }
+    }
+    // the grammar requires a literal
+    let value = value.expect("predicate has a literal");
+    Ok(Predicate::Compare {
+        path,
+        comparison,
+        value,
+    })
+}
+
+/// decode the escape sequences (`\"`, `\'`, `\\`, `\n`, `\t` and `\uXXXX`)
+/// in a quoted name or label
+fn unescape(pair: &Pair<Rule>) -> Result<String, Box<FilterError>> {
+    let mut unescaped = String::new();
+    let mut chars = pair.as_str().chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        // the grammar only allows valid escape sequences
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                let c = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| {
+                        FilterError::new(
+                            FilterErrorKind::InvalidEscape,
+                            format!("invalid unicode escape '\\u{hex}'"),
+                            pair.as_span(),
+                        )
+                    })?;
+                unescaped.push(c);
+            }
+            Some(c) => unescaped.push(c),
+            None => unreachable!(),
+        }
+    }
+    Ok(unescaped)
+}
+
+fn parse_index(pair: Pair<Rule>) -> Result<Index, Box<FilterError>> {
+    let s = pair.as_str();
+    let (offset, from_end) = match s.strip_prefix('-') {
+        Some(offset) => (offset, true),
+        None => (s, false),
+    };
+    let offset: usize = offset.parse().map_err(|_| {
+        FilterError::new(
+            FilterErrorKind::InvalidNumber,
+            "index is too large".to_string(),
+            pair.as_span(),
+        )
+    })?;
+    if from_end {
+        Ok(Index::FromEnd(offset))
+    } else {
+        Ok(Index::FromStart(offset))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -80,7 +972,7 @@ mod tests {
         let input = ".a_name";
         let expected = vec![Field::new("a_name")];
         let fields = parse_filter(input).expect("parse error");
-        assert_eq!(expected, fields);
+        assert_eq!(vec![Stage::new(expected)], fields);
     }
 
     #[test]
@@ -90,12 +982,142 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn name_starting_with_digit_error() {
+        let input = ".foo.00asdf";
+        let err = parse_filter(input).expect_err("filter should not parse");
+        assert_eq!(
+            "field names must start with a letter or underscore; got '00asdf' at column 6 (quote it to use it as is, e.g. .\"00asdf\")",
+            err.message()
+        );
+        assert_eq!(5..6, err.span());
+        assert_eq!(FilterErrorKind::InvalidName, err.kind());
+
+        // the column is counted from the start of the line
+        let input = ".a,\n.00asdf";
+        let err = parse_filter(input).expect_err("filter should not parse");
+        assert!(err.message().contains("got '00asdf' at column 2 "));
+        assert_eq!((2, 2), (err.line(), err.column()));
+        assert_eq!(5..6, err.span());
+    }
+
+    #[test]
+    fn trailing_input_error() {
+        let input = ".foo bar";
+        let err = parse_filter(input).expect_err("filter should not parse");
+        assert_eq!(
+            "unexpected 'bar' after the end of the filter at column 6 (separate paths with ',' and stages with '|')",
+            err.message()
+        );
+        assert_eq!(5..8, err.span());
+        assert_eq!(FilterErrorKind::TrailingInput, err.kind());
+
+        // the column is counted from the start of the line
+        let input = ".foo\n  | .bar ]";
+        let err = parse_filter(input).expect_err("filter should not parse");
+        assert!(err
+            .message()
+            .starts_with("unexpected ']' after the end of the filter at column 10"));
+        assert_eq!(14..15, err.span());
+    }
+
+    #[test]
+    fn filter_error_kinds() {
+        let kind = |input| {
+            parse_filter(input)
+                .expect_err("filter should not parse")
+                .kind()
+        };
+        assert_eq!(FilterErrorKind::UnexpectedToken, kind(".foo["));
+        assert_eq!(FilterErrorKind::InvalidRegex, kind(".~\"(\""));
+        assert_eq!(FilterErrorKind::InvalidEscape, kind(".\"\\uD800\""));
+        assert_eq!(
+            FilterErrorKind::InvalidNumber,
+            kind(".foo[99999999999999999999999]")
+        );
+    }
+
+    #[test]
+    fn multi_line_filter_errors() {
+        for (input, line, column) in [
+            (".resource\n| keys[", 2, 8),
+            (".resource # c\n .x[", 2, 5),
+            ("# first\n# second\n.a |\n  .b[.c == ]", 4, 12),
+            (".a,\n.00asdf", 2, 2),
+        ] {
+            let e = parse_filter(input).expect_err("filter should not parse");
+            assert_eq!((line, column), (e.line(), e.column()), "{input:?}");
+            // rendering only shows the line the error is on, numbered as in
+            // the filter
+            let rendered = format!("{e:?}");
+            assert!(rendered.contains(&format!("{line} |")), "{rendered}");
+        }
+    }
+
+    #[test]
+    fn name_starting_with_digit_quoted_should_parse() {
+        let input = ".ciphers.\"3des\".'1st key'";
+        let expected = vec![
+            Field::new("ciphers"),
+            Field::new("3des"),
+            Field::new("1st key"),
+        ];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn unicode_name_filter() {
+        let input = ".café.naïve-ünï._日本2";
+        let expected = vec![
+            Field::new("café"),
+            Field::new("naïve-ünï"),
+            Field::new("_日本2"),
+        ];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+        // symbols aren't identifier characters, but can be quoted
+        assert!(parse_filter(".a→b").is_err());
+        let fields = parse_filter(".\"a→b\"").expect("parse error");
+        assert_eq!(vec![Stage::new(vec![Field::new("a→b")])], fields);
+    }
+
+    #[test]
+    fn hyphenated_name_filter() {
+        let input = ".my-resource.a_b-c";
+        let expected = vec![Field::new("my-resource"), Field::new("a_b-c")];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn name_starting_with_hyphen_should_err() {
+        let input = ".-foo";
+        let result = parse_filter(input);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn label_filter() {
         let input = ".a_name{\"a_label\"}";
         let expected = vec![Field::labeled("a_name", &["a_label"])];
         let fields = parse_filter(input).expect("parse error");
-        assert_eq!(expected, fields);
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn wildcard_label_filter() {
+        let input = ".resource{*,\"web\"}";
+        let expected = vec![Field {
+            labels: vec![Label::Any, Label::from("web")],
+            ..Field::new("resource")
+        }];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+
+        // a quoted `"*"` is only that label
+        let fields = parse_filter(".resource{\"*\"}").expect("parse error");
+        assert_eq!(vec![Label::from("*")], fields[0].paths[0][0].labels);
     }
 
     #[test]
@@ -107,6 +1129,710 @@ mod tests {
             Field::new("third_name"),
         ];
         let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn alternatives_filter() {
+        let input = ".a_name, .another_name{\"a_label\"}.third_name";
+        let expected = vec![Stage {
+            paths: vec![
+                vec![Field::new("a_name")],
+                vec![
+                    Field::labeled("another_name", &["a_label"]),
+                    Field::new("third_name"),
+                ],
+            ],
+        }];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+    }
+
+    #[test]
+    fn pipe_filter() {
+        let input = ".a_name, .another_name | .third_name";
+        let expected = vec![
+            Stage {
+                paths: vec![vec![Field::new("a_name")], vec![Field::new("another_name")]],
+            },
+            Stage::new(vec![Field::new("third_name")]),
+        ];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+    }
+
+    #[test]
+    fn escaped_name_filter() {
+        let input = r#"["weird\"key\\"]{"a \"label\"","\u00e9"}"#;
+        let expected = vec![Field {
+            bracketed: true,
+            ..Field::labeled("weird\"key\\", &["a \"label\"", "é"])
+        }];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn escaped_whitespace_name_filter() {
+        let input = r#"["tab\tnew\nline"]"#;
+        let expected = vec![Field {
+            bracketed: true,
+            ..Field::new("tab\tnew\nline")
+        }];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn bracket_path_filter() {
+        let input = r#"["resource"]["aws_instance"].ami"#;
+        let expected = vec![
+            Field {
+                bracketed: true,
+                ..Field::new("resource")
+            },
+            Field {
+                bracketed: true,
+                ..Field::new("aws_instance")
+            },
+            Field::new("ami"),
+        ];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn invalid_unicode_escape_should_err() {
+        let input = r#"["\ud800"]"#;
+        let result = parse_filter(input);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn key_filter() {
+        let input = r#".a_name["a \"key\""]"#;
+        let expected = vec![Field::keyed("a_name", "a \"key\"")];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn key_and_index_filter() {
+        let input = r#".a_name["a_key"][0]"#;
+        let mut field = Field::keyed("a_name", "a_key");
+        field.selector = Some(Selector::Index(Index::FromStart(0)));
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(vec![field])], fields);
+    }
+
+    #[test]
+    fn predicate_filter() {
+        let input = r#".a_name[.attr == "a \"value\""].b[][.c.d==true]"#;
+        let mut field = Field::new("a_name");
+        field.predicate = Some(Predicate::Compare {
+            path: vec![Field::new("attr")],
+            comparison: Comparison::Equal,
+            value: Literal::String(String::from("a \"value\"")),
+        });
+        let mut other = Field::new("b");
+        other.selector = Some(Selector::Iterate);
+        other.predicate = Some(Predicate::Compare {
+            path: vec![Field::new("c"), Field::new("d")],
+            comparison: Comparison::Equal,
+            value: Literal::Bool(true),
+        });
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(vec![field, other])], fields);
+    }
+
+    #[test]
+    fn number_predicate_filter() {
+        for (input, number) in [("3", 3.0), ("-1.5", -1.5), ("2e3", 2000.0), ("1E-1", 0.1)] {
+            let input = format!(".a_name[.count == {input}]");
+            let mut field = Field::new("a_name");
+            field.predicate = Some(Predicate::Compare {
+                path: vec![Field::new("count")],
+                comparison: Comparison::Equal,
+                value: Literal::Number(number),
+            });
+            let fields = parse_filter(&input).expect("parse error");
+            assert_eq!(vec![Stage::new(vec![field])], fields);
+        }
+    }
+
+    #[test]
+    fn ordering_predicate_filter() {
+        for (operator, comparison) in [
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            ("<", Comparison::Less),
+            ("<=", Comparison::LessOrEqual),
+            (">", Comparison::Greater),
+            (">=", Comparison::GreaterOrEqual),
+        ] {
+            let mut field = Field::new("resource");
+            field.predicate = Some(Predicate::Compare {
+                path: vec![Field::new("cpu")],
+                comparison,
+                value: Literal::Number(4.0),
+            });
+            let expected = vec![Stage::new(vec![field])];
+            for input in [
+                format!(".resource[.cpu {operator} 4]"),
+                format!(".resource[.cpu{operator}4]"),
+            ] {
+                let fields = parse_filter(&input).expect("parse error");
+                assert_eq!(expected, fields);
+            }
+        }
+        assert!(parse_filter(".resource[.cpu => 4]").is_err());
+        assert!(parse_filter(".resource[.cpu <> 4]").is_err());
+    }
+
+    #[test]
+    fn contains_predicate_filter() {
+        let input = r#".resource[.cidr_blocks contains "0.0.0.0/0"]"#;
+        let mut field = Field::new("resource");
+        field.predicate = Some(Predicate::Compare {
+            path: vec![Field::new("cidr_blocks")],
+            comparison: Comparison::Contains,
+            value: Literal::String(String::from("0.0.0.0/0")),
+        });
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(vec![field])], fields);
+
+        // `contains` is a whole word
+        assert!(parse_filter(r#".resource[.cidr_blocks containsx "a"]"#).is_err());
+    }
+
+    #[test]
+    fn variable_filter() {
+        let input = ".resource[.region == $region]";
+        let mut field = Field::new("resource");
+        field.predicate = Some(Predicate::Compare {
+            path: vec![Field::new("region")],
+            comparison: Comparison::Equal,
+            value: Literal::Variable(String::from("region")),
+        });
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(vec![field])], fields);
+
+        // a variable has a name
+        assert!(parse_filter(".resource[.region == $]").is_err());
+    }
+
+    #[test]
+    fn builtin_filter() {
+        let input = ".a_name | keys[0]";
+        let mut field = Field::builtin(Builtin::Keys);
+        field.selector = Some(Selector::Index(Index::FromStart(0)));
+        let expected = vec![
+            Stage::new(vec![Field::new("a_name")]),
+            Stage::new(vec![field]),
+        ];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+    }
+
+    #[test]
+    fn base64_filter() {
+        let input = ".user_data | @base64d, @base64";
+        let expected = vec![
+            Stage::new(vec![Field::new("user_data")]),
+            Stage {
+                paths: vec![
+                    vec![Field::builtin(Builtin::Base64Decode)],
+                    vec![Field::builtin(Builtin::Base64)],
+                ],
+            },
+        ];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+        assert!(parse_filter(".user_data | @base64x").is_err());
+    }
+
+    #[test]
+    fn values_filter() {
+        let input = ".tags | values?";
+        let mut field = Field::builtin(Builtin::Values);
+        field.optional = true;
+        let expected = vec![
+            Stage::new(vec![Field::new("tags")]),
+            Stage::new(vec![field]),
+        ];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+    }
+
+    #[test]
+    fn add_filter() {
+        let input = ".ports | add";
+        let expected = vec![
+            Stage::new(vec![Field::new("ports")]),
+            Stage::new(vec![Field::builtin(Builtin::Add)]),
+        ];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+        // a name that starts with a builtin's name is still a name
+        assert_eq!(
+            vec![Stage::new(vec![Field::new("address")])],
+            parse_filter(".address").expect("parse error")
+        );
+    }
+
+    #[test]
+    fn flatten_filter() {
+        let input = ".cidrs | flatten, flatten ( 2 )";
+        let expected = vec![
+            Stage::new(vec![Field::new("cidrs")]),
+            Stage {
+                paths: vec![
+                    vec![Field::builtin(Builtin::Flatten(1))],
+                    vec![Field::builtin(Builtin::Flatten(2))],
+                ],
+            },
+        ];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+        // only flatten takes an argument
+        assert!(parse_filter(".cidrs | keys(2)").is_err());
+        let Err(e) = parse_filter("flatten(99999999999999999999999)") else {
+            panic!("the depth doesn't fit");
+        };
+        assert_eq!(FilterErrorKind::InvalidNumber, e.kind());
+    }
+
+    #[test]
+    fn paths_filter() {
+        let input = "paths, leaf_paths";
+        let expected = vec![Stage {
+            paths: vec![
+                vec![Field::builtin(Builtin::Paths)],
+                vec![Field::builtin(Builtin::LeafPaths)],
+            ],
+        }];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+    }
+
+    #[test]
+    fn type_filter() {
+        let input = ".settings[type == \"object\"] | type";
+        let expected = vec![
+            Stage::new(vec![Field {
+                predicate: Some(Predicate::Compare {
+                    path: vec![Field::builtin(Builtin::Type)],
+                    comparison: Comparison::Equal,
+                    value: Literal::String(String::from("object")),
+                }),
+                ..Field::new("settings")
+            }]),
+            Stage::new(vec![Field::builtin(Builtin::Type)]),
+        ];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+        // an attribute named `type` is still a field
+        let stages = parse_filter(".variable.type").expect("parse error");
+        assert_eq!(
+            vec![Stage::new(vec![Field::new("variable"), Field::new("type")])],
+            stages
+        );
+    }
+
+    #[test]
+    fn wildcard_name_filter() {
+        let input = ".*.default, ..*";
+        let expected = vec![Stage {
+            paths: vec![
+                vec![Field::wildcard(), Field::new("default")],
+                vec![Field {
+                    recursive: true,
+                    ..Field::wildcard()
+                }],
+            ],
+        }];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+
+        // a quoted `"*"` is only that name
+        for input in [".\"*\"", "[\"*\"]"] {
+            let stages = parse_filter(input).expect("parse error");
+            let field = &stages[0].paths[0][0];
+            assert_eq!(WILDCARD_NAME, field.name, "{input}");
+            assert!(!field.wildcard, "{input}");
+        }
+    }
+
+    #[test]
+    fn typed_wildcard_filter() {
+        let input = ".*:string, ..*:object{\"web\"}";
+        let expected = vec![Stage {
+            paths: vec![
+                vec![Field {
+                    value_type: Some(ValueType::String),
+                    ..Field::wildcard()
+                }],
+                vec![Field {
+                    recursive: true,
+                    value_type: Some(ValueType::Object),
+                    labels: vec![Label::from("web")],
+                    ..Field::wildcard()
+                }],
+            ],
+        }];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+        for value_type in ValueType::ALL {
+            let stages = parse_filter(&format!(".*:{}", value_type.name())).expect("parse error");
+            assert_eq!(Some(value_type), stages[0].paths[0][0].value_type);
+        }
+        // only the wildcard has a type, and it has to be one of them
+        assert!(parse_filter(".name:string").is_err());
+        assert!(parse_filter(".*:str").is_err());
+        assert!(parse_filter(".*:strings").is_err());
+    }
+
+    #[test]
+    fn regex_name_filter() {
+        let input = r#".~"^aws_", ..~"\d+\"x""#;
+        let expected = vec![Stage {
+            paths: vec![
+                vec![Field::matching(Regex::new("^aws_").unwrap())],
+                vec![Field {
+                    recursive: true,
+                    ..Field::matching(Regex::new(r#"\d+"x"#).unwrap())
+                }],
+            ],
+        }];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+        assert_eq!(r#"~"^aws_""#, stages[0].paths[0][0].name);
+
+        let Err(e) = parse_filter(r#".~"[a-""#) else {
+            panic!("expected an invalid regex");
+        };
+        assert!(
+            e.message().starts_with("invalid regex: "),
+            "{}",
+            e.message()
+        );
+        assert_eq!(3..6, e.span());
+    }
+
+    #[test]
+    fn first_last_filter() {
+        let input = ".tags[] | first, last[0]";
+        let expected = vec![
+            Stage::new(vec![Field {
+                selector: Some(Selector::Iterate),
+                ..Field::new("tags")
+            }]),
+            Stage {
+                paths: vec![
+                    vec![Field::builtin(Builtin::First)],
+                    vec![Field {
+                        selector: Some(Selector::Index(Index::FromStart(0))),
+                        ..Field::builtin(Builtin::Last)
+                    }],
+                ],
+            },
+        ];
+        let stages = parse_filter(input).expect("parse error");
+        assert_eq!(expected, stages);
+    }
+
+    #[test]
+    fn builtin_prefix_name_should_err() {
+        // a builtin is matched as a whole word
+        assert!(parse_filter("keysfoo").is_err());
+        // and only at the start of a path
+        assert!(parse_filter(".a_name keys").is_err());
+    }
+
+    #[test]
+    fn index_filter() {
+        let input = ".a_name[2]";
+        let expected = vec![Field::indexed("a_name", 2)];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn negative_index_filter() {
+        let input = ".a_name{\"a_label\"}[-2]";
+        let mut field = Field::labeled("a_name", &["a_label"]);
+        field.selector = Some(Selector::Index(Index::FromEnd(2)));
+        let expected = vec![field];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn negative_zero_index_should_err() {
+        let input = ".a_name[-0]";
+        let result = parse_filter(input);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn multiple_index_filter() {
+        let input = ".a_name[0, 2,-1]";
+        let mut field = Field::new("a_name");
+        field.selector = Some(Selector::Indices(vec![
+            Index::FromStart(0),
+            Index::FromStart(2),
+            Index::FromEnd(1),
+        ]));
+        let expected = vec![field];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+        assert!(parse_filter(".a_name[0,]").is_err());
+        assert!(parse_filter(".a_name[0,1:2]").is_err());
+    }
+
+    #[test]
+    fn slice_filter() {
+        let input = ".a_name[1:3]";
+        let mut field = Field::new("a_name");
+        field.selector = Some(Selector::Slice {
+            start: Some(Index::FromStart(1)),
+            end: Some(Index::FromStart(3)),
+        });
+        let expected = vec![field];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn open_slice_filter() {
+        let cases = [
+            (".a_name[:2]", None, Some(Index::FromStart(2))),
+            (".a_name[2:]", Some(Index::FromStart(2)), None),
+            (".a_name[-2:]", Some(Index::FromEnd(2)), None),
+            (".a_name[:]", None, None),
+        ];
+        for (input, start, end) in cases {
+            let mut field = Field::new("a_name");
+            field.selector = Some(Selector::Slice { start, end });
+            let fields = parse_filter(input).expect("parse error");
+            assert_eq!(vec![Stage::new(vec![field])], fields);
+        }
+    }
+
+    #[test]
+    fn iterate_filter() {
+        let input = ".a_name[].another_name";
+        let mut field = Field::new("a_name");
+        field.selector = Some(Selector::Iterate);
+        let expected = vec![field, Field::new("another_name")];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn recursive_filter() {
+        let input = ".a_name..another_name{\"a_label\"}";
+        let mut field = Field::labeled("another_name", &["a_label"]);
+        field.recursive = true;
+        let expected = vec![Field::new("a_name"), field];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn optional_filter() {
+        let input = ".a_name[3]?.another_name?";
+        let mut field = Field::indexed("a_name", 3);
+        field.optional = true;
+        let mut another_field = Field::new("another_name");
+        another_field.optional = true;
+        let expected = vec![field, another_field];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(expected)], fields);
+    }
+
+    #[test]
+    fn identity_filter() {
+        let input = ".";
+        let expected = vec![Stage::new(vec![])];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(expected, fields);
+
+        let input = ". | keys";
+        let expected = vec![
+            Stage::new(vec![]),
+            Stage::new(vec![Field::builtin(Builtin::Keys)]),
+        ];
+        let fields = parse_filter(input).expect("parse error");
         assert_eq!(expected, fields);
     }
+
+    #[test]
+    fn has_predicate_filter() {
+        let input = ".resource{\"aws_instance\"}[ has(\"lifecycle\") ]";
+        let mut field = Field::labeled("resource", &["aws_instance"]);
+        field.predicate = Some(Predicate::Has(String::from("lifecycle")));
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(vec![field])], fields);
+    }
+
+    #[test]
+    fn not_predicate_filter() {
+        let has_tags = Predicate::Has(String::from("tags"));
+        for input in [".resource[not has(\"tags\")]", ".resource[!has(\"tags\")]"] {
+            let mut field = Field::new("resource");
+            field.predicate = Some(Predicate::Not(Box::new(has_tags.clone())));
+            let fields = parse_filter(input).expect("parse error");
+            assert_eq!(vec![Stage::new(vec![field])], fields);
+        }
+
+        // negation covers the whole comparison, and can be repeated
+        let input = ".rules[][! not .port == 80]";
+        let mut field = Field::new("rules");
+        field.selector = Some(Selector::Iterate);
+        field.predicate = Some(Predicate::Not(Box::new(Predicate::Not(Box::new(
+            Predicate::Compare {
+                path: vec![Field::new("port")],
+                comparison: Comparison::Equal,
+                value: Literal::Number(80.0),
+            },
+        )))));
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(vec![Stage::new(vec![field])], fields);
+
+        // `not` must be separated from what it negates
+        assert!(parse_filter(".resource[nothas(\"tags\")]").is_err());
+    }
+
+    #[test]
+    fn commented_filter() {
+        let input = "# the web instances\n.resource{\"aws_instance\"}  # of that type\n\t| .ami # just the AMI\n";
+        let expected = vec![
+            Stage::new(vec![Field::labeled("resource", &["aws_instance"])]),
+            Stage::new(vec![Field::new("ami")]),
+        ];
+        let fields = parse_filter(input).expect("parse error");
+        assert_eq!(expected, fields);
+    }
+
+    #[test]
+    fn multiline_filter() {
+        let single = r#".resource{"aws_instance","web"}.tags["Env"], .rules[0:2][.port == 80].protocol | keys[0]"#;
+        let multiline = r#"
+            .resource {
+                "aws_instance",
+                "web"
+            }
+            .tags [ "Env" ],
+            .rules [ 0 : 2 ]
+                [ .port == 80 ]
+                .protocol
+            | keys [ 0 ]
+        "#;
+        assert_eq!(
+            parse_filter(single).expect("parse error"),
+            parse_filter(multiline).expect("parse error")
+        );
+
+        // segments still can't be split apart
+        assert!(parse_filter(".resource . tags").is_err());
+    }
+
+    #[test]
+    fn hash_in_string_is_not_a_comment() {
+        let input = ".tags[. == \"#1\"]";
+        let stages = parse_filter(input).expect("parse error");
+        let Some(Predicate::Compare { ref value, .. }) = stages[0].paths[0][0].predicate else {
+            panic!("expected a predicate");
+        };
+        assert_eq!(&Literal::String(String::from("#1")), value);
+    }
+
+    #[test]
+    fn field_spans() {
+        let input = r#".resource{"aws_instance"} .tags[0] | keys[.x == 1]"#;
+        let stages = parse_filter(input).expect("parse error");
+        let spans: Vec<_> = stages
+            .iter()
+            .flat_map(|stage| &stage.paths)
+            .flatten()
+            .map(|field| &input[field.span.0..field.span.1])
+            .collect();
+        assert_eq!(
+            vec![r#".resource{"aws_instance"}"#, ".tags[0]", "keys[.x == 1]"],
+            spans
+        );
+
+        // fields in a predicate point into the same filter
+        let Some(Predicate::Compare { ref path, .. }) = stages[1].paths[0][0].predicate else {
+            panic!("expected a predicate");
+        };
+        assert_eq!(".x", &input[path[0].span.0..path[0].span.1]);
+
+        // the span doesn't affect equality
+        assert_eq!(Field::new("tags").with_span(1, 5), Field::new("tags"));
+        assert_eq!((1, 5), Field::new("tags").with_span(1, 5).span);
+    }
+
+    #[test]
+    fn matches_labels() {
+        let field = Field::labeled("resource", &["aws_instance"]);
+        assert!(field.matches_labels(&["aws_instance", "web"]));
+        assert!(field.matches_labels(&["aws_instance"]));
+        assert!(!field.matches_labels(&["web", "aws_instance"]));
+        assert!(!field.matches_labels(&[]));
+        assert!(Field::new("resource").matches_labels(&["aws_instance", "web"]));
+        let field = Field {
+            labels: vec![Label::Any, Label::from("web")],
+            ..Field::new("resource")
+        };
+        assert!(field.matches_labels(&["aws_instance", "web"]));
+        assert!(field.matches_labels(&["google_compute_instance", "web"]));
+        assert!(!field.matches_labels(&["aws_instance", "db"]));
+        assert!(!field.matches_labels(&["web"]));
+        let field = Field::labeled("resource", &["*"]);
+        assert!(field.matches_labels(&["*", "web"]));
+        assert!(!field.matches_labels(&["aws_instance", "web"]));
+    }
+
+    #[test]
+    fn unsupported_fields() {
+        let unsupported = |filter| {
+            let stages = parse_filter(filter).expect("parse error");
+            super::unsupported(&stages[0].paths[0])
+        };
+        assert_eq!(None, unsupported(".a{\"b\"}.c[\"d\"]"));
+        assert_eq!(Some("the whole document"), unsupported("."));
+        assert_eq!(Some("a list index"), unsupported(".a[0]"));
+        assert_eq!(Some("with predicates"), unsupported(".a[.b == 1]"));
+        assert_eq!(Some("with builtins"), unsupported("keys"));
+        assert_eq!(Some("with recursive descent"), unsupported("..a"));
+        assert_eq!(Some("with wildcard names"), unsupported(".*"));
+        assert_eq!(Some("with regex names"), unsupported(".~\"^a\""));
+        // only a wildcard has a type in a filter, which is checked first
+        let field = Field {
+            value_type: Some(ValueType::String),
+            ..Field::new("a")
+        };
+        assert_eq!(Some("with value types"), super::unsupported(&[field]));
+    }
+
+    #[test]
+    fn resolve_index() {
+        assert_eq!(Some(0), Index::FromStart(0).resolve(3));
+        assert_eq!(None, Index::FromStart(3).resolve(3));
+        assert_eq!(Some(2), Index::FromEnd(1).resolve(3));
+        assert_eq!(Some(0), Index::FromEnd(3).resolve(3));
+        assert_eq!(None, Index::FromEnd(4).resolve(3));
+        assert_eq!(None, Index::FromEnd(0).resolve(3));
+        assert_eq!(None, Index::FromEnd(0).resolve(0));
+    }
+
+    #[test]
+    fn clamp_index() {
+        assert_eq!(1, Index::FromStart(1).clamp(3));
+        assert_eq!(3, Index::FromStart(5).clamp(3));
+        assert_eq!(2, Index::FromEnd(1).clamp(3));
+        assert_eq!(0, Index::FromEnd(5).clamp(3));
+    }
 }
@@ -0,0 +1,136 @@
+//! syntax highlighting for HCL output
+//!
+//! this is only used for HCL, so that color codes never end up in other
+//! output formats
+
+use anstyle::{AnsiColor, Style};
+
+const BLOCK: Style = AnsiColor::Blue.on_default().bold();
+const KEY: Style = AnsiColor::Blue.on_default();
+const STRING: Style = AnsiColor::Green.on_default();
+const NUMBER: Style = AnsiColor::Cyan.on_default();
+const KEYWORD: Style = AnsiColor::Magenta.on_default();
+const COMMENT: Style = AnsiColor::BrightBlack.on_default();
+
+const KEYWORDS: &[&str] = &[
+    "true", "false", "null", "for", "in", "if", "else", "endif", "endfor",
+];
+
+/// add ANSI color codes to HCL
+///
+/// block types, attribute and object keys, strings (including heredocs),
+/// numbers, keywords, and comments are colored, and removing the color codes
+/// gives back `hcl` unchanged
+pub fn highlight(hcl: &str) -> String {
+    let mut highlighted = String::with_capacity(hcl.len() * 2);
+    // the marker that ends the heredoc we're in, if any
+    let mut heredoc: Option<String> = None;
+    for line in hcl.split_inclusive('\n') {
+        match heredoc {
+            Some(ref marker) => {
+                if line.trim() == marker {
+                    heredoc = None;
+                }
+                paint_line(&mut highlighted, line, STRING);
+            }
+            None => heredoc = highlight_line(&mut highlighted, line),
+        }
+    }
+    highlighted
+}
+
+/// highlight one line that isn't part of a heredoc, returning the heredoc
+/// marker if the line starts one
+fn highlight_line(highlighted: &mut String, line: &str) -> Option<String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut heredoc = None;
+    let mut line_start = true;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let start = i;
+        if c == '#' || (c == '/' && next == Some('/')) {
+            let rest: String = chars[i..].iter().collect();
+            paint_line(highlighted, &rest, COMMENT);
+            return heredoc;
+        } else if c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' && chars[i] != '\n' {
+                // skip whatever is escaped, including a quote
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i = (i + 1).min(chars.len());
+            paint(highlighted, &chars[start..i], STRING);
+        } else if c == '<' && next == Some('<') {
+            i += 2;
+            if chars.get(i) == Some(&'-') {
+                i += 1;
+            }
+            let marker_start = i;
+            while i < chars.len() && is_id_char(chars[i]) {
+                i += 1;
+            }
+            heredoc = Some(chars[marker_start..i].iter().collect());
+            paint(highlighted, &chars[start..i], STRING);
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+            paint(highlighted, &chars[start..i], NUMBER);
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && is_id_char(chars[i]) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let mut rest = chars[i..].iter().skip_while(|c| **c == ' ');
+            let after = rest.next();
+            let assigns = match after {
+                Some('=') => rest.next() != Some(&'='),
+                Some(':') => true,
+                _ => false,
+            };
+            let style = if KEYWORDS.contains(&word.as_str()) {
+                Some(KEYWORD)
+            } else if assigns {
+                Some(KEY)
+            } else if line_start && matches!(after, Some('"' | '{')) {
+                Some(BLOCK)
+            } else {
+                None
+            };
+            match style {
+                Some(style) => paint(highlighted, &chars[start..i], style),
+                None => highlighted.push_str(&word),
+            }
+        } else {
+            highlighted.push(c);
+            i += 1;
+        }
+        if !c.is_whitespace() {
+            line_start = false;
+        }
+    }
+    heredoc
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+fn paint(highlighted: &mut String, chars: &[char], style: Style) {
+    highlighted.push_str(&style.render().to_string());
+    highlighted.extend(chars);
+    highlighted.push_str(&style.render_reset().to_string());
+}
+
+/// paint all of `line` except its line ending, so the reset code comes before
+/// the newline
+fn paint_line(highlighted: &mut String, line: &str, style: Style) {
+    let content = line.trim_end_matches(['\n', '\r']);
+    let chars: Vec<char> = content.chars().collect();
+    if !chars.is_empty() {
+        paint(highlighted, &chars, style);
+    }
+    highlighted.push_str(&line[content.len()..]);
+}
@@ -0,0 +1,192 @@
+//! read HCL written in its [JSON syntax][spec] (e.g. Terraform's `.tf.json`
+//! files) into the same model as the native syntax, so the same filters work
+//! on both
+//!
+//! JSON has no way to tell a block from an attribute with an object value, so
+//! the block types (and how many labels they have) are those of Terraform's
+//! configuration language, e.g. `resource` at the top level has two labels and
+//! `required_providers` inside of `terraform` has none; any other property is
+//! an attribute
+//!
+//! [spec]: https://github.com/hashicorp/hcl/blob/main/json/spec.md
+
+use hcl::{
+    expr::{Expression, Object, ObjectKey},
+    template::{Element, Template},
+    Attribute, Block, BlockLabel, Body, Identifier, Number, Structure,
+};
+use serde::de::Error as _;
+use serde_json::{Map, Value};
+
+use crate::{output::escape_literal, HqError};
+
+/// whether `source` is written in the JSON syntax rather than the native one
+///
+/// a native document can't start with `{`, while a JSON one always does
+pub fn is_json(source: &str) -> bool {
+    source.trim_start().starts_with('{')
+}
+
+/// parse `source`, written in the JSON syntax, into an HCL [`Body`]
+///
+/// strings are templates, as in the native syntax, so `"${var.name}"` is an
+/// interpolation
+///
+/// an empty (or blank) `source` is an empty document, as it is in the native
+/// syntax, rather than JSON that ends too early
+pub fn from_str(source: &str) -> Result<Body, HqError> {
+    if source.trim().is_empty() {
+        return Ok(Body::default());
+    }
+    let value: Value = serde_json::from_str(source)?;
+    let Value::Object(object) = value else {
+        return Err(invalid("the document must be a JSON object"));
+    };
+    body(None, object)
+}
+
+/// the number of labels of the block type `name` inside of a block of type
+/// `parent` (`None` at the top level), or `None` if it isn't a block type
+fn block_labels(parent: Option<&str>, name: &str) -> Option<usize> {
+    match (parent, name) {
+        (None, "resource" | "data" | "ephemeral") => Some(2),
+        (None, "variable" | "output" | "module" | "provider" | "check") => Some(1),
+        (None, "locals" | "terraform" | "moved" | "import" | "removed") => Some(0),
+        (Some("terraform"), "backend") => Some(1),
+        (Some("terraform"), "required_providers" | "cloud") => Some(0),
+        _ => None,
+    }
+}
+
+fn body(parent: Option<&str>, object: Map<String, Value>) -> Result<Body, HqError> {
+    let mut structures = Vec::new();
+    for (name, value) in object {
+        // a `"//"` property is a comment
+        if name == "//" {
+            continue;
+        }
+        let identifier = Identifier::new(name.as_str())
+            .map_err(|_| invalid(&format!("'{name}' is not a valid attribute or block name")))?;
+        match block_labels(parent, &name) {
+            Some(labels) => blocks(&identifier, labels, Vec::new(), value, &mut structures)?,
+            None => {
+                structures.push(Structure::Attribute(Attribute::new(
+                    identifier,
+                    expression(value)?,
+                )));
+            }
+        }
+    }
+    Ok(Body::from(structures))
+}
+
+/// the blocks of type `identifier` in `value`, which is nested `labels` levels
+/// of objects keyed by label (after the labels already found) around the body
+/// of each block, and where an array at any level holds several of them
+fn blocks(
+    identifier: &Identifier,
+    labels: usize,
+    found: Vec<String>,
+    value: Value,
+    structures: &mut Vec<Structure>,
+) -> Result<(), HqError> {
+    match value {
+        Value::Array(values) => {
+            for value in values {
+                blocks(identifier, labels, found.clone(), value, structures)?;
+            }
+        }
+        Value::Object(object) if labels == 0 => {
+            structures.push(Structure::Block(Block {
+                identifier: identifier.clone(),
+                labels: found.into_iter().map(BlockLabel::String).collect(),
+                body: body(Some(identifier.as_str()), object)?,
+            }));
+        }
+        Value::Object(object) => {
+            for (label, value) in object {
+                let mut found = found.clone();
+                found.push(label);
+                blocks(identifier, labels - 1, found, value, structures)?;
+            }
+        }
+        _ => {
+            return Err(invalid(&format!(
+                "the value of a '{identifier}' block must be an object"
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn expression(value: Value) -> Result<Expression, HqError> {
+    Ok(match value {
+        Value::Null => Expression::Null,
+        Value::Bool(b) => Expression::Bool(b),
+        Value::Number(n) => Expression::Number(number(&n)?),
+        Value::String(s) => template(&s)?,
+        Value::Array(values) => Expression::Array(
+            values
+                .into_iter()
+                .map(expression)
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(object) => {
+            let mut entries = Object::new();
+            for (key, value) in object {
+                // keys are written the way they would be in the native syntax
+                let key = match Identifier::new(key.as_str()) {
+                    Ok(identifier) => ObjectKey::Identifier(identifier),
+                    Err(_) => ObjectKey::Expression(Expression::String(key)),
+                };
+                entries.insert(key, expression(value)?);
+            }
+            Expression::Object(entries)
+        }
+    })
+}
+
+fn number(n: &serde_json::Number) -> Result<Number, HqError> {
+    if let Some(i) = n.as_i64() {
+        return Ok(Number::from(i));
+    }
+    if let Some(u) = n.as_u64() {
+        return Ok(Number::from(u));
+    }
+    n.as_f64()
+        .and_then(Number::from_f64)
+        .ok_or_else(|| invalid(&format!("{n} is not a valid number")))
+}
+
+/// a JSON string is a template, parsed the same way as a quoted string in the
+/// native syntax (so a plain string stays a plain string), except that a
+/// template that is a single interpolation (`"${var.name}"`) is the expression
+/// in it, as the spec says it evaluates to its value
+fn template(s: &str) -> Result<Expression, HqError> {
+    let source = format!("value = \"{}\"", escape_literal(s));
+    let body: Body = hcl::from_str(&source).map_err(|_| {
+        invalid(&format!(
+            "invalid template in string \"{}\"",
+            s.escape_debug()
+        ))
+    })?;
+    let expr = body
+        .into_attributes()
+        .next()
+        .map(|attr| attr.expr)
+        .expect("the source has one attribute");
+    if let Expression::TemplateExpr(ref template) = expr {
+        if let Ok(template) = Template::from_expr(template) {
+            if let [Element::Interpolation(interpolation)] = template.elements() {
+                return Ok(interpolation.expr.clone());
+            }
+        }
+    }
+    Ok(expr)
+}
+
+fn invalid(message: &str) -> HqError {
+    HqError::Hcl(hcl::Error::custom(format!(
+        "invalid JSON syntax: {message}"
+    )))
+}
@@ -1,14 +1,105 @@
 #![doc = include_str!("../README.md")]
 
 pub mod filter;
+pub use filter::error::{FilterError, FilterErrorKind};
 pub use filter::parser;
-pub use filter::parser::parse_filter;
+pub use filter::parser::{parse_filter, Field, Stage};
 
 pub mod query;
-pub use query::query;
+pub use query::{
+    query, query_stages, query_stages_each, query_stages_each_on, query_stages_with_paths,
+    QueryOptions, QueryResult,
+};
+
+pub mod path;
 
 pub mod write;
 pub use write::write;
 
 pub mod delete;
 pub use delete::delete;
+
+pub mod merge;
+pub use merge::merge;
+
+pub mod output;
+
+pub mod highlight;
+
+pub mod json;
+
+pub mod error;
+pub use error::{ErrorReport, HclParseError, HqError};
+
+/// parse `source` as HCL and `filter` as an hq filter, then return the
+/// results of applying the filter to the HCL
+///
+/// `source` may be in the native syntax or the JSON syntax, see [`parse_hcl`]
+///
+/// this is the simplest way to use hq as a library, see [`CompiledFilter`] to
+/// apply the same filter to many documents, and [`parse_filter`] and
+/// [`query_stages`] for more control over each step
+pub fn eval(source: &str, filter: &str) -> Result<Vec<QueryResult>, HqError> {
+    let body = parse_hcl(source)?;
+    CompiledFilter::compile(filter)?.apply(&body)
+}
+
+/// parse `source` as HCL written in either the native syntax or the [JSON
+/// syntax][json] (which is told apart by starting with `{`)
+pub fn parse_hcl(source: &str) -> Result<hcl::Body, HqError> {
+    if json::is_json(source) {
+        return json::from_str(source);
+    }
+    Ok(hcl::from_str(source)?)
+}
+
+/// a filter that is parsed once and can then be applied to any number of
+/// documents, e.g. by a long-running service
+///
+/// ```
+/// use hq_rs::CompiledFilter;
+///
+/// let filter = CompiledFilter::compile(".version")?;
+/// for source in ["version = 1", "version = 2"] {
+///     let body = hcl::from_str(source)?;
+///     let results = filter.apply(&body)?;
+///     assert_eq!(1, results.len());
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct CompiledFilter {
+    stages: Vec<Stage>,
+    options: QueryOptions,
+}
+
+impl CompiledFilter {
+    /// parse `filter`, returning an error if it isn't a valid filter
+    pub fn compile(filter: &str) -> Result<Self, HqError> {
+        Ok(CompiledFilter {
+            stages: parse_filter(filter)?,
+            options: QueryOptions::default(),
+        })
+    }
+
+    /// use `options` instead of the defaults when applying the filter
+    pub fn with_options(mut self, options: QueryOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// the parsed [`Stage`]s of the filter
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// return the results of applying the filter to `body`
+    pub fn apply(&self, body: &hcl::Body) -> Result<Vec<QueryResult>, HqError> {
+        let mut results = Vec::new();
+        query_stages_each(&self.stages, body, &self.options, |_, query_result| {
+            results.push(query_result);
+            Ok::<(), HqError>(())
+        })?;
+        Ok(results)
+    }
+}
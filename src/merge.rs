@@ -0,0 +1,75 @@
+//! combine HCL documents into one, the way Terraform applies override files
+//!
+//! documents are merged in order, so where they disagree the last one wins:
+//!
+//! * an attribute replaces the attribute with the same key, whatever its value
+//!   (so lists and objects are replaced as a whole, not merged)
+//! * a block is merged into the block with the same type and labels, by
+//!   merging their bodies the same way, so that nested blocks are merged too;
+//!   when several blocks have the same type and labels (e.g. the `ingress`
+//!   blocks of a security group), the nth of them is merged into the nth
+//! * anything else is added after what is already there
+
+use hcl::{Block, BlockLabel, Body, Identifier, Structure};
+
+/// merge `bodies` into one, in order (see the [module docs][self])
+pub fn merge(bodies: impl IntoIterator<Item = Body>) -> Body {
+    let mut merged = Body::default();
+    for body in bodies {
+        merge_into(&mut merged, body);
+    }
+    merged
+}
+
+/// merge `body` into `into`, with `body` winning
+pub fn merge_into(into: &mut Body, body: Body) {
+    // how many blocks of each type and labels have been merged so far, so the
+    // nth is merged into the nth
+    let mut merged_blocks: Vec<(Identifier, Vec<BlockLabel>)> = Vec::new();
+    let mut structures = std::mem::take(&mut into.0);
+    let mut added = Vec::new();
+    for structure in body {
+        match structure {
+            Structure::Attribute(attr) => {
+                let existing = structures.iter_mut().find_map(|structure| match structure {
+                    Structure::Attribute(existing) if existing.key == attr.key => Some(existing),
+                    _ => None,
+                });
+                match existing {
+                    Some(existing) => existing.expr = attr.expr,
+                    None => added.push(Structure::Attribute(attr)),
+                }
+            }
+            Structure::Block(block) => {
+                let nth = merged_blocks
+                    .iter()
+                    .filter(|(identifier, labels)| {
+                        *identifier == block.identifier && *labels == block.labels
+                    })
+                    .count();
+                let existing = structures
+                    .iter_mut()
+                    .filter_map(|structure| match structure {
+                        Structure::Block(existing) if same_block(existing, &block) => {
+                            Some(existing)
+                        }
+                        _ => None,
+                    })
+                    .nth(nth);
+                match existing {
+                    Some(existing) => {
+                        merged_blocks.push((block.identifier.clone(), block.labels.clone()));
+                        merge_into(&mut existing.body, block.body);
+                    }
+                    None => added.push(Structure::Block(block)),
+                }
+            }
+        }
+    }
+    structures.extend(added);
+    into.0 = structures;
+}
+
+fn same_block(a: &Block, b: &Block) -> bool {
+    a.identifier == b.identifier && a.labels == b.labels
+}
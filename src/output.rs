@@ -0,0 +1,652 @@
+//! render [`QueryResult`]s in output formats other than HCL
+
+use std::{error::Error, fmt};
+
+use hcl::{
+    expr::{Expression as HclExpression, ObjectKey, Operation, TemplateExpr, TraversalOperator},
+    format::{Format, Formatter},
+    structure::{Body as HclBody, Structure as HclStructure},
+    template::{Directive, Element, Template},
+    Value,
+};
+use hcl_edit::{
+    expr::Expression,
+    structure::{Body, Structure},
+    Decor, Decorate, Span,
+};
+use serde::Serialize;
+
+use crate::{
+    path::{Path, Segment},
+    query::QueryResult,
+};
+
+/// how nested values are indented in HCL and pretty-printed JSON
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indent {
+    /// this many spaces for each level
+    Spaces(usize),
+    /// one tab for each level
+    Tab,
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Indent::Spaces(2)
+    }
+}
+
+impl fmt::Display for Indent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Indent::Spaces(n) => write!(f, "{:n$}", ""),
+            Indent::Tab => write!(f, "\t"),
+        }
+    }
+}
+
+/// render a [`QueryResult`] as HCL, indented with `indent`
+///
+/// with the default indentation this is the same as
+/// [`QueryResult::to_string`]
+pub fn to_hcl_indented(result: &QueryResult, indent: Indent) -> Result<String, Box<dyn Error>> {
+    let indent = indent.to_string();
+    let mut formatter = Formatter::builder().indent(indent.as_bytes()).build_vec();
+    let s = match result.clone() {
+        QueryResult::Expr(mut expr) => {
+            escape_templates(&mut expr);
+            expr.format_string(&mut formatter)?
+        }
+        QueryResult::Block(mut block) => {
+            escape_body_templates(&mut block.body);
+            block.format_string(&mut formatter)?
+        }
+        QueryResult::Body(mut body) => {
+            escape_body_templates(&mut body);
+            body.format_string(&mut formatter)?
+        }
+    };
+    Ok(s)
+}
+
+/// render a [`QueryResult`] as HCL on as few lines as it can be written in
+///
+/// arrays and objects are written on one line, and so is a block whose body is
+/// at most one attribute (`name "label" { key = value }`); HCL needs a newline
+/// after every attribute and block in a body, so a bigger body still has one
+/// per line, and heredocs still span the lines they need
+pub fn to_hcl_compact(result: &QueryResult) -> Result<String, Box<dyn Error>> {
+    let s = match result.clone() {
+        QueryResult::Expr(mut expr) => {
+            escape_templates(&mut expr);
+            expr.format_string(&mut compact_formatter())?
+        }
+        QueryResult::Block(mut block) => {
+            escape_body_templates(&mut block.body);
+            compact_block(&block, "")?
+        }
+        QueryResult::Body(mut body) => {
+            escape_body_templates(&mut body);
+            compact_body(&body, "")?
+        }
+    };
+    Ok(s)
+}
+
+fn compact_formatter() -> Formatter<'static, Vec<u8>> {
+    Formatter::builder().compact(true).build_vec()
+}
+
+fn compact_body(body: &HclBody, indent: &str) -> Result<String, hcl::Error> {
+    let mut s = String::new();
+    for structure in body.iter() {
+        match structure {
+            HclStructure::Attribute(attr) => {
+                s.push_str(indent);
+                s.push_str(&compact_attribute(attr)?);
+            }
+            HclStructure::Block(block) => s.push_str(&compact_block(block, indent)?),
+        }
+    }
+    Ok(s)
+}
+
+/// `attr` on a line of its own, ending with a newline
+fn compact_attribute(attr: &hcl::Attribute) -> Result<String, hcl::Error> {
+    HclBody::from(vec![attr.clone()]).format_string(&mut compact_formatter())
+}
+
+fn compact_block(block: &hcl::Block, indent: &str) -> Result<String, hcl::Error> {
+    // an empty block renders as `name "label" {}`, which is the header to
+    // write the body after
+    let empty = hcl::Block {
+        identifier: block.identifier.clone(),
+        labels: block.labels.clone(),
+        body: HclBody::default(),
+    };
+    let empty = empty.format_string(&mut compact_formatter())?;
+    let header = empty.trim_end().trim_end_matches("{}");
+    let structures: Vec<_> = block.body.iter().collect();
+    if let [HclStructure::Attribute(attr)] = structures.as_slice() {
+        let attr = compact_attribute(attr)?;
+        // a heredoc can't be followed by the closing brace on its line
+        if attr.trim_end().lines().count() == 1 {
+            return Ok(format!("{indent}{header}{{ {} }}\n", attr.trim_end()));
+        }
+    }
+    if structures.is_empty() {
+        return Ok(format!("{indent}{header}{{}}\n"));
+    }
+    let nested = format!("{indent}{}", Indent::default());
+    let body = compact_body(&block.body, &nested)?;
+    Ok(format!("{indent}{header}{{\n{body}{indent}}}\n"))
+}
+
+fn escape_body_templates(body: &mut HclBody) {
+    for structure in body.iter_mut() {
+        match structure {
+            HclStructure::Attribute(attr) => escape_templates(&mut attr.expr),
+            HclStructure::Block(block) => escape_body_templates(&mut block.body),
+        }
+    }
+}
+
+/// quoted strings with interpolations or directives are kept as their
+/// template source, but the parser unescapes the literal parts of that source
+/// (e.g. `"say \"${x}\""` becomes `say "${x}"`), so they're escaped again
+/// here, or the rendered HCL wouldn't parse
+fn escape_templates(expr: &mut HclExpression) {
+    match expr {
+        HclExpression::TemplateExpr(template) => {
+            if let TemplateExpr::QuotedString(source) = template.as_mut() {
+                // source that can't be parsed is rendered as is
+                if let Ok(mut parsed) = source.parse::<Template>() {
+                    // only replace the source if something was escaped, since
+                    // rendering a template normalizes its whitespace
+                    let unescaped = parsed.to_string();
+                    escape_template(&mut parsed, true);
+                    let escaped = parsed.to_string();
+                    if escaped != unescaped {
+                        *source = escaped;
+                    }
+                }
+            }
+        }
+        HclExpression::Array(array) => array.iter_mut().for_each(escape_templates),
+        HclExpression::Object(object) => {
+            let entries = std::mem::take(object);
+            for (mut key, mut value) in entries {
+                if let ObjectKey::Expression(key) = &mut key {
+                    escape_templates(key);
+                }
+                escape_templates(&mut value);
+                object.insert(key, value);
+            }
+        }
+        HclExpression::Parenthesis(expr) => escape_templates(expr),
+        HclExpression::Traversal(traversal) => {
+            escape_templates(&mut traversal.expr);
+            for operator in &mut traversal.operators {
+                if let TraversalOperator::Index(index) = operator {
+                    escape_templates(index);
+                }
+            }
+        }
+        HclExpression::FuncCall(call) => call.args.iter_mut().for_each(escape_templates),
+        HclExpression::Conditional(conditional) => {
+            escape_templates(&mut conditional.cond_expr);
+            escape_templates(&mut conditional.true_expr);
+            escape_templates(&mut conditional.false_expr);
+        }
+        HclExpression::Operation(operation) => match operation.as_mut() {
+            Operation::Unary(op) => escape_templates(&mut op.expr),
+            Operation::Binary(op) => {
+                escape_templates(&mut op.lhs_expr);
+                escape_templates(&mut op.rhs_expr);
+            }
+        },
+        HclExpression::ForExpr(for_expr) => {
+            escape_templates(&mut for_expr.collection_expr);
+            if let Some(key_expr) = &mut for_expr.key_expr {
+                escape_templates(key_expr);
+            }
+            escape_templates(&mut for_expr.value_expr);
+            if let Some(cond_expr) = &mut for_expr.cond_expr {
+                escape_templates(cond_expr);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// the literals inside of directives are kept escaped by the parser, so only
+/// the top level literals are escaped when `literals` is set
+fn escape_template(template: &mut Template, literals: bool) {
+    for element in template.elements_mut() {
+        match element {
+            Element::Literal(literal) if literals => *literal = escape_literal(literal),
+            Element::Literal(_) => {}
+            Element::Interpolation(interpolation) => escape_templates(&mut interpolation.expr),
+            Element::Directive(Directive::If(directive)) => {
+                escape_templates(&mut directive.cond_expr);
+                escape_template(&mut directive.true_template, false);
+                if let Some(false_template) = &mut directive.false_template {
+                    escape_template(false_template, false);
+                }
+            }
+            Element::Directive(Directive::For(directive)) => {
+                escape_templates(&mut directive.collection_expr);
+                escape_template(&mut directive.template, false);
+            }
+        }
+    }
+}
+
+/// escape the literal part of a quoted template, leaving `${` and `%{` to the
+/// formatter
+pub(crate) fn escape_literal(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// sort the keys of objects, and the attributes and blocks of bodies, by name
+///
+/// blocks of the same type (and list elements) are kept in order, since their
+/// order can be meaningful, e.g. for `provisioner` blocks
+pub fn sort_keys(result: &QueryResult) -> QueryResult {
+    let mut result = result.clone();
+    match result {
+        QueryResult::Expr(ref mut expr) => sort_expr_keys(expr),
+        QueryResult::Block(ref mut block) => sort_body_keys(&mut block.body),
+        QueryResult::Body(ref mut body) => sort_body_keys(body),
+    }
+    result
+}
+
+fn sort_body_keys(body: &mut HclBody) {
+    // a stable sort keeps blocks of the same type in order
+    body.0
+        .sort_by(|a, b| structure_name(a).cmp(structure_name(b)));
+    for structure in body.iter_mut() {
+        match structure {
+            HclStructure::Attribute(attr) => sort_expr_keys(&mut attr.expr),
+            HclStructure::Block(block) => sort_body_keys(&mut block.body),
+        }
+    }
+}
+
+fn structure_name(structure: &HclStructure) -> &str {
+    match structure {
+        HclStructure::Attribute(attr) => attr.key(),
+        HclStructure::Block(block) => block.identifier(),
+    }
+}
+
+fn sort_expr_keys(expr: &mut HclExpression) {
+    match expr {
+        HclExpression::Array(array) => array.iter_mut().for_each(sort_expr_keys),
+        HclExpression::Object(object) => {
+            let mut entries: Vec<_> = std::mem::take(object).into_iter().collect();
+            entries.sort_by_cached_key(|(key, _)| match key {
+                ObjectKey::Identifier(id) => id.to_string(),
+                ObjectKey::Expression(HclExpression::String(s)) => s.clone(),
+                key => key.to_string(),
+            });
+            for (key, mut value) in entries {
+                sort_expr_keys(&mut value);
+                object.insert(key, value);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// render a [`QueryResult`] as pretty-printed JSON
+///
+/// see [`QueryResult::to_value`] for how HCL is converted
+pub fn to_json(result: &QueryResult) -> Result<String, Box<dyn Error>> {
+    Ok(serde_json::to_string_pretty(&result.to_value())?)
+}
+
+/// render a [`QueryResult`] as JSON, pretty-printed with `indent`
+///
+/// see [`QueryResult::to_value`] for how HCL is converted
+pub fn to_json_indented(result: &QueryResult, indent: Indent) -> Result<String, Box<dyn Error>> {
+    let indent = indent.to_string();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut json = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(&mut json, formatter);
+    result.to_value().serialize(&mut serializer)?;
+    Ok(String::from_utf8(json)?)
+}
+
+/// render a [`QueryResult`] as JSON on a single line
+///
+/// see [`QueryResult::to_value`] for how HCL is converted
+pub fn to_json_compact(result: &QueryResult) -> Result<String, Box<dyn Error>> {
+    Ok(serde_json::to_string(&result.to_value())?)
+}
+
+/// render a [`QueryResult`] as YAML
+///
+/// see [`QueryResult::to_value`] for how HCL is converted
+pub fn to_yaml(result: &QueryResult) -> Result<String, Box<dyn Error>> {
+    Ok(serde_yaml::to_string(&result.to_value())?)
+}
+
+/// render a [`QueryResult`] as YAML on a single line
+///
+/// the result is written in YAML's flow style, which for any value HCL can
+/// hold is the same as compact JSON
+pub fn to_yaml_compact(result: &QueryResult) -> Result<String, Box<dyn Error>> {
+    to_json_compact(result)
+}
+
+/// render a [`QueryResult`] as TOML
+///
+/// the result is converted the same way as for JSON (see
+/// [`QueryResult::to_value`]); objects and blocks are rendered as tables (with
+/// repeated blocks as arrays of tables), and anything else as a TOML value
+///
+/// `path` is where the result was found, and is used to name any value TOML
+/// can't represent (e.g. null) in the error
+pub fn to_toml(result: &QueryResult, path: &Path) -> Result<String, Box<dyn Error>> {
+    match toml_result(result, path)? {
+        toml::Value::Table(table) => Ok(toml::to_string(&table)?),
+        value => Ok(value.to_string()),
+    }
+}
+
+/// render a [`QueryResult`] as TOML with one line for each top-level key
+///
+/// this is the same as [`to_toml`], except that nested tables and arrays of
+/// tables are written inline instead of as sections
+pub fn to_toml_compact(result: &QueryResult, path: &Path) -> Result<String, Box<dyn Error>> {
+    match toml_result(result, path)? {
+        toml::Value::Table(table) => Ok(table
+            .iter()
+            .map(|(key, value)| format!("{} = {value}\n", toml_key(key)))
+            .collect()),
+        value => Ok(value.to_string()),
+    }
+}
+
+/// `key` as written in TOML, quoted unless it's a bare key
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        toml::Value::String(key.to_string()).to_string()
+    }
+}
+
+/// `result`, which is at `path`, as a TOML value
+fn toml_result(result: &QueryResult, path: &Path) -> Result<toml::Value, Box<dyn Error>> {
+    let QueryResult::Block(block) = result else {
+        return toml_value(result.to_value(), path);
+    };
+    // a block's value is its body nested under its identifier and labels,
+    // which `path` already ends with
+    let keys: Vec<&str> = std::iter::once(block.identifier())
+        .chain(block.labels().iter().map(|label| label.as_str()))
+        .collect();
+    let mut value = result.to_value();
+    for key in &keys {
+        value = match value {
+            Value::Object(mut object) => object.swap_remove(*key).unwrap_or(Value::Null),
+            value => value,
+        };
+    }
+    let mut value = toml_value(value, path)?;
+    for key in keys.into_iter().rev() {
+        value = toml::Value::Table(toml::Table::from_iter([(key.to_string(), value)]));
+    }
+    Ok(value)
+}
+
+fn toml_value(value: Value, path: &Path) -> Result<toml::Value, Box<dyn Error>> {
+    Ok(match value {
+        Value::Null => return Err(format!("'{path}' is null, which TOML can't represent").into()),
+        Value::Bool(b) => toml::Value::Boolean(b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => toml::Value::Integer(i),
+            // `as_f64` is always `Some` for numbers that aren't an `i64`
+            None => toml::Value::Float(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => toml::Value::String(s),
+        Value::Array(array) => toml::Value::Array(
+            array
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| toml_value(value, &path.join(Segment::Element(i))))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(object) => toml::Value::Table(
+            object
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = toml_value(value, &path.join(Segment::Entry(key.clone())))?;
+                    Ok::<_, Box<dyn Error>>((key, value))
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+    })
+}
+
+/// render a scalar [`QueryResult`] as raw text
+///
+/// strings are rendered without quotes, and numbers, bools, and null are
+/// rendered in their canonical form; `None` is returned for lists, objects,
+/// and blocks since they have no raw form
+pub fn to_raw(result: &QueryResult) -> Option<String> {
+    match result.to_value() {
+        Value::String(s) => Some(s),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Null => Some(String::from("null")),
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+/// carry the comments around the attributes and blocks of a result over to
+/// `rendered`, the result formatted as HCL
+///
+/// `body` must be the source the result came from parsed with [`hcl_edit`],
+/// and `path` is where the result was found (as for [`to_hcl_raw`]); the
+/// comments on the lines before an attribute or block and the comment after
+/// it on the same line are kept, at any depth, but only blocks and the whole
+/// document have attributes and blocks, so `None` is returned for any other
+/// result (or if `rendered` isn't HCL for the same structures)
+pub fn keep_comments(rendered: &str, body: &Body, path: &Path) -> Option<String> {
+    let mut source_body = body;
+    let mut source_block = None;
+    for segment in &path.segments {
+        let Segment::Block { index, .. } = segment else {
+            return None;
+        };
+        let Structure::Block(block) = source_body.get(*index)? else {
+            return None;
+        };
+        source_body = &block.body;
+        source_block = Some(block);
+    }
+    let mut rendered_body: Body = rendered.parse().ok()?;
+    match source_block {
+        Some(source_block) => {
+            let mut structure = rendered_body.iter_mut().next()?;
+            copy_comments(source_block.decor(), structure.decor_mut());
+            copy_body_comments(&source_block.body, &mut structure.as_block_mut()?.body);
+        }
+        None => copy_body_comments(source_body, &mut rendered_body),
+    }
+    Some(rendered_body.to_string())
+}
+
+/// copy the comments of each attribute and block in `source` to the one in
+/// `rendered` with the same name, which may be in a different order (e.g.
+/// with sorted keys)
+fn copy_body_comments(source: &Body, rendered: &mut Body) {
+    // the nth block of a type with the same labels is the nth in the source
+    let originals: Vec<_> = rendered
+        .iter()
+        .enumerate()
+        .map(|(i, structure)| {
+            let nth = rendered
+                .iter()
+                .take(i)
+                .filter(|before| same_structure(before, structure))
+                .count();
+            source
+                .iter()
+                .filter(|original| same_structure(original, structure))
+                .nth(nth)
+        })
+        .collect();
+    for (mut structure, original) in rendered.iter_mut().zip(originals) {
+        let Some(original) = original else {
+            continue;
+        };
+        copy_comments(original.decor(), structure.decor_mut());
+        if let (Structure::Block(original), Some(block)) = (original, structure.as_block_mut()) {
+            copy_body_comments(&original.body, &mut block.body);
+        }
+    }
+}
+
+/// whether `a` and `b` are attributes with the same key or blocks with the
+/// same identifier and labels
+fn same_structure(a: &Structure, b: &Structure) -> bool {
+    match (a, b) {
+        (Structure::Attribute(a), Structure::Attribute(b)) => a.key.as_str() == b.key.as_str(),
+        (Structure::Block(a), Structure::Block(b)) => {
+            a.ident.as_str() == b.ident.as_str()
+                && a.labels.len() == b.labels.len()
+                && a.labels
+                    .iter()
+                    .zip(&b.labels)
+                    .all(|(a, b)| a.as_str() == b.as_str())
+        }
+        _ => false,
+    }
+}
+
+/// the comments before a structure are written on their own lines at its
+/// indentation, and a comment after it follows it on the same line
+fn copy_comments(source: &Decor, rendered: &mut Decor) {
+    let comments = source.prefix().map_or("", |prefix| prefix.trim());
+    if !comments.is_empty() {
+        let prefix = rendered.prefix().map_or("", |prefix| prefix).to_string();
+        let indent = &prefix[prefix.rfind('\n').map_or(0, |i| i + 1)..];
+        let lines: String = comments
+            .lines()
+            .map(|line| match line.trim() {
+                "" => String::from("\n"),
+                line => format!("{line}\n{indent}"),
+            })
+            .collect();
+        rendered.set_prefix(format!("{prefix}{lines}"));
+    }
+    let comment = source.suffix().map_or("", |suffix| suffix.trim());
+    if !comment.is_empty() {
+        rendered.set_suffix(format!(" {comment}"));
+    }
+}
+
+/// the key to write a result found at `path` with as an attribute
+/// (`key = value`), which is `None` for results that aren't an attribute (or
+/// an object entry whose key can be an attribute's key)
+pub fn attribute_key<'a>(result: &QueryResult, path: &'a Path) -> Option<&'a str> {
+    if !matches!(result, QueryResult::Expr(_)) {
+        return None;
+    }
+    match path.segments.last()? {
+        Segment::Attribute { key, .. } => Some(key),
+        Segment::Entry(key) if hcl::Identifier::new(key.as_str()).is_ok() => Some(key),
+        _ => None,
+    }
+}
+
+/// the key to write a result found at `path` with so that it is a whole HCL
+/// document on its own
+///
+/// blocks and bodies already are one, so they need no key, an attribute (or
+/// an object entry, see [`attribute_key`]) is one written as `key = value`,
+/// and anything else (e.g. a list element) can't be one, which is an error
+pub fn standalone_key<'a>(
+    result: &QueryResult,
+    path: &'a Path,
+) -> Result<Option<&'a str>, Box<dyn Error>> {
+    match result {
+        QueryResult::Block(_) | QueryResult::Body(_) => Ok(None),
+        QueryResult::Expr(_) => match attribute_key(result, path) {
+            Some(key) => Ok(Some(key)),
+            None => Err(format!(
+                "the result at '{path}' can't stand alone as an HCL document, only attributes, blocks, and object entries keyed by a valid name can"
+            )
+            .into()),
+        },
+    }
+}
+
+/// render the original source text of a result, exactly as it was written
+///
+/// `body` must be `source` parsed with [`hcl_edit`], and `path` is where the
+/// result was found (see [`query_stages_with_paths`][crate::query_stages_with_paths]);
+/// the empty path (the identity filter `.`) is the whole source, and `None` is
+/// returned if the result isn't a single contiguous part of the
+/// source (e.g. a slice of a list)
+pub fn to_hcl_raw(source: &str, body: &Body, path: &Path) -> Option<String> {
+    enum Node<'a> {
+        Body(&'a Body),
+        Expr(&'a Expression),
+    }
+
+    if path.segments.is_empty() {
+        return Some(source.to_string());
+    }
+    let mut node = Node::Body(body);
+    let mut span = None;
+    for segment in &path.segments {
+        (node, span) = match (node, segment) {
+            (Node::Body(body), Segment::Attribute { index, .. }) => match body.get(*index)? {
+                Structure::Attribute(attr) => (Node::Expr(&attr.value), attr.value.span()),
+                Structure::Block(_) => return None,
+            },
+            (Node::Body(body), Segment::Block { index, .. }) => match body.get(*index)? {
+                Structure::Block(block) => (Node::Body(&block.body), block.span()),
+                Structure::Attribute(_) => return None,
+            },
+            (Node::Expr(Expression::Array(array)), Segment::Element(index)) => {
+                let expr = array.get(*index)?;
+                (Node::Expr(expr), expr.span())
+            }
+            (Node::Expr(Expression::Object(object)), Segment::Entry(key)) => {
+                let expr = object.iter().find_map(|(object_key, value)| {
+                    let name = match object_key.as_ident() {
+                        Some(ident) => Some(ident.as_str()),
+                        None => object_key.as_expr().and_then(|expr| expr.as_str()),
+                    };
+                    (name == Some(key.as_str())).then(|| value.expr())
+                })?;
+                (Node::Expr(expr), expr.span())
+            }
+            _ => return None,
+        };
+    }
+    source.get(span?).map(str::to_string)
+}
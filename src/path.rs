@@ -0,0 +1,120 @@
+//! the location of a [`QueryResult`][crate::query::QueryResult] in the
+//! document it was found in
+
+use std::fmt;
+
+use unicode_ident::{is_xid_continue, is_xid_start};
+
+use crate::parser::Builtin;
+
+/// one step from a value to something inside of it
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    /// the attribute at `index` in a body's structures
+    Attribute { index: usize, key: String },
+    /// the block at `index` in a body's structures
+    ///
+    /// `position` is where it is among the blocks that its identifier and
+    /// labels match in that body (e.g. the second of two `ingress` blocks is
+    /// `.ingress[1]`), when they match more than one
+    Block {
+        index: usize,
+        identifier: String,
+        labels: Vec<String>,
+        position: Option<usize>,
+    },
+    /// the list element at `index`
+    Element(usize),
+    /// the elements of a list from `start` up to (but not including) `end`
+    Slice { start: usize, end: usize },
+    /// the value of an object's `key`
+    Entry(String),
+    /// the output of a builtin applied to the value
+    Builtin(Builtin),
+}
+
+/// the [`Segment`]s leading from the root of a document to a value, the
+/// root itself has an empty path
+///
+/// a path is displayed as a filter that matches the value it leads to, e.g.
+/// `.resource{"aws_instance","web"}.tags["Env"]` is displayed as
+/// `.resource{"aws_instance","web"}.tags.Env`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    pub segments: Vec<Segment>,
+}
+
+impl Path {
+    /// a new path with `segment` appended to this one
+    pub fn join(&self, segment: Segment) -> Path {
+        let mut segments = self.segments.clone();
+        segments.push(segment);
+        Path { segments }
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.segments.is_empty() {
+            return write!(f, ".");
+        }
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Attribute { key, .. } => write_name(f, key)?,
+                Segment::Block {
+                    identifier,
+                    labels,
+                    position,
+                    ..
+                } => {
+                    write_name(f, identifier)?;
+                    if !labels.is_empty() {
+                        let labels: Vec<_> = labels.iter().map(|label| quote(label)).collect();
+                        write!(f, "{{{}}}", labels.join(","))?;
+                    }
+                    if let Some(position) = position {
+                        write!(f, "[{position}]")?;
+                    }
+                }
+                Segment::Element(index) => write!(f, "[{index}]")?,
+                Segment::Slice { start, end } => write!(f, "[{start}:{end}]")?,
+                Segment::Entry(key) => write_name(f, key)?,
+                // a builtin can only start a path, so anything before it is
+                // piped into it
+                Segment::Builtin(builtin) if i == 0 => write!(f, "{builtin}")?,
+                Segment::Builtin(builtin) => write!(f, " | {builtin}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// write `.name`, quoting the name if it isn't a valid bare name
+fn write_name(f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+    let mut chars = name.chars();
+    let bare = chars.next().is_some_and(|c| is_xid_start(c) || c == '_')
+        && chars.all(|c| is_xid_continue(c) || c == '-');
+    if bare {
+        write!(f, ".{name}")
+    } else {
+        write!(f, ".{}", quote(name))
+    }
+}
+
+/// `s` in quotes, escaped the way a filter unescapes it, so that newlines and
+/// other control characters survive being copied from a terminal
+pub(crate) fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
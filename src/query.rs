@@ -1,120 +1,1936 @@
 //! use the [`hcl-rs`][hcl] crate to query HCL documents
 
-use std::error::Error;
+use std::{cmp::Ordering, collections::HashMap, error::Error, fmt};
 
-use hcl::{Block, Body, Expression, Identifier, ObjectKey};
+use annotate_snippets::{Level, Snippet};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hcl::{
+    template::{Element, Template},
+    Attribute, Block, Body, Expression, FuncCall, ObjectKey, Structure, Value,
+};
 
-use crate::parser::Field;
+use crate::{
+    parser::{
+        Builtin, Comparison, Field, Index, Label, Literal, Predicate, Selector, Stage,
+        WILDCARD_LABEL,
+    },
+    path::{quote, Path, Segment},
+};
+
+pub struct QueryError {
+    reason: String,
+    kind: Option<QueryErrorKind>,
+}
+
+/// the errors that [`HqError`][crate::HqError] has a variant of its own for
+pub(crate) enum QueryErrorKind {
+    TypeMismatch(TypeMismatch),
+    /// a field with labels matched an attribute (or object key) at this path
+    LabelOnAttribute {
+        path: String,
+        label: String,
+    },
+}
+
+/// a segment of the filter was applied to the wrong kind of value, e.g. a
+/// string was indexed with `[0]`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeMismatch {
+    /// where the value is, written as a filter (e.g. `.region`)
+    pub path: String,
+    /// the segment of the filter that was applied to the value (e.g. `[0]`)
+    pub segment: String,
+    /// the kinds of value the segment applies to (e.g. `a list`)
+    pub expected: String,
+    /// the kind of value it was applied to (e.g. `a string`)
+    pub found: String,
+}
+
+impl QueryError {
+    fn new(reason: &str) -> Self {
+        QueryError {
+            reason: reason.to_string(),
+            kind: None,
+        }
+    }
+
+    /// why the filter couldn't be applied, in a sentence
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// the type mismatch that caused the error, if that's what it was
+    pub fn type_mismatch(&self) -> Option<&TypeMismatch> {
+        match self.kind {
+            Some(QueryErrorKind::TypeMismatch(ref mismatch)) => Some(mismatch),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn into_kind(self) -> Result<QueryErrorKind, Self> {
+        match self.kind {
+            Some(kind) => Ok(kind),
+            None => Err(self),
+        }
+    }
+}
+
+impl fmt::Debug for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // these are meant to be read, and are shown the same either way
+        write!(f, "{self}")
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.type_mismatch() {
+            Some(mismatch) => write!(f, "{mismatch}"),
+            None => write!(f, "failed to query HCL: {}", self.reason),
+        }
+    }
+}
+
+impl Error for QueryError {}
+
+/// renders the path and the offending segment with the segment underlined
+impl fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = format!("{}{}", self.path, self.segment);
+        let span = self.path.len()..source.len();
+        let label = format!("'{}' is {}, not {}", self.path, self.found, self.expected);
+        let message = Level::Error
+            .title("failed to query HCL")
+            .snippet(Snippet::source(&source).annotation(Level::Error.span(span).label(&label)));
+        let renderer = crate::error::renderer(crate::error::colored());
+        let rendered = renderer.render(message);
+
+        write!(f, "{}", rendered)
+    }
+}
+
+fn err(reason: &str) -> Box<QueryError> {
+    Box::new(QueryError::new(reason))
+}
+
+/// `segment` of the filter was applied to `found` at `path`, which isn't one of
+/// the kinds of value it applies to (`expected`)
+fn type_mismatch(path: &Path, segment: String, expected: &str, found: &str) -> Box<QueryError> {
+    let path = path.to_string();
+    Box::new(QueryError {
+        reason: type_mismatch_reason(&path, &segment, expected, found),
+        kind: Some(QueryErrorKind::TypeMismatch(TypeMismatch {
+            path,
+            segment,
+            expected: expected.to_string(),
+            found: found.to_string(),
+        })),
+    })
+}
+
+/// the reason for a [`QueryErrorKind::TypeMismatch`] error
+pub(crate) fn type_mismatch_reason(
+    path: &str,
+    segment: &str,
+    expected: &str,
+    found: &str,
+) -> String {
+    format!("cannot apply '{segment}' to '{path}', it is {found}, not {expected}")
+}
+
+/// the reason for a [`QueryErrorKind::LabelOnAttribute`] error
+pub(crate) fn label_on_attribute_reason(path: &str, label: &str) -> String {
+    format!(
+        "'{path}' is an attribute, not a block, so it has no labels to match {{{}}}; \
+         use [{}] to look up a key in it",
+        quote(label),
+        quote(label),
+    )
+}
+
+/// `field` has labels, but its name only matched the attribute (or object key)
+/// at `path`
+fn label_on_attribute(field: &Field, path: &Path) -> Box<QueryError> {
+    let path = path.to_string();
+    let label = match field.labels[0] {
+        Label::Exact(ref label) => label.clone(),
+        Label::Any => WILDCARD_LABEL.to_string(),
+    };
+    Box::new(QueryError {
+        reason: label_on_attribute_reason(&path, &label),
+        kind: Some(QueryErrorKind::LabelOnAttribute { path, label }),
+    })
+}
+
+/// a short description of the kind of `query_result` for error messages
+fn describe_result(query_result: &QueryResult) -> &'static str {
+    match query_result {
+        QueryResult::Expr(expr) => describe(expr),
+        QueryResult::Block(_) => "a block",
+        QueryResult::Body(_) => "the document",
+    }
+}
+
+/// how deep [`QueryOptions::max_depth`] lets `..` look by default
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// options that control how a filter is matched against an HCL document
+#[derive(Clone, Debug)]
+pub struct QueryOptions {
+    /// compare attribute names, block identifiers, block labels, and object
+    /// keys case-insensitively
+    pub ignore_case: bool,
+    /// the values of the variables a predicate can compare to (`$name`), by
+    /// name without the `$`
+    ///
+    /// a value is a string, number, or bool, comparing to a variable that isn't
+    /// here is an error
+    pub variables: HashMap<String, Literal>,
+    /// how many levels of blocks, attributes, object entries, and list
+    /// elements deep `..` looks before giving up with an error, rather than
+    /// overflowing the stack on a document nested deeper than that
+    ///
+    /// the levels are counted from where `..` starts, so in `.a.b..c` they
+    /// don't include `a` and `b`
+    pub max_depth: usize,
+    /// make it an error for a field's labels to match only the first of a
+    /// block's labels, e.g. `.resource{"aws_instance"}` matching
+    /// `resource "aws_instance" "web"`, rather than matching it as a prefix
+    pub strict_labels: bool,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        QueryOptions {
+            ignore_case: false,
+            variables: HashMap::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            strict_labels: false,
+        }
+    }
+}
+
+impl QueryOptions {
+    /// whether `name` from the HCL document matches `filter_name`
+    fn matches(&self, filter_name: &str, name: &str) -> bool {
+        if self.ignore_case {
+            filter_name
+                .chars()
+                .flat_map(char::to_lowercase)
+                .eq(name.chars().flat_map(char::to_lowercase))
+        } else {
+            filter_name == name
+        }
+    }
+
+    /// whether `name` from the HCL document matches the name of `field`, or
+    /// its regex if it has one (which is used as is, `(?i)` makes it
+    /// case-insensitive)
+    fn field_matches(&self, field: &Field, name: &str) -> bool {
+        match field.pattern {
+            Some(ref pattern) => pattern.is_match(name),
+            None => field.wildcard || self.matches(&field.name, name),
+        }
+    }
+}
 
 /// a portion of an HCL document that matched the provided filter
+#[derive(Clone)]
 pub enum QueryResult {
     /// an HCL [`Expression`] matched the filter
     Expr(Expression),
     /// an HCL [`Block`] matched the filter
     Block(Block),
+    /// the whole document matched the filter (the identity filter `.`)
+    Body(Body),
 }
 
 impl QueryResult {
     pub fn to_string(&self) -> Result<String, Box<dyn Error>> {
-        let s = match self {
-            // beware `hcl::to_string`!
-            // https://github.com/martinohmann/hcl-rs/issues/344
-            Self::Expr(expr) => hcl::format::to_string(expr)?,
-            Self::Block(block) => hcl::format::to_string(block)?,
-        };
-        Ok(s)
+        // beware `hcl::to_string`!
+        // https://github.com/martinohmann/hcl-rs/issues/344
+        crate::output::to_hcl_indented(self, crate::output::Indent::default())
+    }
+
+    /// convert the result into the HCL value model
+    ///
+    /// blocks (and the whole document) are converted following the
+    /// [HCL JSON specification][spec], so a block's labels become nested object
+    /// keys, and expressions that can't be
+    /// statically evaluated (e.g. `var.foo`) are converted to their source in
+    /// an interpolated string (e.g. `"${var.foo}"`)
+    ///
+    /// heredocs are converted to the string they contain, including the final
+    /// newline, with the common indentation of `<<-` heredocs removed
+    ///
+    /// use [`Self::is_literal`] to tell whether anything was left unevaluated
+    ///
+    /// [spec]: https://github.com/hashicorp/hcl/blob/main/json/spec.md
+    pub fn to_value(&self) -> Value {
+        match self {
+            Self::Expr(expr) => Value::from(expr.clone()),
+            Self::Block(block) => Value::from(block.clone()),
+            Self::Body(body) => Value::from(body.clone()),
+        }
+    }
+
+    /// whether the result is made up of literal values only
+    ///
+    /// a result that isn't contains expressions that can't be statically
+    /// evaluated, like references, function calls, and `for` expressions,
+    /// which [`Self::to_value`] converts to their source
+    pub fn is_literal(&self) -> bool {
+        match self {
+            Self::Expr(expr) => expr_is_literal(expr),
+            Self::Block(block) => body_is_literal(block.body()),
+            Self::Body(body) => body_is_literal(body),
+        }
+    }
+}
+
+fn body_is_literal(body: &Body) -> bool {
+    body.iter().all(|structure| match structure {
+        Structure::Attribute(attr) => expr_is_literal(attr.expr()),
+        Structure::Block(block) => body_is_literal(block.body()),
+    })
+}
+
+fn expr_is_literal(expr: &Expression) -> bool {
+    match expr {
+        Expression::Null | Expression::Bool(_) | Expression::Number(_) | Expression::String(_) => {
+            true
+        }
+        Expression::Array(array) => array.iter().all(expr_is_literal),
+        Expression::Object(object) => object
+            .iter()
+            .all(|(key, value)| object_key_name(key).is_some() && expr_is_literal(value)),
+        // a template without interpolations or directives is just a string
+        Expression::TemplateExpr(template) => Template::from_expr(template).is_ok_and(|template| {
+            template
+                .elements()
+                .iter()
+                .all(|element| matches!(element, Element::Literal(_)))
+        }),
+        Expression::Parenthesis(expr) => expr_is_literal(expr),
+        _ => false,
     }
 }
 
 /// given a vector of [`Field`]s return a vector of [`QueryResult`]s
 ///
 /// a result vector with multiple results indicates that multiple entities
-/// matched the provided filter
-pub fn query(fields: &mut Vec<Field>, body: &Body) -> Vec<QueryResult> {
-    if fields.is_empty() {
-        // our grammar/parser for filters won't allow an empty filter
-        unreachable!();
+/// matched the provided filter, and they are always in the order they appear
+/// in the source (the order of a list's elements or an object's keys, and
+/// attributes and blocks in the order they are written)
+///
+/// an error is returned if a [`Field`] indexes past the end of a list or
+/// indexes something that is not a list (slices are clamped to the bounds of
+/// the list instead), unless the field is optional (`.foo[5]?`) in which case
+/// a missing element is skipped but indexing something that is not a list is
+/// still an error
+///
+/// a name that only matched blocks is indexed as if the blocks were a list
+/// (`.ingress[2]` is the third `ingress` block), while a name that matched a
+/// list attribute indexes into the list
+///
+/// each name after the first is looked up in whatever the previous field
+/// matched, a block's body or an object's keys alike, so `.settings.mode`
+/// matches both `settings { mode = "fast" }` and `settings = { mode = "fast" }`
+///
+/// a name that doesn't match any attribute or block is never an error, it
+/// simply produces no results, but a [`Field`] with more labels than a block
+/// of that name has is an error, and so is a [`Field`] with labels whose name
+/// only matches attributes or object keys (which have no labels)
+///
+/// a [`Field`] with a key (`.tags["Env"]`) looks the key up in the objects
+/// that the field matched, a missing key produces no results but looking up a
+/// key in a block (use `{"label"}` to match block labels) or in something that
+/// is not an object is an error, unless the field is optional
+///
+/// a [`Field`] with a [`Predicate`] (`.resource[.ami == "ami-123"]`, or
+/// `.resource[has("lifecycle")]` to test for a member whatever its value)
+/// keeps only the matches that meet it, which is applied after any list selector
+/// (so `.rules[][.port == 80]` keeps the elements of `rules` with that port)
+///
+/// iterating with `[]` expands a list (or the values of an object) into one
+/// result per element, and any subsequent fields are applied to each of them
+///
+/// a recursive [`Field`] (`..name`) matches at any depth, and its results are
+/// in pre-order: each match comes before any matches nested inside of it, and
+/// siblings are visited in source order
+///
+/// an empty vector of [`Field`]s (the identity filter `.`) matches the whole
+/// document
+pub fn query(fields: &[Field], body: &Body) -> Result<Vec<QueryResult>, Box<QueryError>> {
+    let mut results = Vec::new();
+    body_path_each(fields, body, None, &QueryOptions::default(), &mut |(
+        _,
+        query_result,
+    )| {
+        results.push(query_result);
+        Ok::<(), Box<QueryError>>(())
+    })?;
+    Ok(results)
+}
+
+/// given a vector of [`Stage`]s return a vector of [`QueryResult`]s
+///
+/// the first stage queries `body` and each subsequent stage queries the
+/// results of the stage before it (e.g. `.foo | .bar` is equivalent to
+/// `.foo.bar`), and within a stage the results of each comma-separated path
+/// are concatenated in order
+///
+/// see [`query`] for how each path is evaluated
+pub fn query_stages(
+    stages: Vec<Stage>,
+    body: &Body,
+    options: &QueryOptions,
+) -> Result<Vec<QueryResult>, Box<QueryError>> {
+    let mut results = Vec::new();
+    query_stages_each(&stages, body, options, |_, query_result| {
+        results.push(query_result);
+        Ok::<(), Box<QueryError>>(())
+    })?;
+    Ok(results)
+}
+
+/// like [`query_stages`], but each [`QueryResult`] is paired with the
+/// [`Path`] to where it was found in `body`
+pub fn query_stages_with_paths(
+    stages: Vec<Stage>,
+    body: &Body,
+    options: &QueryOptions,
+) -> Result<Vec<(Path, QueryResult)>, Box<QueryError>> {
+    let mut matches = Vec::new();
+    query_stages_each(&stages, body, options, |path, query_result| {
+        matches.push((path, query_result));
+        Ok::<(), Box<QueryError>>(())
+    })?;
+    Ok(matches)
+}
+
+/// like [`query_stages_with_paths`], but each [`QueryResult`] is passed to
+/// `emit` as soon as it is found instead of being collected
+///
+/// results are emitted in the same order, and evaluation stops at the first
+/// error from either the query or `emit`
+///
+/// results are found one at a time, except that a stage (other than the
+/// first) with more than one comma-separated path needs all of the results of
+/// the stages before it, so those are collected first (a stage with only
+/// `first` stops at the first of them, and one with only `last` keeps just
+/// the last)
+pub fn query_stages_each<E>(
+    stages: &[Stage],
+    body: &Body,
+    options: &QueryOptions,
+    mut emit: impl FnMut(Path, QueryResult) -> Result<(), E>,
+) -> Result<(), E>
+where
+    E: From<Box<QueryError>>,
+{
+    stages_each(stages, Input::Body(body), options, &mut |(
+        path,
+        query_result,
+    )| {
+        emit(path, query_result)
+    })
+}
+
+/// like [`query_stages_each`], but the first stage queries `input` (found at
+/// `path`) instead of a document's body
+///
+/// this is how results are queried further after they have been combined
+/// (e.g. the results of several documents gathered into one list)
+pub fn query_stages_each_on<E>(
+    stages: &[Stage],
+    path: Path,
+    input: QueryResult,
+    options: &QueryOptions,
+    mut emit: impl FnMut(Path, QueryResult) -> Result<(), E>,
+) -> Result<(), E>
+where
+    E: From<Box<QueryError>>,
+{
+    let input = (path, input);
+    stages_each(stages, Input::Result(&input), options, &mut |(
+        path,
+        query_result,
+    )| {
+        emit(path, query_result)
+    })
+}
+
+/// a [`QueryResult`] and where it was found
+type Match = (Path, QueryResult);
+
+/// the function a query passes each of its results to
+type Emit<'a, E> = dyn FnMut(Match) -> Result<(), E> + 'a;
+
+/// what the first stage of a filter queries
+#[derive(Clone, Copy)]
+enum Input<'a> {
+    Body(&'a Body),
+    Result(&'a Match),
+}
+
+fn stages_each<E>(
+    stages: &[Stage],
+    input: Input,
+    options: &QueryOptions,
+    emit: &mut Emit<E>,
+) -> Result<(), E>
+where
+    E: From<Box<QueryError>>,
+{
+    // the last stage that has to collect its inputs, every stage after it can
+    // be evaluated one input at a time
+    let collecting = stages
+        .iter()
+        .enumerate()
+        .skip(1)
+        .rfind(|(_, stage)| {
+            stage.paths.len() > 1 || stage.paths.iter().any(|fields| is_stream_path(fields))
+        })
+        .map(|(i, _)| i);
+
+    match collecting {
+        None => {
+            let Some((first, rest)) = stages.split_first() else {
+                // our grammar/parser for filters won't allow an empty filter
+                unreachable!();
+            };
+            // the paths all look names up in the same input
+            let index = match input {
+                _ if first.paths.len() == 1 => None,
+                Input::Body(body) => Some(BodyIndex::new(body)),
+                Input::Result((_, query_result)) => BodyIndex::of(query_result),
+            };
+            for fields in &first.paths {
+                input_path_each(fields, input, index.as_ref(), options, &mut |m| {
+                    chain_each(rest, m, options, emit)
+                })?;
+            }
+        }
+        Some(i) => {
+            let inputs = stage_inputs(&stages[..i], &stages[i], input, options)?;
+            // with several paths, each input is looked up in by all of them
+            let indexes: Vec<_> = match stages[i].paths.len() {
+                1 => Vec::new(),
+                _ => inputs
+                    .iter()
+                    .map(|(_, input)| BodyIndex::of(input))
+                    .collect(),
+            };
+            // each input goes through every path before the next input
+            for j in 0..inputs.len() {
+                for fields in &stages[i].paths {
+                    let selected = match fields.first().and_then(|field| field.builtin) {
+                        Some(Builtin::First) => j == 0,
+                        Some(Builtin::Last) => j + 1 == inputs.len(),
+                        _ => true,
+                    };
+                    if !selected {
+                        continue;
+                    }
+                    // a stream builtin has already been applied by picking its input
+                    let fields = match is_stream_path(fields) {
+                        true => &fields[1..],
+                        false => &fields[..],
+                    };
+                    let emit = &mut |m| chain_each(&stages[i + 1..], m, options, emit);
+                    match indexes.get(j) {
+                        Some(Some(index)) => {
+                            indexed_path_each(fields, &inputs[j], index, options, emit)?;
+                        }
+                        _ => path_each(fields, inputs[j].clone(), options, emit)?,
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// whether `fields` starts with a builtin that is applied to all of its
+/// inputs together (`first` or `last`)
+fn is_stream_path(fields: &[Field]) -> bool {
+    fields
+        .first()
+        .and_then(|field| field.builtin)
+        .is_some_and(|builtin| builtin.is_stream())
+}
+
+/// a query was stopped early, either because it found what it was looking for
+/// or because of an error
+enum Halt<E> {
+    Found,
+    Error(E),
+}
+
+impl<E: From<Box<QueryError>>> From<Box<QueryError>> for Halt<E> {
+    fn from(value: Box<QueryError>) -> Self {
+        Halt::Error(E::from(value))
+    }
+}
+
+/// the results of `stages`, which are the inputs of `stage`
+///
+/// when `stage` only needs the first result (`first`) the query stops as soon
+/// as it is found, and when it only needs the last (`last`) only that one is
+/// kept
+fn stage_inputs<E>(
+    stages: &[Stage],
+    stage: &Stage,
+    input: Input,
+    options: &QueryOptions,
+) -> Result<Vec<Match>, E>
+where
+    E: From<Box<QueryError>>,
+{
+    let only = |builtin| {
+        stage
+            .paths
+            .iter()
+            .all(|fields| fields.first().and_then(|field| field.builtin) == Some(builtin))
+    };
+    let mut inputs = Vec::new();
+    if only(Builtin::First) {
+        let found = stages_each(stages, input, options, &mut |m| {
+            inputs.push(m);
+            Err(Halt::Found)
+        });
+        match found {
+            Ok(()) | Err(Halt::Found) => {}
+            Err(Halt::Error(e)) => return Err(e),
+        }
+    } else if only(Builtin::Last) {
+        stages_each(stages, input, options, &mut |m| {
+            inputs.clear();
+            inputs.push(m);
+            Ok::<(), E>(())
+        })?;
+    } else {
+        stages_each(stages, input, options, &mut |m| {
+            inputs.push(m);
+            Ok::<(), E>(())
+        })?;
+    }
+    Ok(inputs)
+}
+
+/// `index`, if there is one, is of the body of `input`
+fn input_path_each<E>(
+    fields: &[Field],
+    input: Input,
+    index: Option<&BodyIndex>,
+    options: &QueryOptions,
+    emit: &mut Emit<E>,
+) -> Result<(), E>
+where
+    E: From<Box<QueryError>>,
+{
+    match (input, index) {
+        (Input::Body(body), _) => body_path_each(fields, body, index, options, emit),
+        (Input::Result(m), Some(index)) => indexed_path_each(fields, m, index, options, emit),
+        (Input::Result(m), None) => path_each(fields, m.clone(), options, emit),
+    }
+}
+
+/// like [`path_each`], where `m` is a block or the document and `index` is of
+/// its body, but `m` isn't copied when the first field looks a name up in it
+fn indexed_path_each<E>(
+    fields: &[Field],
+    m: &Match,
+    index: &BodyIndex,
+    options: &QueryOptions,
+    emit: &mut Emit<E>,
+) -> Result<(), E>
+where
+    E: From<Box<QueryError>>,
+{
+    let (path, query_result) = m;
+    let body = match query_result {
+        QueryResult::Block(block) => block.body(),
+        QueryResult::Body(body) => body,
+        QueryResult::Expr(_) => return path_each(fields, m.clone(), options, emit),
+    };
+    match fields.split_first() {
+        Some((field, rest)) if field.builtin.is_none() => {
+            let matches = body_query(field, path, body, Some(index), options)?;
+            rest_each(field, matches, rest, options, emit)
+        }
+        _ => path_each(fields, m.clone(), options, emit),
     }
+}
 
+/// evaluate `stages`, which each have a single path, on `m`
+fn chain_each<E>(
+    stages: &[Stage],
+    m: Match,
+    options: &QueryOptions,
+    emit: &mut Emit<E>,
+) -> Result<(), E>
+where
+    E: From<Box<QueryError>>,
+{
+    match stages.split_first() {
+        None => emit(m),
+        Some((stage, rest)) => path_each(&stage.paths[0], m, options, &mut |m| {
+            chain_each(rest, m, options, emit)
+        }),
+    }
+}
+
+/// `index`, if there is one, is of `body` (see [`body_query`])
+fn body_path_each<E>(
+    fields: &[Field],
+    body: &Body,
+    index: Option<&BodyIndex>,
+    options: &QueryOptions,
+    emit: &mut Emit<E>,
+) -> Result<(), E>
+where
+    E: From<Box<QueryError>>,
+{
     // take the first field and do a `Body` query
     // e.g. `.foo.bar` will start with 'foo'
-    let field = fields.remove(0);
-    let mut query_result = body_query(&field, body);
+    let Some((field, rest)) = fields.split_first() else {
+        // the identity filter `.` has no fields
+        return emit((Path::default(), QueryResult::Body(body.clone())));
+    };
+    let matches = match field.builtin {
+        // the document is the only input, so it is both the first and the last
+        Some(builtin) if builtin.is_stream() => {
+            vec![(Path::default(), QueryResult::Body(body.clone()))]
+        }
+        Some(Builtin::Values) => body_values(&Path::default(), body),
+        Some(builtin) => match body_builtin(builtin, body) {
+            Some(output) => {
+                let path = Path::default().join(Segment::Builtin(builtin));
+                vec![(path, QueryResult::Expr(output))]
+            }
+            None if field.optional => Vec::new(),
+            None => return Err(cannot_apply(builtin, "the document").into()),
+        },
+        None => body_query(field, &Path::default(), body, index, options)?,
+    };
+    rest_each(field, matches, rest, options, emit)
+}
 
-    // iteratively evaluate each subsequent field
+/// evaluate `fields` on `m`, depth-first so each result is emitted as soon
+/// as it is found
+fn path_each<E>(
+    fields: &[Field],
+    m: Match,
+    options: &QueryOptions,
+    emit: &mut Emit<E>,
+) -> Result<(), E>
+where
+    E: From<Box<QueryError>>,
+{
     // e.g. having handled 'foo' we move on to 'bar'
-    while !fields.is_empty() {
-        let field = fields.remove(0);
-        query_result = result_query(&field, query_result);
+    let Some((field, rest)) = fields.split_first() else {
+        return emit(m);
+    };
+    let matches = match field.builtin {
+        Some(builtin) => builtin_query(field, builtin, vec![m])?,
+        None => result_query(field, vec![m], options)?,
+    };
+    rest_each(field, matches, rest, options, emit)
+}
+
+/// apply the parts of `field` that narrow down what its name matched to
+/// `matches`, and evaluate the fields after it on each of them
+fn rest_each<E>(
+    field: &Field,
+    matches: Vec<Match>,
+    rest: &[Field],
+    options: &QueryOptions,
+    emit: &mut Emit<E>,
+) -> Result<(), E>
+where
+    E: From<Box<QueryError>>,
+{
+    for m in refine_query(field, matches, options)? {
+        let (matches, rest) = label_steps(field, m, rest, options)?;
+        for m in matches {
+            path_each(rest, m, options, emit)?;
+        }
     }
+    Ok(())
+}
 
-    query_result
+/// match the bracketed fields at the start of `rest` (`["aws_instance"]`)
+/// with the labels of the block `m`, after the ones `field` matched, the way
+/// `{"aws_instance"}` would
+///
+/// returns what is left of `m` (nothing if a label didn't match) and the
+/// fields after the ones that matched labels
+fn label_steps<'f>(
+    field: &Field,
+    m: Match,
+    rest: &'f [Field],
+    options: &QueryOptions,
+) -> Result<(Vec<Match>, &'f [Field]), Box<QueryError>> {
+    let mut matched = field.labels.len();
+    let mut matches = vec![m];
+    let mut rest = rest;
+    while let Some((next, after)) = rest.split_first() {
+        if !next.bracketed || !next.labels.is_empty() {
+            break;
+        }
+        let Some((_, QueryResult::Block(block))) = matches.first() else {
+            break;
+        };
+        let Some(label) = block.labels().get(matched) else {
+            break;
+        };
+        if !options.matches(&next.name, label.as_str()) {
+            return Ok((Vec::new(), after));
+        }
+        matched += 1;
+        matches = refine_query(next, matches, options)?;
+        rest = after;
+    }
+    Ok((matches, rest))
 }
 
-fn body_query(field: &Field, body: &Body) -> Vec<QueryResult> {
+/// like [`path_each`] but the results are collected
+fn path_collect(
+    fields: &[Field],
+    m: Match,
+    options: &QueryOptions,
+) -> Result<Vec<Match>, Box<QueryError>> {
     let mut matches = Vec::new();
-    let mut attr_matches = attr_query(&field.name, body);
-    matches.append(&mut attr_matches);
-    let mut block_matches = block_query(field, body);
-    matches.append(&mut block_matches);
-    matches
+    path_each(fields, m, options, &mut |m| {
+        matches.push(m);
+        Ok::<(), Box<QueryError>>(())
+    })?;
+    Ok(matches)
 }
 
-fn attr_query(field: &str, body: &Body) -> Vec<QueryResult> {
-    let mut matches = Vec::new();
-    for attr in body.attributes() {
-        if attr.key() == field {
-            matches.push(QueryResult::Expr(attr.expr().clone()));
+/// apply the parts of `field` that narrow down what its name matched
+fn refine_query(
+    field: &Field,
+    matches: Vec<Match>,
+    options: &QueryOptions,
+) -> Result<Vec<Match>, Box<QueryError>> {
+    let matches = type_query(field, matches);
+    let matches = key_query(field, matches, options)?;
+    let matches = selector_query(field, matches)?;
+    predicate_query(field, matches, options)
+}
+
+fn builtin_query(
+    field: &Field,
+    builtin: Builtin,
+    matches: Vec<Match>,
+) -> Result<Vec<Match>, Box<QueryError>> {
+    if builtin.is_stream() {
+        // applied to a single input at a time, which is both the first and the last
+        return Ok(matches);
+    }
+    let mut outputs = Vec::new();
+    if builtin == Builtin::Values {
+        for (path, query_result) in matches {
+            outputs.extend(values(field, path, query_result)?);
         }
+        return Ok(outputs);
     }
-    matches
+    for (path, query_result) in matches {
+        let output = match query_result {
+            QueryResult::Block(ref block) => body_builtin(builtin, block.body()),
+            QueryResult::Body(ref body) => body_builtin(builtin, body),
+            QueryResult::Expr(ref expr) => expr_builtin(builtin, expr)?,
+        };
+        let output = match output {
+            Some(output) => output,
+            // an optional builtin (`keys?`) ignores inputs it can't be applied to
+            None if field.optional => continue,
+            None => {
+                let what = match query_result {
+                    QueryResult::Block(_) => "a block",
+                    QueryResult::Body(_) => "the document",
+                    QueryResult::Expr(ref expr) => describe(expr),
+                };
+                return Err(cannot_apply(builtin, what));
+            }
+        };
+        outputs.push((
+            path.join(Segment::Builtin(builtin)),
+            QueryResult::Expr(output),
+        ));
+    }
+    Ok(outputs)
 }
 
-fn block_query(field: &Field, body: &Body) -> Vec<QueryResult> {
-    let mut matches = Vec::new();
-    for block in body.blocks() {
-        if block.identifier() != field.name {
-            continue;
+fn cannot_apply(builtin: Builtin, what: &str) -> Box<QueryError> {
+    err(&format!(
+        "cannot apply '{}' to {what} (it applies to {})",
+        builtin.name(),
+        applies_to(builtin),
+    ))
+}
+
+/// the kinds of input `builtin` can be applied to, for error messages
+fn applies_to(builtin: Builtin) -> &'static str {
+    match builtin {
+        Builtin::Keys | Builtin::Values => "blocks, objects, and lists",
+        Builtin::Length => "blocks, objects, lists, strings, and null",
+        Builtin::Type => "anything",
+        Builtin::Duplicates => "blocks",
+        Builtin::First | Builtin::Last => "anything",
+        Builtin::Paths | Builtin::LeafPaths => "anything",
+        Builtin::Base64 | Builtin::Base64Decode => "strings",
+        Builtin::Add | Builtin::Flatten(_) => "lists",
+    }
+}
+
+/// a short description of the kind of `expr` for error messages
+fn describe(expr: &Expression) -> &'static str {
+    match expr {
+        Expression::Null => "null",
+        Expression::Bool(_) => "a bool",
+        Expression::Number(_) => "a number",
+        Expression::String(_) | Expression::TemplateExpr(_) => "a string",
+        Expression::Array(_) => "a list",
+        Expression::Object(_) => "an object",
+        Expression::Variable(_) | Expression::Traversal(_) => "a reference",
+        Expression::FuncCall(_) => "a function call",
+        Expression::Conditional(_) => "a conditional",
+        Expression::Operation(_) => "an operation",
+        Expression::ForExpr(_) => "a for expression",
+        _ => "an expression",
+    }
+}
+
+/// the outputs of `values` for `query_result`, each of which is where it was
+/// found, like the elements that `[]` iterates over
+fn values(
+    field: &Field,
+    path: Path,
+    query_result: QueryResult,
+) -> Result<Vec<Match>, Box<QueryError>> {
+    Ok(match query_result {
+        QueryResult::Expr(Expression::Array(array)) => array
+            .into_iter()
+            .enumerate()
+            .map(|(i, expr)| (path.join(Segment::Element(i)), QueryResult::Expr(expr)))
+            .collect(),
+        QueryResult::Expr(Expression::Object(object)) => object
+            .into_iter()
+            .map(|(key, expr)| (path.join(entry_segment(&key)), QueryResult::Expr(expr)))
+            .collect(),
+        QueryResult::Block(block) => body_values(&path, block.body()),
+        QueryResult::Body(body) => body_values(&path, &body),
+        // an optional builtin (`values?`) ignores inputs it can't be applied to
+        QueryResult::Expr(_) if field.optional => Vec::new(),
+        QueryResult::Expr(ref expr) => return Err(cannot_apply(Builtin::Values, describe(expr))),
+    })
+}
+
+/// the attribute values and blocks of `body`, which is at `path`
+fn body_values(path: &Path, body: &Body) -> Vec<Match> {
+    let positions = block_positions(body);
+    body.iter()
+        .enumerate()
+        .map(|(index, structure)| match structure {
+            Structure::Attribute(attr) => (
+                path.join(attr_segment(index, attr)),
+                QueryResult::Expr(attr.expr().clone()),
+            ),
+            Structure::Block(block) => (
+                path.join(block_segment(index, block, positions[index])),
+                QueryResult::Block(block.clone()),
+            ),
+        })
+        .collect()
+}
+
+/// returns `None` if `builtin` can't be applied to a body
+fn body_builtin(builtin: Builtin, body: &Body) -> Option<Expression> {
+    let output = match builtin {
+        Builtin::Keys => {
+            let mut keys: Vec<_> = body
+                .iter()
+                .map(|structure| match structure {
+                    Structure::Attribute(attr) => attr.key().to_string(),
+                    Structure::Block(block) => block.identifier().to_string(),
+                })
+                .collect();
+            keys.sort();
+            // blocks often share an identifier (e.g. `resource`)
+            keys.dedup();
+            Expression::Array(keys.into_iter().map(Expression::String).collect())
+        }
+        Builtin::Length => Expression::from(body.iter().count() as u64),
+        Builtin::Type => Expression::from("object"),
+        Builtin::Duplicates => {
+            let mut seen = Vec::new();
+            let mut duplicates = Vec::new();
+            for block in body.blocks() {
+                // unlabeled blocks (e.g. `ingress {}`) are often meant to repeat
+                if block.labels().is_empty() {
+                    continue;
+                }
+                let id: Vec<_> = std::iter::once(block.identifier())
+                    .chain(block.labels().iter().map(|label| label.as_str()))
+                    .collect();
+                if seen.contains(&id) {
+                    if !duplicates.contains(&id) {
+                        duplicates.push(id);
+                    }
+                } else {
+                    seen.push(id);
+                }
+            }
+            Expression::Array(
+                duplicates
+                    .into_iter()
+                    .map(|id| Expression::Array(id.into_iter().map(Expression::from).collect()))
+                    .collect(),
+            )
+        }
+        Builtin::Paths | Builtin::LeafPaths => {
+            let mut paths = Vec::new();
+            body_paths(body, &Path::default(), builtin, &mut paths);
+            Expression::Array(paths)
         }
-        if field.labels.is_empty() {
-            matches.push(QueryResult::Block(block.clone()));
+        Builtin::Base64 | Builtin::Base64Decode | Builtin::Add | Builtin::Flatten(_) => {
+            return None
         }
-        for filter_label in &field.labels {
-            for block_label in block.labels() {
-                if block_label.as_str() == filter_label {
-                    matches.push(QueryResult::Block(block.clone()));
+        // these pass their input through, and are handled before getting here
+        Builtin::First | Builtin::Last => return None,
+        // this has many outputs, and is handled before getting here
+        Builtin::Values => return None,
+    };
+    Some(output)
+}
+
+/// returns `None` if `builtin` can't be applied to `expr`, and an error if it
+/// can be but fails (e.g. decoding a string that isn't base64)
+fn expr_builtin(
+    builtin: Builtin,
+    expr: &Expression,
+) -> Result<Option<Expression>, Box<QueryError>> {
+    let output = match (builtin, expr) {
+        (Builtin::Keys, Expression::Object(object)) => {
+            let mut keys: Vec<_> = object
+                .keys()
+                .map(|key| object_key_name(key).map_or_else(|| key.to_string(), str::to_string))
+                .collect();
+            keys.sort();
+            Some(Expression::Array(
+                keys.into_iter().map(Expression::String).collect(),
+            ))
+        }
+        (Builtin::Keys, Expression::Array(array)) => Some(Expression::Array(
+            (0..array.len() as u64).map(Expression::from).collect(),
+        )),
+        (Builtin::Keys, _) => None,
+        (Builtin::Values, _) => None,
+        (Builtin::Length, Expression::Null) => Some(Expression::from(0u64)),
+        (Builtin::Length, Expression::Array(array)) => Some(Expression::from(array.len() as u64)),
+        (Builtin::Length, Expression::Object(object)) => {
+            Some(Expression::from(object.len() as u64))
+        }
+        (Builtin::Length, Expression::String(_) | Expression::TemplateExpr(_)) => {
+            string_value(expr).map(|s| Expression::from(s.chars().count() as u64))
+        }
+        (Builtin::Length, _) => None,
+        (Builtin::Type, _) => Some(Expression::from(type_name(expr))),
+        (Builtin::Duplicates, _) => None,
+        (Builtin::First | Builtin::Last, _) => None,
+        (Builtin::Paths | Builtin::LeafPaths, _) => {
+            let mut paths = Vec::new();
+            expr_paths(expr, &Path::default(), builtin, &mut paths);
+            Some(Expression::Array(paths))
+        }
+        (Builtin::Add, Expression::Array(array)) => Some(add(array)?),
+        (Builtin::Add, _) => None,
+        (Builtin::Flatten(depth), Expression::Array(array)) => {
+            let mut flattened = Vec::new();
+            flatten(array, depth, &mut flattened);
+            Some(Expression::Array(flattened))
+        }
+        (Builtin::Flatten(_), _) => None,
+        (Builtin::Base64, _) => string_value(expr).map(|s| Expression::String(BASE64.encode(s))),
+        (Builtin::Base64Decode, _) => match string_value(expr) {
+            Some(s) => {
+                let s: String = s.split_ascii_whitespace().collect();
+                let decoded = BASE64.decode(s).map_err(|e| {
+                    err(&format!(
+                        "'@base64d' cannot decode the input as base64: {e}"
+                    ))
+                })?;
+                let decoded = String::from_utf8(decoded).map_err(|_| {
+                    err("'@base64d' decoded the input, but the result is not valid UTF-8")
+                })?;
+                Some(Expression::String(decoded))
+            }
+            None => None,
+        },
+    };
+    Ok(output)
+}
+
+/// the elements of `array` added together, for `add`
+///
+/// all of them (except nulls, which are skipped) have to be numbers, strings,
+/// or lists
+fn add(array: &[Expression]) -> Result<Expression, Box<QueryError>> {
+    let mut sum: Option<Expression> = None;
+    for expr in array.iter().filter(|expr| **expr != Expression::Null) {
+        let added = match (sum, expr) {
+            (None, Expression::Number(_) | Expression::Array(_)) => expr.clone(),
+            (None, Expression::String(_) | Expression::TemplateExpr(_)) => match string_value(expr)
+            {
+                Some(s) => Expression::String(s),
+                None => return Err(cannot_add(expr, None)),
+            },
+            (Some(Expression::Number(a)), Expression::Number(b)) => Expression::Number(a + *b),
+            (Some(Expression::String(a)), Expression::String(_) | Expression::TemplateExpr(_)) => {
+                match string_value(expr) {
+                    Some(b) => Expression::String(a + &b),
+                    None => return Err(cannot_add(expr, None)),
                 }
             }
+            (Some(Expression::Array(mut a)), Expression::Array(b)) => {
+                a.extend(b.iter().cloned());
+                Expression::Array(a)
+            }
+            (sum, expr) => return Err(cannot_add(expr, sum.as_ref())),
+        };
+        sum = Some(added);
+    }
+    Ok(sum.unwrap_or(Expression::Null))
+}
+
+/// add the elements of `array` to `flattened`, with the elements of the lists
+/// in it (`depth` levels deep) in their place, for `flatten`
+fn flatten(array: &[Expression], depth: usize, flattened: &mut Vec<Expression>) {
+    for expr in array {
+        match expr {
+            Expression::Array(inner) if depth > 0 => flatten(inner, depth - 1, flattened),
+            _ => flattened.push(expr.clone()),
+        }
+    }
+}
+
+fn cannot_add(expr: &Expression, sum: Option<&Expression>) -> Box<QueryError> {
+    match sum {
+        Some(sum) => err(&format!(
+            "'add' cannot add {} to {} (the elements have to all be numbers, strings, or lists)",
+            describe(expr),
+            describe(sum),
+        )),
+        None => err(&format!(
+            "'add' cannot add {} (it adds numbers, strings, and lists)",
+            describe(expr),
+        )),
+    }
+}
+
+/// add the paths inside of `body`, which is at `path`, to `paths` for the
+/// builtin `paths` (or `leaf_paths`, which leaves out blocks)
+fn body_paths(body: &Body, path: &Path, builtin: Builtin, paths: &mut Vec<Expression>) {
+    let positions = block_positions(body);
+    for (index, structure) in body.iter().enumerate() {
+        match structure {
+            Structure::Attribute(attr) => {
+                let path = path.join(Segment::Attribute {
+                    index,
+                    key: attr.key().to_string(),
+                });
+                expr_paths(attr.expr(), &path, builtin, paths);
+            }
+            Structure::Block(block) => {
+                let path = path.join(block_segment(index, block, positions[index]));
+                if builtin == Builtin::Paths {
+                    paths.push(Expression::String(path.to_string()));
+                }
+                body_paths(block.body(), &path, builtin, paths);
+            }
+        }
+    }
+}
+
+/// add `path`, where `expr` is, and the paths inside of `expr` to `paths`
+/// (see [`body_paths`]), except for the input itself
+fn expr_paths(expr: &Expression, path: &Path, builtin: Builtin, paths: &mut Vec<Expression>) {
+    let leaf = !matches!(expr, Expression::Array(_) | Expression::Object(_));
+    if !path.segments.is_empty() && (leaf || builtin == Builtin::Paths) {
+        paths.push(Expression::String(path.to_string()));
+    }
+    match expr {
+        Expression::Array(array) => {
+            for (i, element) in array.iter().enumerate() {
+                expr_paths(element, &path.join(Segment::Element(i)), builtin, paths);
+            }
+        }
+        Expression::Object(object) => {
+            for (key, value) in object {
+                let key = object_key_name(key).map_or_else(|| key.to_string(), str::to_string);
+                expr_paths(value, &path.join(Segment::Entry(key)), builtin, paths);
+            }
         }
+        _ => {}
+    }
+}
+
+/// the canonical name of the kind of value `expr` is, for `type`
+fn type_name(expr: &Expression) -> &'static str {
+    match expr {
+        Expression::Null => "null",
+        Expression::Bool(_) => "bool",
+        Expression::Number(_) => "number",
+        Expression::String(_) | Expression::TemplateExpr(_) => "string",
+        Expression::Array(_) => "list",
+        Expression::Object(_) => "object",
+        _ => "expression",
+    }
+}
+
+/// keep only the matches of the kind of value `field` is for (`.*:string`)
+fn type_query(field: &Field, mut matches: Vec<Match>) -> Vec<Match> {
+    if let Some(value_type) = field.value_type {
+        matches.retain(|(_, query_result)| {
+            let name = match query_result {
+                QueryResult::Expr(expr) => type_name(expr),
+                QueryResult::Block(_) | QueryResult::Body(_) => "object",
+            };
+            name == value_type.name()
+        });
     }
     matches
 }
 
-fn result_query(field: &Field, query_results: Vec<QueryResult>) -> Vec<QueryResult> {
-    let mut matches = Vec::new();
-    for query_result in query_results {
+/// the string that `expr` is, if it is one
+fn string_value(expr: &Expression) -> Option<String> {
+    match expr {
+        Expression::String(_) | Expression::TemplateExpr(_) => match Value::from(expr.clone()) {
+            Value::String(s) => Some(s),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn key_query(
+    field: &Field,
+    matches: Vec<Match>,
+    options: &QueryOptions,
+) -> Result<Vec<Match>, Box<QueryError>> {
+    let Some(ref key) = field.key else {
+        return Ok(matches);
+    };
+    let mut key_matches = Vec::new();
+    for (path, query_result) in matches {
         match query_result {
-            QueryResult::Expr(expr) => {
-                if let Expression::Object(object) = expr {
-                    // some objects are keyed with an Identifier
-                    if let Ok(id) = Identifier::new(&field.name) {
-                        let key = ObjectKey::Identifier(id);
-                        if let Some(expr) = object.get(&key) {
-                            matches.push(QueryResult::Expr(expr.clone()));
+            QueryResult::Expr(Expression::Object(object)) => {
+                for (object_key, expr) in object {
+                    if object_key_matches(&object_key, key, options) {
+                        let path = path.join(entry_segment(&object_key));
+                        key_matches.push((path, QueryResult::Expr(expr)));
+                    }
+                }
+            }
+            QueryResult::Block(_) => {
+                return Err(err(&format!(
+                    "cannot look up key \"{key}\" in block '{}', use {{\"{key}\"}} to match its labels",
+                    field.name
+                )));
+            }
+            // an optional field (`.foo["key"]?`) ignores something that is not an object
+            QueryResult::Expr(_) | QueryResult::Body(_) if field.optional => {}
+            query_result @ (QueryResult::Expr(_) | QueryResult::Body(_)) => {
+                return Err(type_mismatch(
+                    &path,
+                    format!("[{}]", quote(key)),
+                    "an object",
+                    describe_result(&query_result),
+                ));
+            }
+        }
+    }
+    Ok(key_matches)
+}
+
+fn selector_query(field: &Field, matches: Vec<Match>) -> Result<Vec<Match>, Box<QueryError>> {
+    let Some(ref selector) = field.selector else {
+        return Ok(matches);
+    };
+    // a name that only matched blocks (e.g. the `ingress` blocks of a security
+    // group) is indexed into as if they were a list, so `.ingress[2]` is the
+    // third of them
+    if !matches.is_empty()
+        && matches
+            .iter()
+            .all(|(_, query_result)| matches!(query_result, QueryResult::Block(_)))
+    {
+        return select_blocks(field, selector, matches);
+    }
+    let mut selected = Vec::new();
+    for (path, query_result) in matches {
+        match query_result {
+            QueryResult::Expr(Expression::Array(mut array)) => match *selector {
+                Selector::Index(index) => {
+                    match index.resolve(array.len()) {
+                        Some(i) => selected.push((
+                            path.join(Segment::Element(i)),
+                            QueryResult::Expr(array.swap_remove(i)),
+                        )),
+                        // an optional field (`.foo[5]?`) ignores a missing element
+                        None if field.optional => {}
+                        None => return Err(out_of_bounds(index, &field.name, array.len())),
+                    }
+                }
+                Selector::Slice { start, end } => {
+                    let len = array.len();
+                    let start = start.map_or(0, |start| start.clamp(len));
+                    let end = end.map_or(len, |end| end.clamp(len)).max(start);
+                    let slice = array.drain(start..end).collect();
+                    selected.push((
+                        path.join(Segment::Slice { start, end }),
+                        QueryResult::Expr(Expression::Array(slice)),
+                    ));
+                }
+                Selector::Indices(ref indices) => {
+                    for index in indices {
+                        match index.resolve(array.len()) {
+                            Some(i) => selected.push((
+                                path.join(Segment::Element(i)),
+                                QueryResult::Expr(array[i].clone()),
+                            )),
+                            None if field.optional => {}
+                            None => return Err(out_of_bounds(*index, &field.name, array.len())),
                         }
                     }
-                    // some objects are keyed with a String Expression
-                    let key = ObjectKey::Expression(Expression::String(field.name.clone()));
-                    if let Some(expr) = object.get(&key) {
-                        matches.push(QueryResult::Expr(expr.clone()));
+                }
+                Selector::Iterate => {
+                    selected.extend(array.into_iter().enumerate().map(|(i, expr)| {
+                        (path.join(Segment::Element(i)), QueryResult::Expr(expr))
+                    }));
+                }
+            },
+            QueryResult::Expr(Expression::Object(object)) if *selector == Selector::Iterate => {
+                selected.extend(
+                    object.into_iter().map(|(key, expr)| {
+                        (path.join(entry_segment(&key)), QueryResult::Expr(expr))
+                    }),
+                );
+            }
+            // blocks are already separate results, so iterating over a set of
+            // matching blocks yields each of them unchanged
+            query_result @ QueryResult::Block(_) if *selector == Selector::Iterate => {
+                selected.push((path, query_result));
+            }
+            query_result => {
+                let expected = match selector {
+                    Selector::Iterate => "a list or an object",
+                    _ => "a list",
+                };
+                return Err(type_mismatch(
+                    &path,
+                    selector.to_string(),
+                    expected,
+                    describe_result(&query_result),
+                ));
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// apply `selector` to the blocks that `field` matched, each of which stays a
+/// separate result
+fn select_blocks(
+    field: &Field,
+    selector: &Selector,
+    mut blocks: Vec<Match>,
+) -> Result<Vec<Match>, Box<QueryError>> {
+    let len = blocks.len();
+    let selected = match *selector {
+        Selector::Index(index) => match index.resolve(len) {
+            Some(i) => vec![blocks.swap_remove(i)],
+            None if field.optional => Vec::new(),
+            None => return Err(blocks_out_of_bounds(index, &field.name, len)),
+        },
+        Selector::Slice { start, end } => {
+            let start = start.map_or(0, |start| start.clamp(len));
+            let end = end.map_or(len, |end| end.clamp(len)).max(start);
+            blocks.drain(start..end).collect()
+        }
+        Selector::Indices(ref indices) => {
+            let mut selected = Vec::new();
+            for index in indices {
+                match index.resolve(len) {
+                    Some(i) => selected.push(blocks[i].clone()),
+                    None if field.optional => {}
+                    None => return Err(blocks_out_of_bounds(*index, &field.name, len)),
+                }
+            }
+            selected
+        }
+        Selector::Iterate => blocks,
+    };
+    Ok(selected)
+}
+
+fn predicate_query(
+    field: &Field,
+    matches: Vec<Match>,
+    options: &QueryOptions,
+) -> Result<Vec<Match>, Box<QueryError>> {
+    let Some(ref predicate) = field.predicate else {
+        return Ok(matches);
+    };
+    let mut kept = Vec::new();
+    for m in matches {
+        if predicate_holds(predicate, &m, options)? {
+            kept.push(m);
+        }
+    }
+    Ok(kept)
+}
+
+fn predicate_holds(
+    predicate: &Predicate,
+    m: &Match,
+    options: &QueryOptions,
+) -> Result<bool, Box<QueryError>> {
+    match predicate {
+        Predicate::Compare {
+            path,
+            comparison,
+            value,
+        } => {
+            let value = match value {
+                Literal::Variable(name) => options
+                    .variables
+                    .get(name)
+                    .ok_or_else(|| err(&format!("variable '${name}' is not defined")))?,
+                value => value,
+            };
+            let found = path_collect(path, m.clone(), options)?;
+            Ok(found
+                .iter()
+                .any(|(_, query_result)| compare(&query_result.to_value(), *comparison, value)))
+        }
+        Predicate::Has(name) => Ok(has_member(&m.1, name, options)),
+        Predicate::Not(predicate) => Ok(!predicate_holds(predicate, m, options)?),
+    }
+}
+
+/// whether `query_result` has an attribute, block, or object key called `name`
+fn has_member(query_result: &QueryResult, name: &str, options: &QueryOptions) -> bool {
+    let body_has = |body: &Body| {
+        body.iter().any(|structure| match structure {
+            Structure::Attribute(attr) => options.matches(name, attr.key()),
+            Structure::Block(block) => options.matches(name, block.identifier()),
+        })
+    };
+    match query_result {
+        QueryResult::Block(block) => body_has(block.body()),
+        QueryResult::Body(body) => body_has(body),
+        QueryResult::Expr(Expression::Object(object)) => object
+            .keys()
+            .any(|key| object_key_matches(key, name, options)),
+        QueryResult::Expr(_) => false,
+    }
+}
+
+/// values of different types never compare equal (e.g. `"3"` is not `3`, and
+/// `true` is not `1`), and numbers compare by value (so `3` equals `3.0`)
+///
+/// values of different types aren't unequal or ordered either, so `!=`, `<`,
+/// `<=`, `>`, and `>=` only hold for a value of the same type as the literal
+/// (and only numbers and strings are ordered, strings lexically)
+///
+/// a list contains a literal if one of its elements equals it, and a string
+/// contains a string literal that is a substring of it, anything else
+/// contains nothing
+fn compare(value: &Value, comparison: Comparison, literal: &Literal) -> bool {
+    match (comparison, value, literal) {
+        (Comparison::Equal, Value::String(value), Literal::String(literal)) => value == literal,
+        (Comparison::Equal, Value::Number(value), Literal::Number(literal)) => {
+            value.as_f64() == Some(*literal)
+        }
+        (Comparison::Equal, Value::Bool(value), Literal::Bool(literal)) => value == literal,
+        (Comparison::Contains, Value::Array(values), _) => values
+            .iter()
+            .any(|value| compare(value, Comparison::Equal, literal)),
+        (Comparison::Contains, Value::String(value), Literal::String(literal)) => {
+            value.contains(literal.as_str())
+        }
+        (Comparison::NotEqual, Value::Bool(value), Literal::Bool(literal)) => value != literal,
+        (comparison, value, literal) => match order(value, literal) {
+            Some(ordering) => match comparison {
+                Comparison::NotEqual => ordering.is_ne(),
+                Comparison::Less => ordering.is_lt(),
+                Comparison::LessOrEqual => ordering.is_le(),
+                Comparison::Greater => ordering.is_gt(),
+                Comparison::GreaterOrEqual => ordering.is_ge(),
+                Comparison::Equal | Comparison::Contains => false,
+            },
+            None => false,
+        },
+    }
+}
+
+/// how `value` is ordered relative to `literal`, if they're both numbers or
+/// both strings
+fn order(value: &Value, literal: &Literal) -> Option<Ordering> {
+    match (value, literal) {
+        (Value::String(value), Literal::String(literal)) => Some(value.as_str().cmp(literal)),
+        (Value::Number(value), Literal::Number(literal)) => value.as_f64()?.partial_cmp(literal),
+        _ => None,
+    }
+}
+
+fn out_of_bounds(index: Index, name: &str, len: usize) -> Box<QueryError> {
+    err(&format!(
+        "index {index} is out of bounds for '{name}' (list has {len} element(s))"
+    ))
+}
+
+fn blocks_out_of_bounds(index: Index, name: &str, len: usize) -> Box<QueryError> {
+    err(&format!(
+        "index {index} is out of bounds for '{name}' (it matched {len} block(s))"
+    ))
+}
+
+/// the positions of the attributes and blocks of a body by name, for a body
+/// that several paths look names up in, so that it's only scanned once
+struct BodyIndex<'a> {
+    positions: HashMap<&'a str, Vec<usize>>,
+    /// see [`block_positions`]
+    block_positions: Vec<Option<usize>>,
+}
+
+impl<'a> BodyIndex<'a> {
+    fn new(body: &'a Body) -> Self {
+        let mut positions: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (index, structure) in body.iter().enumerate() {
+            let name = match structure {
+                Structure::Attribute(attr) => attr.key(),
+                Structure::Block(block) => block.identifier(),
+            };
+            positions.entry(name).or_default().push(index);
+        }
+        BodyIndex {
+            positions,
+            block_positions: block_positions(body),
+        }
+    }
+
+    /// the index of the body of a block or the document, which are the
+    /// results that have names in them to look up
+    fn of(query_result: &'a QueryResult) -> Option<Self> {
+        match query_result {
+            QueryResult::Block(block) => Some(BodyIndex::new(block.body())),
+            QueryResult::Body(body) => Some(BodyIndex::new(body)),
+            QueryResult::Expr(_) => None,
+        }
+    }
+
+    /// the positions, in source order, of the structures that `field` can
+    /// match, or `None` if it doesn't match names exactly (e.g. `.*`) so every
+    /// structure has to be checked
+    fn positions(&self, field: &Field, options: &QueryOptions) -> Option<&[usize]> {
+        if field.wildcard || field.pattern.is_some() || options.ignore_case {
+            return None;
+        }
+        Some(
+            self.positions
+                .get(field.name.as_str())
+                .map_or(&[], Vec::as_slice),
+        )
+    }
+}
+
+/// `index`, if there is one, is of `body`, and is used to only visit the
+/// structures with the field's name
+fn body_query(
+    field: &Field,
+    path: &Path,
+    body: &Body,
+    index: Option<&BodyIndex>,
+    options: &QueryOptions,
+) -> Result<Vec<Match>, Box<QueryError>> {
+    let mut matches = Vec::new();
+    if field.recursive {
+        recursive_body_query(
+            field,
+            path,
+            path.segments.len(),
+            body,
+            options,
+            &mut matches,
+        )?;
+        return Ok(matches);
+    }
+    let positions = index.and_then(|index| index.positions(field, options));
+    let structures = || -> Box<dyn Iterator<Item = (usize, &Structure)>> {
+        match positions {
+            Some(positions) => Box::new(positions.iter().map(|&i| (i, &body.0[i]))),
+            None => Box::new(body.iter().enumerate()),
+        }
+    };
+    // the positions of the blocks, found once a block matches
+    let indexed = index.map(|index| &index.block_positions);
+    let mut computed = None;
+    // matching attributes and blocks are in source order, even when they are
+    // interleaved
+    for (index, structure) in structures() {
+        match structure {
+            Structure::Attribute(attr) => {
+                if unlabeled_matches(field, attr.key(), options) {
+                    let path = path.join(attr_segment(index, attr));
+                    matches.push((path, QueryResult::Expr(attr.expr().clone())));
+                }
+            }
+            Structure::Block(block) => {
+                if block_matches(field, block, options)? {
+                    let block_positions = match indexed {
+                        Some(block_positions) => block_positions,
+                        None => computed.get_or_insert_with(|| block_positions(body)),
+                    };
+                    let path = path.join(block_segment(index, block, block_positions[index]));
+                    check_label_count(field, block, &path, options)?;
+                    matches.push((path, QueryResult::Block(block.clone())));
+                }
+            }
+        }
+    }
+    // labels on a name that's only an attribute are a mistake for a key
+    // (`.tags{"Env"}` for `.tags["Env"]`), rather than a search that found
+    // nothing
+    if matches.is_empty() && has_labels(field) {
+        let attr = structures().find_map(|(index, structure)| {
+            structure
+                .as_attribute()
+                .filter(|attr| options.field_matches(field, attr.key()))
+                .map(|attr| attr_segment(index, attr))
+        });
+        if let Some(segment) = attr {
+            return Err(label_on_attribute(field, &path.join(segment)));
+        }
+    }
+    Ok(matches)
+}
+
+/// whether `field` matches block labels, which attributes and object keys
+/// don't have (`.*{"label"}` looks for blocks of any type, not for a name)
+fn has_labels(field: &Field) -> bool {
+    !field.labels.is_empty() && !matches_many_names(field)
+}
+
+/// whether `field` matches names it doesn't spell out, with a wildcard (`.*`)
+/// or a regex (`.~"^aws_"`), so a match it can't be is skipped rather than an
+/// error
+fn matches_many_names(field: &Field) -> bool {
+    field.wildcard || field.pattern.is_some()
+}
+
+/// a block matches if its identifier is the field's name and its labels start
+/// with the field's labels
+///
+/// e.g. `.resource{"aws_instance"}` matches every `resource "aws_instance" ...`
+/// block, and `.resource{"aws_instance","web"}` only matches
+/// `resource "aws_instance" "web"`, while `.resource{*,"web"}` matches a block
+/// of any type labeled `"web"`
+fn block_matches(
+    field: &Field,
+    block: &Block,
+    options: &QueryOptions,
+) -> Result<bool, Box<QueryError>> {
+    if !options.field_matches(field, block.identifier()) {
+        return Ok(false);
+    }
+    let labels = block.labels();
+    if field.labels.len() > labels.len() {
+        // `.*{"label"}` is looking for any block with that label, not this one
+        if matches_many_names(field) {
+            return Ok(false);
+        }
+        return Err(too_many_labels(field, labels.len()));
+    }
+    Ok(field
+        .labels
+        .iter()
+        .zip(labels)
+        .all(|(filter_label, label)| match filter_label {
+            Label::Any => true,
+            Label::Exact(filter_label) => options.matches(filter_label, label.as_str()),
+        }))
+}
+
+fn too_many_labels(field: &Field, len: usize) -> Box<QueryError> {
+    err(&format!(
+        "filter has {} label(s) for '{}' but the block only has {len}",
+        field.labels.len(),
+        field.name,
+    ))
+}
+
+/// with [`QueryOptions::strict_labels`], a block at `path` that `field`
+/// matched must have exactly as many labels as `field` has
+fn check_label_count(
+    field: &Field,
+    block: &Block,
+    path: &Path,
+    options: &QueryOptions,
+) -> Result<(), Box<QueryError>> {
+    let len = block.labels().len();
+    if !options.strict_labels || field.labels.is_empty() || field.labels.len() == len {
+        return Ok(());
+    }
+    Err(err(&format!(
+        "filter has {} label(s) for '{}' but the block at '{path}' has {len}, \
+         and --strict-labels needs all of them",
+        field.labels.len(),
+        field.name,
+    )))
+}
+
+/// `start` is the length of the path that `..` started from
+fn recursive_body_query(
+    field: &Field,
+    path: &Path,
+    start: usize,
+    body: &Body,
+    options: &QueryOptions,
+    matches: &mut Vec<Match>,
+) -> Result<(), Box<QueryError>> {
+    check_depth(path, start, options)?;
+    let positions = block_positions(body);
+    for (index, structure) in body.iter().enumerate() {
+        match structure {
+            Structure::Attribute(attr) => {
+                let path = path.join(attr_segment(index, attr));
+                if unlabeled_matches(field, attr.key(), options) {
+                    matches.push((path.clone(), QueryResult::Expr(attr.expr().clone())));
+                }
+                recursive_expr_query(field, &path, start, attr.expr(), options, matches)?;
+            }
+            Structure::Block(block) => {
+                let path = path.join(block_segment(index, block, positions[index]));
+                if block_matches(field, block, options)? {
+                    check_label_count(field, block, &path, options)?;
+                    matches.push((path.clone(), QueryResult::Block(block.clone())));
+                }
+                recursive_body_query(field, &path, start, block.body(), options, matches)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `start` is the length of the path that `..` started from
+fn recursive_expr_query(
+    field: &Field,
+    path: &Path,
+    start: usize,
+    expr: &Expression,
+    options: &QueryOptions,
+    matches: &mut Vec<Match>,
+) -> Result<(), Box<QueryError>> {
+    check_depth(path, start, options)?;
+    match expr {
+        Expression::Object(object) => {
+            for (key, value) in object {
+                let path = path.join(entry_segment(key));
+                if object_key_name(key).is_some_and(|key| unlabeled_matches(field, key, options)) {
+                    matches.push((path.clone(), QueryResult::Expr(value.clone())));
+                }
+                recursive_expr_query(field, &path, start, value, options, matches)?;
+            }
+        }
+        Expression::Array(array) => {
+            for (i, value) in array.iter().enumerate() {
+                let path = path.join(Segment::Element(i));
+                recursive_expr_query(field, &path, start, value, options, matches)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// an error if `..` has gone deeper than [`QueryOptions::max_depth`] to get
+/// to `path`
+///
+/// the depth is the number of segments that `path` has past the first
+/// `start`, which lead to where `..` started, so it counts only the blocks,
+/// attributes, object entries, and list elements that `..` itself went into
+fn check_depth(path: &Path, start: usize, options: &QueryOptions) -> Result<(), Box<QueryError>> {
+    if path.segments.len() - start <= options.max_depth {
+        return Ok(());
+    }
+    Err(err(&format!(
+        "'..' went deeper than the maximum depth of {} at '{path}'",
+        options.max_depth
+    )))
+}
+
+fn attr_segment(index: usize, attr: &Attribute) -> Segment {
+    Segment::Attribute {
+        index,
+        key: attr.key().to_string(),
+    }
+}
+
+/// the segment for `block`, at `index` in its body, where `position` is from
+/// [`block_positions`]
+fn block_segment(index: usize, block: &Block, position: Option<usize>) -> Segment {
+    Segment::Block {
+        index,
+        identifier: block.identifier().to_string(),
+        labels: block
+            .labels()
+            .iter()
+            .map(|label| label.as_str().to_string())
+            .collect(),
+        position,
+    }
+}
+
+/// the position of each block in `body` among the blocks that its identifier
+/// and labels match (see [`Segment::Block`]), by where it is in `body`
+///
+/// attributes, and blocks that are the only ones they match, have no position
+fn block_positions(body: &Body) -> Vec<Option<usize>> {
+    // how many blocks each `.identifier{labels}` has matched so far, which
+    // includes the blocks with more labels after these
+    let mut counts: HashMap<(&str, Vec<&str>), usize> = HashMap::new();
+    let mut positions = Vec::new();
+    for structure in body.iter() {
+        let Structure::Block(block) = structure else {
+            positions.push(None);
+            continue;
+        };
+        let labels: Vec<&str> = block.labels().iter().map(|label| label.as_str()).collect();
+        for len in 0..=labels.len() {
+            let count = counts
+                .entry((block.identifier(), labels[..len].to_vec()))
+                .or_default();
+            if len == labels.len() {
+                positions.push(Some(*count));
+            }
+            *count += 1;
+        }
+    }
+    for (structure, position) in body.iter().zip(&mut positions) {
+        if let Structure::Block(block) = structure {
+            let labels = block.labels().iter().map(|label| label.as_str()).collect();
+            if counts[&(block.identifier(), labels)] == 1 {
+                *position = None;
+            }
+        }
+    }
+    positions
+}
+
+fn entry_segment(key: &ObjectKey) -> Segment {
+    // keys that aren't names (e.g. `(var.foo) = 1`) use their source instead
+    let key = object_key_name(key).map_or_else(|| key.to_string(), str::to_string);
+    Segment::Entry(key)
+}
+
+fn object_key_name(key: &ObjectKey) -> Option<&str> {
+    match key {
+        // some objects are keyed with an Identifier
+        ObjectKey::Identifier(id) => Some(id.as_str()),
+        // some objects are keyed with a String Expression
+        ObjectKey::Expression(Expression::String(s)) => Some(s),
+        _ => None,
+    }
+}
+
+fn object_key_matches(key: &ObjectKey, name: &str, options: &QueryOptions) -> bool {
+    object_key_name(key).is_some_and(|key| options.matches(name, key))
+}
+
+/// whether the name of an attribute or object key (which have no labels)
+/// matches `field`, which it never does if the field has labels
+fn unlabeled_matches(field: &Field, name: &str, options: &QueryOptions) -> bool {
+    field.labels.is_empty() && options.field_matches(field, name)
+}
+
+/// the `name` (a string) and `args` (a list) of a function call at `path`,
+/// which a name after it looks up as if they were the keys of an object, so
+/// `.subnet.args[0]` is the first argument of `subnet = cidrsubnet(...)`
+fn func_call_members(
+    field: &Field,
+    path: &Path,
+    call: FuncCall,
+    options: &QueryOptions,
+) -> Vec<Match> {
+    [
+        ("name", Expression::String(call.name.to_string())),
+        ("args", Expression::Array(call.args)),
+    ]
+    .into_iter()
+    .filter(|(name, _)| unlabeled_matches(field, name, options))
+    .map(|(name, expr)| {
+        (
+            path.join(Segment::Entry(name.to_string())),
+            QueryResult::Expr(expr),
+        )
+    })
+    .collect()
+}
+
+fn result_query(
+    field: &Field,
+    matches: Vec<Match>,
+    options: &QueryOptions,
+) -> Result<Vec<Match>, Box<QueryError>> {
+    let mut field_matches = Vec::new();
+    for (path, query_result) in matches {
+        match query_result {
+            QueryResult::Expr(expr) if field.recursive => {
+                recursive_expr_query(
+                    field,
+                    &path,
+                    path.segments.len(),
+                    &expr,
+                    options,
+                    &mut field_matches,
+                )?;
+            }
+            QueryResult::Expr(Expression::Object(object)) => {
+                if has_labels(field) {
+                    if let Some(key) = object.keys().find(|key| {
+                        object_key_name(key).is_some_and(|key| options.field_matches(field, key))
+                    }) {
+                        return Err(label_on_attribute(field, &path.join(entry_segment(key))));
+                    }
+                }
+                for (key, expr) in object {
+                    if object_key_name(&key)
+                        .is_some_and(|key| unlabeled_matches(field, key, options))
+                    {
+                        let path = path.join(entry_segment(&key));
+                        field_matches.push((path, QueryResult::Expr(expr)));
                     }
                 }
             }
+            QueryResult::Expr(Expression::FuncCall(call)) => {
+                field_matches.extend(func_call_members(field, &path, *call, options));
+            }
+            QueryResult::Expr(_) => {}
             QueryResult::Block(block) => {
-                let mut body_matches = body_query(field, block.body());
-                matches.append(&mut body_matches);
+                let mut body_matches = body_query(field, &path, block.body(), None, options)?;
+                field_matches.append(&mut body_matches);
+            }
+            QueryResult::Body(body) => {
+                let mut body_matches = body_query(field, &path, &body, None, options)?;
+                field_matches.append(&mut body_matches);
             }
         }
     }
-    matches
+    Ok(field_matches)
 }
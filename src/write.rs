@@ -4,7 +4,7 @@ use std::{error::Error, fmt};
 
 use hcl_edit::{expr::Expression, structure::Body, visit_mut::VisitMut};
 
-use crate::parser::Field;
+use crate::parser::{split_keys, unsupported, Field};
 
 #[derive(Debug)]
 pub struct WriteError {
@@ -73,32 +73,22 @@ impl VisitMut for HclEditor<'_> {
         let next = self.next.clone();
         if let Some(next) = next {
             if node.ident.as_str() == next.name {
-                if next.labels.is_empty() {
+                let labels: Vec<_> = node.labels.iter().map(|label| label.as_str()).collect();
+                if next.labels.len() > labels.len() {
+                    self.error = Some(err("filter has more labels than the block"));
+                    return;
+                }
+                // the block is a match if its name matches and its labels start
+                // with the filter labels
+                if next.matches_labels(&labels) {
                     if self.fields.is_empty() {
                         self.error = Some(err("unable to write expr as block body"));
                         return;
                     }
-                    // the block is a match if its name matches and there are no labels
                     // traverse to the next field
                     self.next = Some(self.fields.remove(0));
                     // then visit the body
                     self.visit_body_mut(&mut node.body);
-                } else {
-                    for filter_label in &next.labels {
-                        for block_label in &node.labels {
-                            if block_label.as_str() == filter_label {
-                                if self.fields.is_empty() {
-                                    self.error = Some(err("unable to write expr as block body"));
-                                    return;
-                                }
-                                // the block name and this label match the filters
-                                // traverse to the next field
-                                self.next = Some(self.fields.remove(0));
-                                // then visit the body
-                                self.visit_body_mut(&mut node.body);
-                            }
-                        }
-                    }
                 }
             }
         }
@@ -112,6 +102,10 @@ pub fn write(
     body: &mut Body,
     value: &Expression,
 ) -> Result<(), Box<WriteError>> {
+    if let Some(what) = unsupported(&fields) {
+        return Err(err(&format!("writing {what} is not supported")));
+    }
+    let fields = split_keys(fields);
     let mut visitor = HclEditor::new(fields, value);
     visitor.visit_body_mut(body);
     if let Some(err) = visitor.error {
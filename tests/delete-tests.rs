@@ -1,4 +1,7 @@
-use hq_rs::{delete, parser::Field};
+use hq_rs::{
+    delete,
+    parser::{Field, ValueType},
+};
 
 #[test]
 fn delete_attr() {
@@ -77,6 +80,18 @@ fn delete_from_object() {
     assert_eq!("local { obj = {} }", body.to_string());
 }
 
+#[test]
+fn delete_object_key() {
+    // filter '.local.obj["val"]'
+    let fields = vec![Field::new("local"), Field::keyed("obj", "val")];
+
+    let mut body = utilities::edit_hcl("local { obj = { \"val\" = 5 } }").expect("hcl error");
+
+    delete(fields, &mut body).expect("delete error");
+
+    assert_eq!("local { obj = {} }", body.to_string());
+}
+
 #[test]
 fn delete_from_nested_object() {
     // filter '.local.obj.obj2.val'
@@ -94,3 +109,64 @@ fn delete_from_nested_object() {
 
     assert_eq!("local { obj = { obj2 = {} } }", body.to_string());
 }
+
+#[test]
+fn delete_too_many_labels() {
+    // filter '.module{"cool-module","extra"}'
+    let fields = vec![Field::labeled("module", &["cool-module", "extra"])];
+
+    let mut body =
+        utilities::edit_hcl("module \"cool-module\" { version = \"1.0\" }").expect("hcl error");
+
+    assert!(delete(fields, &mut body).is_err());
+}
+
+#[test]
+fn delete_wildcard_name() {
+    // filter '.options.*'
+    let fields = vec![Field::new("options"), Field::wildcard()];
+
+    let mut body = utilities::edit_hcl("options { enabled = false }").expect("hcl error");
+
+    let Err(e) = delete(fields, &mut body) else {
+        panic!("deleting with a wildcard name should fail");
+    };
+    assert_eq!(
+        "deleting with wildcard names is not supported",
+        e.to_string()
+    );
+    assert_eq!("options { enabled = false }", body.to_string());
+}
+
+#[test]
+fn delete_regex_name() {
+    // filter '.options.~"^en"'
+    let pattern = regex::Regex::new("^en").expect("regex error");
+    let fields = vec![Field::new("options"), Field::matching(pattern)];
+
+    let mut body = utilities::edit_hcl("options { enabled = false }").expect("hcl error");
+
+    let Err(e) = delete(fields, &mut body) else {
+        panic!("deleting with a regex name should fail");
+    };
+    assert_eq!("deleting with regex names is not supported", e.to_string());
+}
+
+#[test]
+fn delete_value_type() {
+    // a field with a value type, like the wildcard in '.options.*:bool'
+    let fields = vec![
+        Field::new("options"),
+        Field {
+            value_type: Some(ValueType::Bool),
+            ..Field::new("enabled")
+        },
+    ];
+
+    let mut body = utilities::edit_hcl("options { enabled = false }").expect("hcl error");
+
+    let Err(e) = delete(fields, &mut body) else {
+        panic!("deleting with a value type should fail");
+    };
+    assert_eq!("deleting with value types is not supported", e.to_string());
+}
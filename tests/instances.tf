@@ -0,0 +1,26 @@
+# several blocks that `.resource{"aws_instance"}` matches, not all of which
+# have every attribute
+resource "aws_instance" "web" {
+  ami = "ami-web"
+  tags = {
+    Name = "web"
+    Env  = "prod"
+  }
+}
+
+resource "aws_instance" "db" {
+  ami = "ami-db"
+}
+
+resource "aws_instance" "cache" {
+  ami = "ami-cache"
+  tags = {
+    Name = "cache"
+  }
+}
+
+resource "aws_s3_bucket" "logs" {
+  tags = {
+    Name = "logs"
+  }
+}
@@ -0,0 +1,143 @@
+use std::fs;
+
+use hq_rs::{merge, parser::Field, query};
+
+fn read_fixture(name: &str) -> hcl::Body {
+    let contents = fs::read_to_string(format!("tests/override/{name}")).expect("read error");
+    hcl::from_str(&contents).expect("hcl error")
+}
+
+fn merged_fixtures() -> hcl::Body {
+    merge([read_fixture("main.tf"), read_fixture("main_override.tf")])
+}
+
+#[test]
+fn merge_overrides_attribute() {
+    // filter '.resource{"aws_instance","web"}.instance_type'
+    let fields = vec![
+        Field::labeled("resource", &["aws_instance", "web"]),
+        Field::new("instance_type"),
+    ];
+
+    let body = merged_fixtures();
+    let query_results: Vec<String> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(vec!["\"t3.large\""], query_results);
+}
+
+#[test]
+fn merge_keeps_attribute() {
+    // filter '.resource{"aws_instance","web"}.ami'
+    let fields = vec![
+        Field::labeled("resource", &["aws_instance", "web"]),
+        Field::new("ami"),
+    ];
+
+    let body = merged_fixtures();
+    let query_results: Vec<String> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(vec!["\"ami-123\""], query_results);
+}
+
+#[test]
+fn merge_replaces_list_and_object() {
+    // filter '.resource{"aws_instance","web"}.security_groups'
+    let fields = vec![
+        Field::labeled("resource", &["aws_instance", "web"]),
+        Field::new("security_groups"),
+    ];
+
+    let body = merged_fixtures();
+    let query_results: Vec<String> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(vec!["[\n  \"prod\"\n]"], query_results);
+
+    // filter '.resource{"aws_instance","web"}.tags'
+    let fields = vec![
+        Field::labeled("resource", &["aws_instance", "web"]),
+        Field::new("tags"),
+    ];
+
+    let query_results: Vec<String> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(vec!["{\n  Name = \"web-prod\"\n}"], query_results);
+}
+
+#[test]
+fn merge_nested_blocks() {
+    // filter '.resource{"aws_instance","web"}.root_block_device'
+    let fields = vec![
+        Field::labeled("resource", &["aws_instance", "web"]),
+        Field::new("root_block_device"),
+    ];
+
+    let body = merged_fixtures();
+    let query_results: Vec<String> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(
+        vec!["root_block_device {\n  volume_size = 32\n  volume_type = \"gp3\"\n}\n"],
+        query_results
+    );
+
+    // filter '.terraform.backend{"s3"}.region'
+    let fields = vec![
+        Field::new("terraform"),
+        Field::labeled("backend", &["s3"]),
+        Field::new("region"),
+    ];
+
+    let query_results: Vec<String> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(vec!["\"eu-west-1\""], query_results);
+}
+
+#[test]
+fn merge_adds_new_blocks() {
+    // filter '.resource{"aws_instance"}'
+    let fields = vec![Field::labeled("resource", &["aws_instance"])];
+
+    let body = merged_fixtures();
+    let query_results = query(&fields, &body).expect("query error");
+
+    assert_eq!(3, query_results.len());
+}
+
+#[test]
+fn merge_repeated_blocks_nth_into_nth() {
+    let body = merge([
+        hcl::from_str("ingress { port = 80 }\ningress { port = 443 }\n").expect("hcl error"),
+        hcl::from_str(
+            "ingress { cidr = \"a\" }\ningress { cidr = \"b\" }\ningress { port = 22 }\n",
+        )
+        .expect("hcl error"),
+    ]);
+
+    assert_eq!(
+        "ingress {\n  port = 80\n  cidr = \"a\"\n}\n\ningress {\n  port = 443\n  cidr = \"b\"\n}\n\ningress {\n  port = 22\n}\n",
+        hcl::to_string(&body).unwrap()
+    );
+}
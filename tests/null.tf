@@ -0,0 +1,10 @@
+# attributes that are explicitly null, next to ones that aren't
+description = null
+region      = "us-east-1"
+
+settings = {
+  timeout = null
+  retries = 3
+}
+
+tags = ["web", null]
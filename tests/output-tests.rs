@@ -0,0 +1,778 @@
+use hq_rs::{
+    highlight::highlight,
+    output::{self, Indent},
+    parser::{Field, Index, Selector, Stage},
+    query, query_stages, query_stages_with_paths, QueryOptions, QueryResult,
+};
+
+#[test]
+fn json_obj_attr() {
+    // filter '.options'
+    let fields = vec![Field::new("options")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from(
+        "{\n  \"verbose\": true,\n  \"debug\": false\n}",
+    )];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| output::to_json(r).unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn json_compact_list_attr() {
+    // filter '.tags'
+    let fields = vec![Field::new("tags")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("[\"alpha\",\"beta\",\"gamma\"]")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| output::to_json_compact(r).unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn json_compact_single_line() {
+    // filter '..content'
+    let body: hcl::Body = hcl::from_str(
+        "content {\n  text = <<EOT\nfirst\nsecond\nEOT\n  list = [\n    1,\n    2,\n  ]\n}\n",
+    )
+    .expect("hcl error");
+    let mut field = Field::new("content");
+    field.recursive = true;
+    let results = query(&[field], &body).expect("query error");
+
+    // each result is a line of newline-delimited JSON
+    assert_eq!(
+        r#"{"content":{"text":"first\nsecond\n","list":[1,2]}}"#,
+        output::to_json_compact(&results[0]).unwrap()
+    );
+}
+
+#[test]
+fn json_labeled_block() {
+    // filter '.data{"a_data_block"}'
+    let fields = vec![Field::labeled("data", &["a_data_block"])];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from(
+        "{\"data\":{\"a_data_block\":{\"with_some_attrs\":{\"my_attr\":\"my_attr_value\",\"another_attr\":\"another_attr_value\"}}}}",
+    )];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| output::to_json_compact(r).unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn json_nested_blocks() {
+    // filter '.resource{"aws_security_group"}'
+    let fields = vec![Field::labeled("resource", &["aws_security_group"])];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    // nested blocks become nested objects, and expressions that can't be
+    // evaluated are emitted as their source
+    let expected = vec![String::from(
+        "{\"resource\":{\"aws_security_group\":{\"web\":{\"dynamic\":{\"ingress\":{\"for_each\":\"${var.ports}\",\"content\":{\"from_port\":\"${ingress.value}\",\"dynamic\":{\"cidr\":{\"content\":{\"block\":\"0.0.0.0/0\"}}}}}}}}}}",
+    )];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| output::to_json_compact(r).unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn yaml_labeled_block() {
+    // filter '.variable{"my_var"}'
+    let fields = vec![Field::labeled("variable", &["my_var"])];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from(
+        "variable:\n  my_var:\n    default: my_default_value\n",
+    )];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| output::to_yaml(r).unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn yaml_list_attr() {
+    // filter '.tags'
+    let fields = vec![Field::new("tags")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("- alpha\n- beta\n- gamma\n")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| output::to_yaml(r).unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn toml_output() {
+    // filter '.variable{"my_var"}, .tags, .rules'
+    let body = utilities::read_test_hcl().expect("hcl error");
+    let stages = hq_rs::parse_filter(r#".variable{"my_var"}, .tags, .rules"#).unwrap();
+
+    let expected = vec![
+        String::from("[variable.my_var]\ndefault = \"my_default_value\"\n"),
+        String::from("[\"alpha\", \"beta\", \"gamma\"]"),
+        String::from("[{ port = 80, protocol = \"tcp\" }, { port = 443, protocol = \"tcp\" }]"),
+    ];
+
+    let results: Vec<_> = query_stages_with_paths(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|(path, r)| output::to_toml(r, path).unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn toml_repeated_blocks() {
+    // filter '.'
+    let body: hcl::Body = hcl::from_str("rule { port = 80 }\nrule { port = 443 }\n").unwrap();
+
+    let results: Vec<_> =
+        query_stages_with_paths(vec![Stage::new(vec![])], &body, &QueryOptions::default())
+            .expect("query error")
+            .iter()
+            .map(|(path, r)| output::to_toml(r, path).unwrap())
+            .collect();
+
+    assert_eq!(
+        vec![String::from(
+            "[[rule]]\nport = 80\n\n[[rule]]\nport = 443\n"
+        )],
+        results
+    );
+}
+
+#[test]
+fn json_null() {
+    // filter '.description, .settings'
+    let body = utilities::read_null_hcl().expect("hcl error");
+    let stages = hq_rs::parse_filter(".description, .settings").unwrap();
+
+    let expected = vec![
+        String::from("null"),
+        String::from("{\n  \"timeout\": null,\n  \"retries\": 3\n}"),
+    ];
+
+    let results: Vec<_> = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| output::to_json(r).unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn toml_null() {
+    // filter '.settings'
+    let body: hcl::Body = hcl::from_str("settings = { retry = [1, null] }\n").unwrap();
+    let stages = hq_rs::parse_filter(".settings").unwrap();
+
+    let (path, result) =
+        &query_stages_with_paths(stages, &body, &QueryOptions::default()).expect("query error")[0];
+    let e = output::to_toml(result, path).unwrap_err();
+
+    assert_eq!(
+        "'.settings.retry[1]' is null, which TOML can't represent",
+        e.to_string()
+    );
+}
+
+#[test]
+fn toml_null_in_block() {
+    let body: hcl::Body =
+        hcl::from_str("b {\n  c = null\n}\nresource \"a\" \"b\" {\n  c = null\n}\n").unwrap();
+    let error = |filter| {
+        let stages = hq_rs::parse_filter(filter).unwrap();
+        let (path, result) = &query_stages_with_paths(stages, &body, &QueryOptions::default())
+            .expect("query error")[0];
+        output::to_toml(result, path).unwrap_err().to_string()
+    };
+
+    // filter '.b'
+    assert_eq!("'.b.c' is null, which TOML can't represent", error(".b"));
+    // filter '.resource'
+    assert_eq!(
+        "'.resource{\"a\",\"b\"}.c' is null, which TOML can't represent",
+        error(".resource")
+    );
+}
+
+#[test]
+fn raw_scalars() {
+    // filter '.version, .options.verbose, .rules[0].port'
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let paths = vec![
+        vec![Field::new("version")],
+        vec![Field::new("options"), Field::new("verbose")],
+        vec![Field::indexed("rules", 0), Field::new("port")],
+    ];
+
+    let expected = vec![
+        Some(String::from("test")),
+        Some(String::from("true")),
+        Some(String::from("80")),
+    ];
+
+    let results: Vec<_> = paths
+        .into_iter()
+        .flat_map(|fields| query(&fields, &body).expect("query error"))
+        .map(|r| output::to_raw(&r))
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn raw_compound() {
+    // filter '.tags'
+    let fields = vec![Field::new("tags")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(output::to_raw)
+        .collect();
+
+    assert_eq!(vec![None], results);
+}
+
+#[test]
+fn hcl_raw() {
+    // filter '.resource.tags, .resource.tags.list[1:]'
+    let source = "resource \"a\" \"b\" {\n  tags = {\n    Env  = \"prod\" # comment\n    list = [1,  2]\n  }\n}\n";
+    let stages = vec![Stage {
+        paths: vec![
+            vec![Field::new("resource"), Field::new("tags")],
+            vec![Field::new("resource"), Field::new("tags"), {
+                let mut field = Field::new("list");
+                field.selector = Some(Selector::Slice {
+                    start: Some(Index::FromStart(1)),
+                    end: None,
+                });
+                field
+            }],
+        ],
+    }];
+    let body: hcl::Body = hcl::from_str(source).expect("hcl error");
+    let edit_body: hcl_edit::structure::Body = source.parse().expect("hcl error");
+
+    let expected = vec![
+        Some(String::from(
+            "{\n    Env  = \"prod\" # comment\n    list = [1,  2]\n  }",
+        )),
+        // a slice isn't contiguous in the source
+        None,
+    ];
+
+    let results: Vec<_> = query_stages_with_paths(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|(path, _)| output::to_hcl_raw(source, &edit_body, path))
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn keep_comments() {
+    let source = r#"
+# the web server
+resource "aws_instance" "web" {
+  # pinned
+  ami = "ami-123" # do not change
+
+  lifecycle {
+    prevent_destroy = true // keep it
+  }
+}
+
+resource "aws_instance" "db" {
+  ami = "ami-456" # the other one
+}
+"#;
+    let body: hcl::Body = hcl::from_str(source).expect("hcl error");
+    let edit_body: hcl_edit::structure::Body = source.parse().expect("hcl error");
+
+    // filter '.resource{"aws_instance","web"}, .resource.ami, .'
+    let stages =
+        hq_rs::parse_filter(r#".resource{"aws_instance","web"}, .resource.ami, ."#).unwrap();
+    let results: Vec<_> = query_stages_with_paths(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|(path, r)| output::keep_comments(&r.to_string().unwrap(), &edit_body, path))
+        .collect();
+
+    assert_eq!(
+        Some(String::from(
+            "# the web server\nresource \"aws_instance\" \"web\" {\n  # pinned\n  ami = \"ami-123\" # do not change\n\n  lifecycle {\n    prevent_destroy = true // keep it\n  }\n}\n"
+        )),
+        results[0]
+    );
+    // attributes' values have no comments of their own
+    assert_eq!(None, results[1]);
+    assert_eq!(None, results[2]);
+    // the whole document keeps the comments of every block, and reordered
+    // blocks keep their own comments
+    let document = results[3].as_deref().unwrap();
+    assert!(document.contains("# the web server\nresource \"aws_instance\" \"web\""));
+    assert!(document.contains("ami = \"ami-456\" # the other one"));
+
+    let sorted = output::sort_keys(&QueryResult::Body(body));
+    let document = output::keep_comments(
+        &sorted.to_string().unwrap(),
+        &edit_body,
+        &hq_rs::path::Path::default(),
+    )
+    .unwrap();
+    assert!(document.contains("ami = \"ami-123\" # do not change"));
+    assert!(document.contains("prevent_destroy = true // keep it"));
+}
+
+#[test]
+fn heredocs() {
+    // filter '.heredoc, .indented_heredoc'
+    let paths = vec![
+        vec![Field::new("heredoc")],
+        vec![Field::new("indented_heredoc")],
+    ];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    // the trailing newline is part of the string, and `<<-` strips the
+    // indentation shared by the non-blank lines
+    let expected = vec![
+        (
+            String::from("\"first line\\n  second line\\n\""),
+            Some(String::from("first line\n  second line\n")),
+        ),
+        (
+            String::from("\"first line\\n\\n  second line\\n\""),
+            Some(String::from("first line\n\n  second line\n")),
+        ),
+    ];
+
+    let results: Vec<_> = paths
+        .into_iter()
+        .flat_map(|fields| query(&fields, &body).expect("query error"))
+        .map(|r| (output::to_json(&r).unwrap(), output::to_raw(&r)))
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+fn strip_colors(s: &str) -> String {
+    let mut stripped = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // skip to the end of the escape sequence
+            chars.by_ref().find(|c| *c == 'm');
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+#[test]
+fn highlight_only_adds_colors() {
+    let hcl = std::fs::read_to_string("tests/test.tf").expect("read error");
+
+    let highlighted = highlight(&hcl);
+
+    assert_ne!(hcl, highlighted);
+    assert_eq!(hcl, strip_colors(&highlighted));
+}
+
+#[test]
+fn highlight_tokens() {
+    let hcl = "block \"label\" {\n  attr = 1 # note\n  flag = a == true\n}\n";
+
+    let expected = "\x1b[1m\x1b[34mblock\x1b[0m \x1b[32m\"label\"\x1b[0m {\n  \
+                    \x1b[34mattr\x1b[0m = \x1b[36m1\x1b[0m \x1b[90m# note\x1b[0m\n  \
+                    \x1b[34mflag\x1b[0m = a == \x1b[35mtrue\x1b[0m\n}\n";
+
+    assert_eq!(expected, highlight(hcl));
+}
+
+#[test]
+fn json_identity() {
+    // filter '.'
+    let source = "a = 1\nblock \"label\" {\n  b = true # comment\n}\n";
+    let body: hcl::Body = hcl::from_str(source).expect("hcl error");
+    let edit_body: hcl_edit::structure::Body = source.parse().expect("hcl error");
+
+    let results =
+        query_stages_with_paths(vec![Stage::new(vec![])], &body, &QueryOptions::default())
+            .expect("query error");
+
+    let json: Vec<_> = results
+        .iter()
+        .map(|(_, r)| output::to_json_compact(r).unwrap())
+        .collect();
+    assert_eq!(
+        vec![String::from(r#"{"a":1,"block":{"label":{"b":true}}}"#)],
+        json
+    );
+
+    // the whole document is printed verbatim as hcl-raw
+    let raw: Vec<_> = results
+        .iter()
+        .map(|(path, _)| output::to_hcl_raw(source, &edit_body, path))
+        .collect();
+    assert_eq!(vec![Some(String::from(source))], raw);
+}
+
+#[test]
+fn for_expr_source() {
+    // filter '.names'
+    let fields = vec![Field::new("names")];
+    let body = hcl::from_str("names = [for s in var.list : upper(s)]\n").expect("hcl error");
+
+    let results = query(&fields, &body).expect("query error");
+
+    assert_eq!(1, results.len());
+    assert!(!results[0].is_literal());
+    // HCL output is the for expression itself
+    assert_eq!(
+        "[for s in var.list : upper(s)]",
+        results[0].to_string().unwrap()
+    );
+    // other formats get its source in an interpolated string
+    assert_eq!(
+        "\"${[for s in var.list : upper(s)]}\"",
+        output::to_json(&results[0]).unwrap()
+    );
+}
+
+#[test]
+fn literal_results() {
+    // filter '.options, .tags, .heredoc, .interpolated'
+    let paths = vec![
+        vec![Field::new("options")],
+        vec![Field::new("tags")],
+        vec![Field::new("heredoc")],
+        vec![Field::new("interpolated")],
+    ];
+    let source = "options = { a = 1 }\ntags = [\"a\"]\nheredoc = <<EOF\nx\nEOF\ninterpolated = \"${var.name}-suffix\"\n";
+    let body: hcl::Body = hcl::from_str(source).expect("hcl error");
+
+    let results: Vec<_> = query_stages(vec![Stage { paths }], &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.is_literal())
+        .collect();
+
+    assert_eq!(vec![true, true, true, false], results);
+}
+
+#[test]
+fn indented_output() {
+    // filter '.options'
+    let fields = vec![Field::new("options")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+    let results = query(&fields, &body).expect("query error");
+
+    assert_eq!(
+        "{\n    verbose = true\n    debug = false\n}",
+        output::to_hcl_indented(&results[0], Indent::Spaces(4)).unwrap()
+    );
+    assert_eq!(
+        "{\n\t\"verbose\": true,\n\t\"debug\": false\n}",
+        output::to_json_indented(&results[0], Indent::Tab).unwrap()
+    );
+    // the default matches the unindented functions
+    assert_eq!(
+        results[0].to_string().unwrap(),
+        output::to_hcl_indented(&results[0], Indent::default()).unwrap()
+    );
+    assert_eq!(
+        output::to_json(&results[0]).unwrap(),
+        output::to_json_indented(&results[0], Indent::default()).unwrap()
+    );
+}
+
+/// render `result` as HCL, and parse it back as a document
+fn reparse(result: &QueryResult) -> QueryResult {
+    let hcl = result.to_string().unwrap();
+    QueryResult::Body(hcl::from_str(&hcl).unwrap_or_else(|e| panic!("{e}:\n{hcl}")))
+}
+
+#[test]
+fn hcl_round_trip() {
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    // filter '.'
+    let results = query(&[], &body).expect("query error");
+    let document = &results[0];
+    assert_eq!(document.to_value(), reparse(document).to_value());
+
+    // filter '..{ident}' for every kind of block, at any depth
+    for ident in [
+        "variable", "data", "module", "resource", "dynamic", "content",
+    ] {
+        let mut field = Field::new(ident);
+        field.recursive = true;
+        let blocks = query(&[field], &body).expect("query error");
+        assert!(!blocks.is_empty());
+        for block in &blocks {
+            assert_eq!(block.to_value(), reparse(block).to_value());
+        }
+    }
+}
+
+#[test]
+fn hcl_round_trip_templates() {
+    let body = hcl::from_str(
+        r#"
+quotes = "say \"hi\" to ${name}"
+escapes = "tab\t${x}\n\\ and \\${y} and $${z} and %%{w}"
+directive = "%{ if x }\"yes\"%{ else }no%{ endif }"
+nested = "${"a\"b${x}"}"
+object = { "${key}\"" = "v" }
+block "with \"quotes\"" "back\\slash" {
+  call = format("%s \"${x}\"", y)
+}
+"#,
+    )
+    .expect("hcl error");
+    let document = QueryResult::Body(body);
+
+    let hcl = document.to_string().unwrap();
+    assert!(hcl.contains(r#"quotes = "say \"hi\" to ${name}""#), "{hcl}");
+    assert!(
+        hcl.contains(r#"escapes = "tab\t${x}\n\\ and \\${y} and $${z} and %%{w}""#),
+        "{hcl}"
+    );
+    assert_eq!(document.to_value(), reparse(&document).to_value());
+}
+
+#[test]
+fn compact_output() {
+    let body: hcl::Body = hcl::from_str(
+        r#"
+block "label" {
+  object = {
+    port = 80
+    list = [
+      1,
+      2,
+    ]
+  }
+}
+other {
+  a = 1
+  nested {
+    b = "two"
+  }
+  empty {}
+}
+"#,
+    )
+    .expect("hcl error");
+    let document = QueryResult::Body(body);
+
+    assert_eq!(
+        "block \"label\" { object = { port = 80, list = [1, 2] } }\nother {\n  a = 1\n  nested { b = \"two\" }\n  empty {}\n}\n",
+        output::to_hcl_compact(&document).unwrap()
+    );
+    assert_eq!(
+        "{\"block\":{\"label\":{\"object\":{\"port\":80,\"list\":[1,2]}}},\"other\":{\"a\":1,\"nested\":{\"b\":\"two\"},\"empty\":{}}}",
+        output::to_yaml_compact(&document).unwrap()
+    );
+    assert_eq!(
+        "block = { label = { object = { port = 80, list = [1, 2] } } }\nother = { a = 1, nested = { b = \"two\" }, empty = {} }\n",
+        output::to_toml_compact(&document, &hq_rs::path::Path::default()).unwrap()
+    );
+}
+
+#[test]
+fn compact_round_trip() {
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    // filter '.'
+    let results = query(&[], &body).expect("query error");
+    let hcl = output::to_hcl_compact(&results[0]).unwrap();
+    let reparsed: hcl::Body = hcl::from_str(&hcl).unwrap_or_else(|e| panic!("{e}:\n{hcl}"));
+    assert_eq!(
+        results[0].to_value(),
+        QueryResult::Body(reparsed).to_value()
+    );
+
+    // filter '..resource'
+    let mut field = Field::new("resource");
+    field.recursive = true;
+    for block in query(&[field], &body).expect("query error") {
+        let hcl = output::to_hcl_compact(&block).unwrap();
+        let reparsed: hcl::Body = hcl::from_str(&hcl).unwrap_or_else(|e| panic!("{e}:\n{hcl}"));
+        assert_eq!(block.to_value(), QueryResult::Body(reparsed).to_value());
+    }
+}
+
+#[test]
+fn interpolation_passthrough() {
+    let source = std::fs::read_to_string("tests/test.tf").expect("read error");
+    let edit_body = utilities::edit_hcl(&source).expect("hcl error");
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    // filter '.interpolated'
+    let stages = vec![Stage::new(vec![Field::new("interpolated")])];
+    let results =
+        query_stages_with_paths(stages, &body, &QueryOptions::default()).expect("query error");
+    let (path, result) = &results[0];
+    assert_eq!("\"${var.prefix}-web\"", result.to_string().unwrap());
+    assert_eq!("\"${var.prefix}-web\"", output::to_json(result).unwrap());
+    assert_eq!(
+        "\"${var.prefix}-web\"",
+        output::to_json_compact(result).unwrap()
+    );
+    assert_eq!("${var.prefix}-web\n", output::to_yaml(result).unwrap());
+    assert_eq!(Some("${var.prefix}-web"), output::to_raw(result).as_deref());
+    assert_eq!(
+        Some("\"${var.prefix}-web\""),
+        output::to_hcl_raw(&source, &edit_body, path).as_deref()
+    );
+
+    // filter '.escaped_interpolation'
+    let stages = vec![Stage::new(vec![Field::new("escaped_interpolation")])];
+    let results =
+        query_stages_with_paths(stages, &body, &QueryOptions::default()).expect("query error");
+    let (path, result) = &results[0];
+    assert_eq!("\"$${not_interpolated}\"", result.to_string().unwrap());
+    assert_eq!("\"${not_interpolated}\"", output::to_json(result).unwrap());
+    assert_eq!(
+        Some("${not_interpolated}"),
+        output::to_raw(result).as_deref()
+    );
+    assert_eq!(
+        Some("\"$${not_interpolated}\""),
+        output::to_hcl_raw(&source, &edit_body, path).as_deref()
+    );
+}
+
+#[test]
+fn sorted_keys() {
+    // filter '.'
+    let body = hcl::from_str(
+        r#"
+z = [{ y = 1, b = 2 }, 0]
+provisioner "remote-exec" {}
+a = { q = 1, "c" = 2 }
+provisioner "file" {
+  source = "x"
+  destination = "y"
+}
+"#,
+    )
+    .expect("hcl error");
+    let results = query(&[], &body).expect("query error");
+
+    // blocks of the same type keep their order, and so do list elements
+    let expected = "a = {
+  \"c\" = 2
+  q = 1
+}
+
+provisioner \"remote-exec\" {}
+
+provisioner \"file\" {
+  destination = \"y\"
+  source = \"x\"
+}
+
+z = [
+  {
+    b = 2
+    y = 1
+  },
+  0
+]
+";
+    let sorted = output::sort_keys(&results[0]);
+    assert_eq!(expected, sorted.to_string().unwrap());
+    assert_eq!(
+        "{\"a\":{\"c\":2,\"q\":1},\"provisioner\":{\"remote-exec\":{},\"file\":{\"destination\":\"y\",\"source\":\"x\"}},\"z\":[{\"b\":2,\"y\":1},0]}",
+        output::to_json_compact(&sorted).unwrap()
+    );
+}
+
+#[test]
+fn standalone() {
+    let body = utilities::read_test_hcl().expect("hcl error");
+    let standalone = |filter: &str| {
+        let stages = hq_rs::parse_filter(filter).expect("parse error");
+        query_stages_with_paths(stages, &body, &QueryOptions::default())
+            .expect("query error")
+            .iter()
+            .map(|(path, result)| {
+                let hcl = result.to_string()?;
+                Ok(match output::standalone_key(result, path)? {
+                    Some(key) => format!("{key} = {hcl}"),
+                    None => hcl,
+                })
+            })
+            .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()
+    };
+
+    // each document parses back to the result it was printed from, as an
+    // attribute when it needed a key
+    for filter in [
+        ".variable",
+        ".data{\"a_data_block\"}",
+        ".tags",
+        ".settings.mode",
+        ".",
+    ] {
+        for hcl in standalone(filter).expect("output error") {
+            hcl::from_str::<hcl::Body>(&hcl).unwrap_or_else(|e| panic!("{e}:\n{hcl}"));
+        }
+    }
+    assert_eq!(
+        vec![String::from("mode = \"fast\"")],
+        standalone(".settings.mode").expect("output error")
+    );
+
+    // a list element has no key to be written with
+    let e = standalone(".tags[0]").expect_err("a list element can't stand alone");
+    assert!(e
+        .to_string()
+        .starts_with("the result at '.tags[0]' can't stand alone as an HCL document"));
+    // and neither does an object entry whose key isn't a valid name
+    assert!(standalone(".escaped[\"weird\\\"key\"]").is_err());
+}
@@ -0,0 +1,28 @@
+terraform {
+  required_version = ">= 1.5"
+
+  backend "s3" {
+    bucket = "state"
+    region = "us-east-1"
+  }
+}
+
+resource "aws_instance" "web" {
+  ami           = "ami-123"
+  instance_type = "t3.micro"
+  tags = {
+    Name = "web"
+    Team = "platform"
+  }
+  security_groups = ["default", "web"]
+
+  root_block_device {
+    volume_size = 8
+    volume_type = "gp3"
+  }
+}
+
+resource "aws_instance" "worker" {
+  ami           = "ami-123"
+  instance_type = "t3.micro"
+}
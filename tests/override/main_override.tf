@@ -0,0 +1,21 @@
+terraform {
+  backend "s3" {
+    region = "eu-west-1"
+  }
+}
+
+resource "aws_instance" "web" {
+  instance_type = "t3.large"
+  tags = {
+    Name = "web-prod"
+  }
+  security_groups = ["prod"]
+
+  root_block_device {
+    volume_size = 32
+  }
+}
+
+resource "aws_instance" "batch" {
+  ami = "ami-456"
+}
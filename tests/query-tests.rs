@@ -1,14 +1,22 @@
-use hq_rs::{parser::Field, query};
+use hq_rs::{
+    parser::{Builtin, Comparison, Field, Index, Label, Literal, Predicate, Selector, Stage},
+    path::{Path, Segment},
+    query,
+    query::QueryError,
+    query_stages, query_stages_each, query_stages_each_on, query_stages_with_paths, CompiledFilter,
+    HqError, QueryOptions, QueryResult,
+};
 
 #[test]
 fn scalar_attr() {
     // filter '.version'
-    let mut fields = vec![Field::new("version")];
+    let fields = vec![Field::new("version")];
     let body = utilities::read_test_hcl().expect("hcl error");
 
     let expected = vec![String::from("\"test\"")];
 
-    let results: Vec<_> = query(&mut fields, &body)
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
         .iter()
         .map(|r| r.to_string().unwrap())
         .collect();
@@ -19,12 +27,13 @@ fn scalar_attr() {
 #[test]
 fn obj_attr() {
     // filter '.options'
-    let mut fields = vec![Field::new("options")];
+    let fields = vec![Field::new("options")];
     let body = utilities::read_test_hcl().expect("hcl error");
 
     let expected = vec![String::from("{\n  verbose = true\n  debug = false\n}")];
 
-    let results: Vec<_> = query(&mut fields, &body)
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
         .iter()
         .map(|r| r.to_string().unwrap())
         .collect();
@@ -35,7 +44,7 @@ fn obj_attr() {
 #[test]
 fn block_attr() {
     // filter '.variable.default'
-    let mut fields = vec![Field::new("variable"), Field::new("default")];
+    let fields = vec![Field::new("variable"), Field::new("default")];
     let body = utilities::read_test_hcl().expect("hcl error");
 
     let expected = vec![
@@ -43,7 +52,8 @@ fn block_attr() {
         String::from("\"another_default_value\""),
     ];
 
-    let results: Vec<_> = query(&mut fields, &body)
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
         .iter()
         .map(|r| r.to_string().unwrap())
         .collect();
@@ -54,7 +64,7 @@ fn block_attr() {
 #[test]
 fn labeled_block_attr() {
     // filter '.variable{"my_var"}.default'
-    let mut fields = vec![
+    let fields = vec![
         Field::labeled("variable", &["my_var"]),
         Field::new("default"),
     ];
@@ -62,7 +72,8 @@ fn labeled_block_attr() {
 
     let expected = vec![String::from("\"my_default_value\"")];
 
-    let results: Vec<_> = query(&mut fields, &body)
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
         .iter()
         .map(|r| r.to_string().unwrap())
         .collect();
@@ -73,7 +84,7 @@ fn labeled_block_attr() {
 #[test]
 fn block() {
     // filter '.data'
-    let mut fields = vec![Field::new("data")];
+    let fields = vec![Field::new("data")];
     let body = utilities::read_test_hcl().expect("hcl error");
 
     let expected = vec![
@@ -81,7 +92,8 @@ fn block() {
         String::from("data \"another_data_block\" \"with_some_attrs\" {\n  cromulent_attr = \"cromulent_value\"\n}\n"),
     ];
 
-    let results: Vec<_> = query(&mut fields, &body)
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
         .iter()
         .map(|r| r.to_string().unwrap())
         .collect();
@@ -92,12 +104,152 @@ fn block() {
 #[test]
 fn labeled_block() {
     // filter '.data{"another_data_block"}'
-    let mut fields = vec![Field::labeled("data", &["another_data_block"])];
+    let fields = vec![Field::labeled("data", &["another_data_block"])];
     let body = utilities::read_test_hcl().expect("hcl error");
 
     let expected = vec![String::from("data \"another_data_block\" \"with_some_attrs\" {\n  cromulent_attr = \"cromulent_value\"\n}\n")];
 
-    let results: Vec<_> = query(&mut fields, &body)
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn multi_labeled_block_attr() {
+    // filter '.data{"a_data_block","with_some_attrs"}.my_attr'
+    let fields = vec![
+        Field::labeled("data", &["a_data_block", "with_some_attrs"]),
+        Field::new("my_attr"),
+    ];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("\"my_attr_value\"")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn wildcard_label() {
+    // filter '.data{*,"with_some_attrs"}.my_attr, .data{*,"with_some_attrs"}.cromulent_attr'
+    let data = Field {
+        labels: vec![Label::Any, Label::from("with_some_attrs")],
+        ..Field::new("data")
+    };
+    let stages = vec![Stage {
+        paths: vec![
+            vec![data.clone(), Field::new("my_attr")],
+            vec![data, Field::new("cromulent_attr")],
+        ],
+    }];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![
+        String::from("\"my_attr_value\""),
+        String::from("\"cromulent_value\""),
+    ];
+
+    let results: Vec<_> = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn attr_and_block_with_same_name() {
+    // filter '.shared'
+    let stages = vec![Stage::new(vec![Field::new("shared")])];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    // both the attribute and the block match, in source order
+    let expected = vec![
+        String::from(".shared: \"attribute\""),
+        String::from(".shared{\"label\"}: shared \"label\" {\n  value = \"block\"\n}\n"),
+    ];
+
+    let results: Vec<_> = query_stages_with_paths(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|(path, r)| format!("{path}: {}", r.to_string().unwrap()))
+        .collect();
+
+    assert_eq!(expected, results);
+
+    // filter '.shared.value'
+    let fields = vec![Field::new("shared"), Field::new("value")];
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(vec![String::from("\"block\"")], results);
+}
+
+#[test]
+fn source_order() {
+    // filter '.x'
+    let fields = vec![Field::new("x")];
+    let body = hcl::from_str("x \"b\" {}\nx = 1\nx \"a\" {}\n").expect("hcl error");
+
+    // blocks and attributes come out in the order they were written
+    let expected = vec![
+        String::from("x \"b\" {}\n"),
+        String::from("1"),
+        String::from("x \"a\" {}\n"),
+    ];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn labels_match_by_prefix() {
+    // filter '.data{"with_some_attrs"}'
+    let fields = vec![Field::labeled("data", &["with_some_attrs"])];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let results = query(&fields, &body).expect("query error");
+
+    assert!(results.is_empty());
+}
+
+#[test]
+fn too_many_labels() {
+    // filter '.variable{"my_var","extra"}'
+    let fields = vec![Field::labeled("variable", &["my_var", "extra"])];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    assert!(query(&fields, &body).is_err());
+}
+
+#[test]
+fn dash_named_attr() {
+    // filter '.my-block.my-attr'
+    let fields = vec![Field::new("my-block"), Field::new("my-attr")];
+    let body = hcl::from_str("my-block {\n  my-attr = 1\n}\n").expect("hcl error");
+
+    let expected = vec![String::from("1")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
         .iter()
         .map(|r| r.to_string().unwrap())
         .collect();
@@ -108,7 +260,7 @@ fn labeled_block() {
 #[test]
 fn dash_labeled_block() {
     // filter '.module{"cool-module"}.version'
-    let mut fields = vec![
+    let fields = vec![
         Field::labeled("module", &["cool-module"]),
         Field::new("version"),
     ];
@@ -116,10 +268,2680 @@ fn dash_labeled_block() {
 
     let expected = vec![String::from("\"1.2.3\"")];
 
-    let results: Vec<_> = query(&mut fields, &body)
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn inline_object_attr() {
+    // filter '.settings.mode'
+    let fields = vec![Field::new("settings"), Field::new("mode")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("\"fast\"")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn nested_inline_object_index() {
+    // filter '.nested_settings.retry.backoff[-1], .nested_settings.retry.attempts'
+    let stages = vec![Stage {
+        paths: vec![
+            vec![Field::new("nested_settings"), Field::new("retry"), {
+                let mut field = Field::new("backoff");
+                field.selector = Some(Selector::Index(Index::FromEnd(1)));
+                field
+            }],
+            vec![
+                Field::new("nested_settings"),
+                Field::new("retry"),
+                Field::new("attempts"),
+            ],
+        ],
+    }];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("4"), String::from("3")];
+
+    let results: Vec<_> = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn nested_inline_object_keys() {
+    // filter '.nested_settings.retry | keys'
+    let stages = vec![
+        Stage::new(vec![Field::new("nested_settings"), Field::new("retry")]),
+        Stage::new(vec![Field::builtin(Builtin::Keys)]),
+    ];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("[\n  \"attempts\",\n  \"backoff\"\n]")];
+
+    let results: Vec<_> = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn escaped_object_key() {
+    // filter '.escaped["weird\"key"]'
+    let fields = vec![Field::new("escaped"), Field::new("weird\"key")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("\"odd\"")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn quoted_digit_name() {
+    // filter '.ciphers."3des"'
+    let fields = vec![Field::new("ciphers"), Field::new("3des")];
+    let body =
+        hcl::from_str("ciphers = {\n  \"3des\" = false\n  aes = true\n}\n").expect("hcl error");
+
+    let expected = vec![String::from("false")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn unicode_name() {
+    // filter '.café.ünï'
+    let stages = vec![Stage::new(vec![Field::new("café"), Field::new("ünï")])];
+    let body = hcl::from_str("café = {\n  ünï = 1\n  \"a→b\" = 2\n}\n").expect("hcl error");
+
+    let results: Vec<_> = query_stages_with_paths(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|(path, r)| format!("{path}: {}", r.to_string().unwrap()))
+        .collect();
+
+    // names are only quoted in paths when they have to be
+    assert_eq!(vec![String::from(".café.ünï: 1")], results);
+    let path = Path::default()
+        .join(Segment::Attribute {
+            index: 0,
+            key: String::from("café"),
+        })
+        .join(Segment::Entry(String::from("a→b")));
+    assert_eq!(".café.\"a→b\"", path.to_string());
+}
+
+#[test]
+fn object_key() {
+    // filter '.options["debug"]'
+    let fields = vec![Field::keyed("options", "debug")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("false")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+
+    // filter '.options["missing"]'
+    let fields = vec![Field::keyed("options", "missing")];
+    let results = query(&fields, &body).expect("query error");
+    assert!(results.is_empty());
+}
+
+#[test]
+fn object_key_on_block_should_err() {
+    // filter '.data["a_data_block"]'
+    let fields = vec![Field::keyed("data", "a_data_block")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let result = query(&fields, &body);
+    assert!(result.is_err());
+}
+
+#[test]
+fn object_key_on_list() {
+    // filter '.tags["alpha"]'
+    let fields = vec![Field::keyed("tags", "alpha")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let result = query(&fields, &body);
+    assert!(result.is_err());
+
+    // filter '.tags["alpha"]?'
+    let mut field = Field::keyed("tags", "alpha");
+    field.optional = true;
+    let results = query(&[field], &body).expect("query error");
+    assert!(results.is_empty());
+}
+
+#[test]
+fn list_index() {
+    // filter '.tags[1]'
+    let fields = vec![Field::indexed("tags", 1)];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("\"beta\"")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn negative_list_index() {
+    // filter '.tags[-1]'
+    let mut field = Field::new("tags");
+    field.selector = Some(Selector::Index(Index::FromEnd(1)));
+    let fields = vec![field];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("\"gamma\"")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn multiple_list_indices() {
+    // filter '.tags[2,0,-1,0]'
+    let mut field = Field::new("tags");
+    field.selector = Some(Selector::Indices(vec![
+        Index::FromStart(2),
+        Index::FromStart(0),
+        Index::FromEnd(1),
+        Index::FromStart(0),
+    ]));
+    let mut fields = vec![field];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![
+        String::from(".tags[2]: \"gamma\""),
+        String::from(".tags[0]: \"alpha\""),
+        String::from(".tags[2]: \"gamma\""),
+        String::from(".tags[0]: \"alpha\""),
+    ];
+
+    let results: Vec<_> = query_stages_with_paths(
+        vec![Stage::new(fields.clone())],
+        &body,
+        &QueryOptions::default(),
+    )
+    .expect("query error")
+    .iter()
+    .map(|(path, r)| format!("{path}: {}", r.to_string().unwrap()))
+    .collect();
+    assert_eq!(expected, results);
+
+    // filter '.tags[0,5]'
+    fields[0].selector = Some(Selector::Indices(vec![
+        Index::FromStart(0),
+        Index::FromStart(5),
+    ]));
+    assert!(query(&fields, &body).is_err());
+    // filter '.tags[0,5]?'
+    fields[0].optional = true;
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+    assert_eq!(vec![String::from("\"alpha\"")], results);
+}
+
+#[test]
+fn negative_list_index_out_of_bounds() {
+    // filter '.tags[-4]'
+    let mut field = Field::new("tags");
+    field.selector = Some(Selector::Index(Index::FromEnd(4)));
+    let fields = vec![field];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    assert!(query(&fields, &body).is_err());
+}
+
+#[test]
+fn optional_list_index_out_of_bounds() {
+    // filter '.tags[5]?'
+    let mut field = Field::indexed("tags", 5);
+    field.optional = true;
+    let fields = vec![field];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let results = query(&fields, &body).expect("query error");
+
+    assert!(results.is_empty());
+}
+
+#[test]
+fn optional_index_not_a_list() {
+    // filter '.version[0]?'
+    let mut field = Field::indexed("version", 0);
+    field.optional = true;
+    let fields = vec![field];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    assert!(query(&fields, &body).is_err());
+}
+
+#[test]
+fn list_slice() {
+    // filter '.tags[1:3]'
+    let mut field = Field::new("tags");
+    field.selector = Some(Selector::Slice {
+        start: Some(Index::FromStart(1)),
+        end: Some(Index::FromStart(3)),
+    });
+    let fields = vec![field];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("[\n  \"beta\",\n  \"gamma\"\n]")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn empty_list_slice() {
+    // filter '.tags[5:]'
+    let mut field = Field::new("tags");
+    field.selector = Some(Selector::Slice {
+        start: Some(Index::FromStart(5)),
+        end: None,
+    });
+    let fields = vec![field];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("[]")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn list_iterate() {
+    // filter '.tags[]'
+    let mut field = Field::new("tags");
+    field.selector = Some(Selector::Iterate);
+    let fields = vec![field];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![
+        String::from("\"alpha\""),
+        String::from("\"beta\""),
+        String::from("\"gamma\""),
+    ];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
         .iter()
         .map(|r| r.to_string().unwrap())
         .collect();
 
     assert_eq!(expected, results);
 }
+
+#[test]
+fn list_iterate_chained() {
+    // filter '.rules[].port'
+    let mut field = Field::new("rules");
+    field.selector = Some(Selector::Iterate);
+    let fields = vec![field, Field::new("port")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("80"), String::from("443")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn recursive_attr() {
+    // filter '..port'
+    let mut field = Field::new("port");
+    field.recursive = true;
+    let fields = vec![field];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("80"), String::from("443")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn recursive_block() {
+    // filter '.resource..content'
+    let mut field = Field::new("content");
+    field.recursive = true;
+    let fields = vec![Field::new("resource"), field];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    // pre-order: the outer `content` block comes before the nested one
+    let expected = vec![
+        String::from("content {\n  from_port = ingress.value\n\n  dynamic \"cidr\" {\n    content {\n      block = \"0.0.0.0/0\"\n    }\n  }\n}\n"),
+        String::from("content {\n  block = \"0.0.0.0/0\"\n}\n"),
+    ];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn pipe() {
+    // filter '.rules[] | .protocol, .port'
+    let mut field = Field::new("rules");
+    field.selector = Some(Selector::Iterate);
+    let stages = vec![
+        Stage::new(vec![field]),
+        Stage {
+            paths: vec![vec![Field::new("protocol")], vec![Field::new("port")]],
+        },
+    ];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    // each rule's protocol and port are together
+    let expected = vec![
+        String::from("\"tcp\""),
+        String::from("80"),
+        String::from("\"tcp\""),
+        String::from("443"),
+    ];
+
+    let results: Vec<_> = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn ignore_case() {
+    // filter '.DATA{"A_Data_Block"}.My_Attr'
+    let stages = vec![Stage::new(vec![
+        Field::labeled("DATA", &["A_Data_Block"]),
+        Field::new("My_Attr"),
+    ])];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("\"my_attr_value\"")];
+
+    let options = QueryOptions {
+        ignore_case: true,
+        ..QueryOptions::default()
+    };
+    let results: Vec<_> = query_stages(stages.clone(), &body, &options)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+
+    // the default is case-sensitive
+    let results = query_stages(stages, &body, &QueryOptions::default()).expect("query error");
+    assert!(results.is_empty());
+}
+
+#[test]
+fn eval_source() {
+    let source = "resource \"aws_instance\" \"web\" {\n  ami = \"ami-123\"\n}\n";
+
+    let expected = vec![String::from("\"ami-123\"")];
+
+    let results: Vec<_> = hq_rs::eval(source, ".resource{\"aws_instance\"}.ami")
+        .expect("eval error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn eval_errors() {
+    assert!(matches!(
+        hq_rs::eval("a = ", ".a"),
+        Err(hq_rs::HqError::HclParse(_))
+    ));
+    assert!(matches!(
+        hq_rs::eval("a = 1", ".1a"),
+        Err(hq_rs::HqError::Filter(_))
+    ));
+    assert!(matches!(
+        hq_rs::eval("a = 1", ".a[0]"),
+        Err(hq_rs::HqError::TypeMismatch { .. })
+    ));
+    assert!(matches!(
+        hq_rs::eval("a = [1]", ".a[5]"),
+        Err(hq_rs::HqError::Query(_))
+    ));
+}
+
+#[test]
+fn error_report() {
+    let report = |source, filter| {
+        let Err(e) = hq_rs::eval(source, filter) else {
+            panic!("eval should fail");
+        };
+        hq_rs::ErrorReport::from(&e)
+    };
+
+    // parse errors in the filter and the HCL are described the same way
+    let filter = report("a = 1", ".a\n  | .b[");
+    assert_eq!("filter_parse", filter.kind);
+    assert_eq!((Some(2), Some(8)), (filter.line, filter.column));
+    let hcl = report("a = 1\nb = ", ".a");
+    assert_eq!("hcl_parse", hcl.kind);
+    assert_eq!(Some(2), hcl.line);
+    assert_eq!(
+        format!(
+            "{{\"kind\":\"hcl_parse\",\"message\":{},\"line\":2,\"column\":{},\"file\":null}}",
+            serde_json::to_string(&hcl.message).unwrap(),
+            hcl.column.expect("a parse error has a column"),
+        ),
+        hcl.to_json()
+    );
+
+    let query = report("a = 1", ".a[0]");
+    assert_eq!("query", query.kind);
+    assert_eq!(
+        "cannot apply '[0]' to '.a', it is a number, not a list",
+        query.message
+    );
+    assert_eq!((None, None), (query.line, query.column));
+}
+
+#[test]
+fn type_mismatch() {
+    let hcl = utilities::read_test_hcl().expect("hcl error");
+
+    // filter '.version[0]'
+    let Err(HqError::TypeMismatch {
+        path,
+        segment,
+        expected,
+        found,
+    }) = hq_rs::CompiledFilter::compile(".version[0]")
+        .unwrap()
+        .apply(&hcl)
+    else {
+        panic!("expected a type mismatch");
+    };
+    assert_eq!(".version", path);
+    assert_eq!("[0]", segment);
+    assert_eq!("a list", expected);
+    assert_eq!("a string", found);
+
+    // filter '.variable{"my_var"}.default["key"]'
+    let Err(HqError::TypeMismatch {
+        path,
+        segment,
+        expected,
+        found,
+    }) = hq_rs::CompiledFilter::compile(r#".variable{"my_var"}.default["key"]"#)
+        .unwrap()
+        .apply(&hcl)
+    else {
+        panic!("expected a type mismatch");
+    };
+    assert_eq!(r#".variable{"my_var"}.default"#, path);
+    assert_eq!(r#"["key"]"#, segment);
+    assert_eq!("an object", expected);
+    assert_eq!("a string", found);
+
+    // filter '.version[]'
+    let Err(e) = hq_rs::CompiledFilter::compile(".version[]")
+        .unwrap()
+        .apply(&hcl)
+    else {
+        panic!("expected a type mismatch");
+    };
+    let rendered = e.to_string();
+    assert!(rendered.contains(".version[]"));
+    assert!(rendered.contains("'.version' is a string, not a list or an object"));
+}
+
+#[test]
+fn label_on_attribute() {
+    let hcl = utilities::read_test_hcl().expect("hcl error");
+
+    // filter '.options{"verbose"}'
+    let Err(HqError::LabelOnAttribute { path, label }) =
+        hq_rs::CompiledFilter::compile(r#".options{"verbose"}"#)
+            .unwrap()
+            .apply(&hcl)
+    else {
+        panic!("expected labels on an attribute");
+    };
+    assert_eq!(".options", path);
+    assert_eq!("verbose", label);
+
+    // filter '.locals.common_tags{"Team"}'
+    let Err(e) = hq_rs::CompiledFilter::compile(r#".locals.common_tags{"Team"}"#)
+        .unwrap()
+        .apply(&hcl)
+    else {
+        panic!("expected labels on an attribute");
+    };
+    let rendered = e.to_string();
+    assert!(rendered.contains("'.locals.common_tags' is an attribute"));
+    assert!(rendered.contains(r#"use ["Team"] to look up a key"#));
+
+    // filter '.options["verbose"]'
+    let results = hq_rs::CompiledFilter::compile(r#".options["verbose"]"#)
+        .unwrap()
+        .apply(&hcl)
+        .expect("query error");
+    assert_eq!("true", results[0].to_string().unwrap());
+
+    // filter '..options{"verbose"}'
+    let mut field = Field::new("options");
+    field.labels = vec![Label::from("verbose")];
+    field.recursive = true;
+    assert!(query(&[field], &hcl).expect("query error").is_empty());
+}
+
+#[test]
+fn hcl_parse_error_location() {
+    let Err(hq_rs::HqError::HclParse(e)) = hq_rs::eval("a = 1\nb = {\n  c = \n}\n", ".a") else {
+        panic!("expected an HCL parse error");
+    };
+    assert_eq!((3, 7), (e.line(), e.column()));
+    assert_eq!(None, e.file());
+    assert!(e.message().starts_with("invalid expression"));
+
+    let e = e.with_file("main.tf");
+    assert_eq!(Some("main.tf"), e.file());
+    let rendered = e.to_string();
+    assert!(rendered.contains("main.tf:3:7"));
+    assert!(rendered.contains("  c = "));
+}
+
+#[test]
+fn result_paths() {
+    // filter '..content'
+    let mut field = Field::new("content");
+    field.recursive = true;
+    let stages = vec![Stage::new(vec![field])];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let results =
+        query_stages_with_paths(stages, &body, &QueryOptions::default()).expect("query error");
+
+    let identifiers: Vec<Vec<_>> = results
+        .iter()
+        .map(|(path, _)| {
+            path.segments
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Block { identifier, .. } => identifier.as_str(),
+                    _ => "?",
+                })
+                .collect()
+        })
+        .collect();
+
+    assert_eq!(
+        vec![
+            vec!["resource", "dynamic", "content"],
+            vec!["resource", "dynamic", "content", "dynamic", "content"],
+        ],
+        identifiers
+    );
+}
+
+fn equals(path: Vec<Field>, value: Literal) -> Option<Predicate> {
+    Some(Predicate::Compare {
+        path,
+        comparison: Comparison::Equal,
+        value,
+    })
+}
+
+#[test]
+fn block_predicate() {
+    // filter '.data[.my_attr == "my_attr_value"]'
+    let mut field = Field::new("data");
+    field.predicate = equals(
+        vec![Field::new("my_attr")],
+        Literal::String(String::from("my_attr_value")),
+    );
+    let fields = vec![field, Field::new("another_attr")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("\"another_attr_value\"")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn has_predicate() {
+    // filter '.data[has("cromulent_attr")], .resource.dynamic[has("content")] | keys'
+    let mut data = Field::new("data");
+    data.predicate = Some(Predicate::Has(String::from("cromulent_attr")));
+    let mut dynamic = Field::new("dynamic");
+    dynamic.predicate = Some(Predicate::Has(String::from("content")));
+    let stages = vec![
+        Stage {
+            paths: vec![vec![data], vec![Field::new("resource"), dynamic]],
+        },
+        Stage::new(vec![Field::builtin(Builtin::Keys)]),
+    ];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![
+        String::from("[\n  \"cromulent_attr\"\n]"),
+        String::from("[\n  \"content\",\n  \"for_each\"\n]"),
+    ];
+
+    let results: Vec<_> = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn not_predicate() {
+    // filter '.data[not has("cromulent_attr")].my_attr, .rules[][!.port == 80].port'
+    let mut data = Field::new("data");
+    data.predicate = Some(Predicate::Not(Box::new(Predicate::Has(String::from(
+        "cromulent_attr",
+    )))));
+    let mut rules = Field::new("rules");
+    rules.selector = Some(Selector::Iterate);
+    rules.predicate = Some(Predicate::Not(Box::new(
+        equals(vec![Field::new("port")], Literal::Number(80.0)).unwrap(),
+    )));
+    let stages = vec![Stage {
+        paths: vec![
+            vec![data, Field::new("my_attr")],
+            vec![rules, Field::new("port")],
+        ],
+    }];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("\"my_attr_value\""), String::from("443")];
+
+    let results: Vec<_> = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn has_predicate_on_exprs() {
+    // filter '.rules[][has("port")], .tags[][has("port")]'
+    let has_port = |name| {
+        let mut field = Field::new(name);
+        field.selector = Some(Selector::Iterate);
+        field.predicate = Some(Predicate::Has(String::from("port")));
+        vec![field]
+    };
+    let stages = vec![Stage {
+        paths: vec![has_port("rules"), has_port("tags")],
+    }];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let results = query_stages(stages, &body, &QueryOptions::default()).expect("query error");
+
+    // both rules are objects with a port, and none of the tags are objects
+    assert_eq!(2, results.len());
+}
+
+#[test]
+fn element_predicate() {
+    // filter '.rules[][.port == 443].protocol'
+    let mut field = Field::new("rules");
+    field.selector = Some(Selector::Iterate);
+    field.predicate = equals(vec![Field::new("port")], Literal::Number(443.0));
+    let fields = vec![field, Field::new("protocol")];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![String::from("\"tcp\"")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn bool_predicate() {
+    // filter '.options[.verbose == true], .options[.debug == true]'
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let mut field = Field::new("options");
+    field.predicate = equals(vec![Field::new("verbose")], Literal::Bool(true));
+    let results = query(&[field], &body).expect("query error");
+    assert_eq!(1, results.len());
+
+    let mut field = Field::new("options");
+    field.predicate = equals(vec![Field::new("debug")], Literal::Bool(true));
+    let results = query(&[field], &body).expect("query error");
+    assert!(results.is_empty());
+}
+
+#[test]
+fn mismatched_type_predicate() {
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    // filter '.rules[][.port == "80"]', strings are never converted to numbers
+    let mut field = Field::new("rules");
+    field.selector = Some(Selector::Iterate);
+    field.predicate = equals(
+        vec![Field::new("port")],
+        Literal::String(String::from("80")),
+    );
+    let results = query(&[field], &body).expect("query error");
+    assert!(results.is_empty());
+
+    // filter '.rules[][.protocol == 80]'
+    let mut field = Field::new("rules");
+    field.selector = Some(Selector::Iterate);
+    field.predicate = equals(vec![Field::new("protocol")], Literal::Number(80.0));
+    let results = query(&[field], &body).expect("query error");
+    assert!(results.is_empty());
+
+    // filter '.rules[][.port == 80.0]', but numbers compare by value
+    let mut field = Field::new("rules");
+    field.selector = Some(Selector::Iterate);
+    field.predicate = equals(vec![Field::new("port")], Literal::Number(80.0));
+    let results = query(&[field], &body).expect("query error");
+    assert_eq!(1, results.len());
+}
+
+#[test]
+fn keys() {
+    // filter 'keys'
+    let fields = vec![Field::builtin(Builtin::Keys)];
+    let body = hcl::from_str("b = 1\nblock {}\na = 2\nblock \"label\" {}\n").expect("hcl error");
+
+    let expected = vec![String::from("[\n  \"a\",\n  \"b\",\n  \"block\"\n]")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn keys_of_results() {
+    // filter '.data{"a_data_block"}, .options, .tags | keys'
+    let stages = vec![
+        Stage {
+            paths: vec![
+                vec![Field::labeled("data", &["a_data_block"])],
+                vec![Field::new("options")],
+                vec![Field::new("tags")],
+            ],
+        },
+        Stage::new(vec![Field::builtin(Builtin::Keys)]),
+    ];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec![
+        String::from("[\n  \"another_attr\",\n  \"my_attr\"\n]"),
+        String::from("[\n  \"debug\",\n  \"verbose\"\n]"),
+        String::from("[\n  0,\n  1,\n  2\n]"),
+    ];
+
+    let results: Vec<_> = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn keys_of_scalar_should_err() {
+    // filter '.version | keys'
+    let stages = vec![
+        Stage::new(vec![Field::new("version")]),
+        Stage::new(vec![Field::builtin(Builtin::Keys)]),
+    ];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let result = query_stages(stages, &body, &QueryOptions::default());
+    assert!(result.is_err());
+}
+
+#[test]
+fn length() {
+    // filter '.rules, .options, .version, .heredoc, .data{"a_data_block"} | length'
+    let stages = vec![
+        Stage {
+            paths: vec![
+                vec![Field::new("rules")],
+                vec![Field::new("options")],
+                vec![Field::new("version")],
+                vec![Field::new("heredoc")],
+                vec![Field::labeled("data", &["a_data_block"])],
+            ],
+        },
+        Stage::new(vec![Field::builtin(Builtin::Length)]),
+    ];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let expected = vec!["2", "2", "4", "25", "2"];
+
+    let results: Vec<_> = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn length_of_number_should_err() {
+    // filter '.rules[0].port | length'
+    let stages = vec![
+        Stage::new(vec![Field::indexed("rules", 0), Field::new("port")]),
+        Stage::new(vec![Field::builtin(Builtin::Length)]),
+    ];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let err = query_stages(stages, &body, &QueryOptions::default())
+        .err()
+        .expect("length of a number should err");
+    assert!(err
+        .to_string()
+        .contains("cannot apply 'length' to a number"));
+}
+
+#[test]
+fn streamed_results() {
+    // filter '.rules[] | .protocol, .port'
+    let mut field = Field::new("rules");
+    field.selector = Some(Selector::Iterate);
+    let stages = vec![
+        Stage::new(vec![field]),
+        Stage {
+            paths: vec![vec![Field::new("protocol")], vec![Field::new("port")]],
+        },
+    ];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    // results are emitted in the same order they are collected in
+    let mut streamed = Vec::new();
+    query_stages_each(&stages, &body, &QueryOptions::default(), |_, r| {
+        streamed.push(r.to_string().unwrap());
+        Ok::<(), Box<QueryError>>(())
+    })
+    .expect("query error");
+    let collected: Vec<_> = query_stages(stages.clone(), &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+    assert_eq!(collected, streamed);
+
+    // an error from the callback stops the query
+    let mut calls = 0;
+    let result = query_stages_each(&stages, &body, &QueryOptions::default(), |_, _| {
+        calls += 1;
+        Err(Box::<dyn std::error::Error>::from("stop"))
+    });
+    assert_eq!("stop", result.unwrap_err().to_string());
+    assert_eq!(1, calls);
+}
+
+#[test]
+fn stages_on_result() {
+    // filter '.a | length' on the results of several documents, like `--slurp`
+    let stages = [
+        Stage::new(vec![Field::new("a")]),
+        Stage::new(vec![Field::builtin(Builtin::Length)]),
+    ];
+    let mut slurped = Vec::new();
+    for source in ["a = 1", "a = 2\nb = 3"] {
+        let body: hcl::Body = hcl::from_str(source).expect("hcl error");
+        query_stages_each(&stages[..1], &body, &QueryOptions::default(), |_, r| {
+            slurped.push(hcl::Expression::from(r.to_value()));
+            Ok::<(), Box<QueryError>>(())
+        })
+        .expect("query error");
+    }
+    let input = QueryResult::Expr(hcl::Expression::Array(slurped));
+
+    let mut results = Vec::new();
+    query_stages_each_on(
+        &stages[1..],
+        Path::default(),
+        input,
+        &QueryOptions::default(),
+        |path, r| {
+            results.push((path, r.to_string().unwrap()));
+            Ok::<(), Box<QueryError>>(())
+        },
+    )
+    .expect("query error");
+
+    let expected = vec![(
+        Path::default().join(Segment::Builtin(Builtin::Length)),
+        String::from("2"),
+    )];
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn identity() {
+    // filter '.'
+    let fields = vec![];
+    let body = hcl::from_str("a = 1\nblock \"label\" {\n  b = true\n}\n").expect("hcl error");
+
+    let expected = vec![String::from("a = 1\n\nblock \"label\" {\n  b = true\n}\n")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn identity_in_pipes() {
+    // filter '. | keys, .block{"label"}.b'
+    let stages = vec![
+        Stage::new(vec![]),
+        Stage {
+            paths: vec![
+                vec![Field::builtin(Builtin::Keys)],
+                vec![Field::labeled("block", &["label"]), Field::new("b")],
+            ],
+        },
+    ];
+    let body = hcl::from_str("a = 1\nblock \"label\" {\n  b = true\n}\n").expect("hcl error");
+
+    let expected = vec![
+        String::from("[\n  \"a\",\n  \"block\"\n]"),
+        String::from("true"),
+    ];
+
+    let results: Vec<_> = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn identity_predicate() {
+    // filter '.tags[][. == "web"]'
+    let mut field = Field::new("tags");
+    field.selector = Some(Selector::Iterate);
+    field.predicate = Some(Predicate::Compare {
+        path: vec![],
+        comparison: Comparison::Equal,
+        value: Literal::String(String::from("web")),
+    });
+    let fields = vec![field];
+    let body = hcl::from_str("tags = [\"db\", \"web\"]\n").expect("hcl error");
+
+    let expected = vec![String::from("\"web\"")];
+
+    let results: Vec<_> = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+
+    assert_eq!(expected, results);
+}
+
+#[test]
+fn result_paths_display() {
+    // filter '.tags[1], .options[], .data{"a_data_block"}.my_attr, .escaped["weird\"key"]'
+    let mut tag = Field::new("tags");
+    tag.selector = Some(Selector::Index(Index::FromStart(1)));
+    let mut options = Field::new("options");
+    options.selector = Some(Selector::Iterate);
+    let stages = vec![Stage {
+        paths: vec![
+            vec![tag],
+            vec![options],
+            vec![
+                Field::labeled("data", &["a_data_block"]),
+                Field::new("my_attr"),
+            ],
+            vec![Field::keyed("escaped", "weird\"key")],
+        ],
+    }];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let results =
+        query_stages_with_paths(stages, &body, &QueryOptions::default()).expect("query error");
+
+    let expected = vec![
+        ".tags[1]",
+        ".options.verbose",
+        ".options.debug",
+        r#".data{"a_data_block","with_some_attrs"}.my_attr"#,
+        r#".escaped."weird\"key""#,
+    ];
+    let paths: Vec<_> = results.iter().map(|(path, _)| path.to_string()).collect();
+    assert_eq!(expected, paths);
+
+    // each path is a filter that matches the result it leads to
+    for (path, query_result) in results {
+        let stages = hq_rs::parse_filter(&path.to_string()).expect("parse error");
+        let found = query_stages(stages, &body, &QueryOptions::default()).expect("query error");
+        assert_eq!(
+            vec![query_result.to_string().unwrap()],
+            found
+                .iter()
+                .map(|r| r.to_string().unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+    assert_eq!(".", Path::default().to_string());
+}
+
+#[test]
+fn escaped_paths_round_trip() {
+    let body = hcl::from_str(
+        r#"
+tags = {
+  "app.kubernetes.io/name" = "dots"
+  "cost center"            = "spaces"
+  "say \"hi\""             = "quotes"
+  "back\\slash"            = "backslash"
+  "two\nlines\ttabbed"      = "whitespace"
+  "bell\u0007"             = "control"
+  "-leading"               = "dash"
+  "3des"                   = "digit"
+  "*"                      = "star"
+}
+module "a \"quoted\" label" "with.dot" {
+  source = "label"
+}
+rule "*" {
+  port = 80
+}
+"#,
+    )
+    .expect("hcl error");
+    let stages = hq_rs::parse_filter(".tags[], .module, .rule").expect("parse error");
+
+    let results =
+        query_stages_with_paths(stages, &body, &QueryOptions::default()).expect("query error");
+
+    let expected = vec![
+        r#".tags."app.kubernetes.io/name""#,
+        r#".tags."cost center""#,
+        r#".tags."say \"hi\"""#,
+        r#".tags."back\\slash""#,
+        r#".tags."two\nlines\ttabbed""#,
+        r#".tags."bell\u0007""#,
+        r#".tags."-leading""#,
+        r#".tags."3des""#,
+        r#".tags."*""#,
+        r#".module{"a \"quoted\" label","with.dot"}"#,
+        r#".rule{"*"}"#,
+    ];
+    let paths: Vec<_> = results.iter().map(|(path, _)| path.to_string()).collect();
+    assert_eq!(expected, paths);
+
+    // each path can be pasted back in as a filter for the same result
+    for (path, query_result) in results {
+        let stages = hq_rs::parse_filter(&path.to_string()).expect("parse error");
+        let found = query_stages(stages, &body, &QueryOptions::default()).expect("query error");
+        assert_eq!(
+            vec![query_result.to_string().unwrap()],
+            found
+                .iter()
+                .map(|r| r.to_string().unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn base64_round_trip() {
+    // filter '.heredoc | @base64', then '.heredoc | @base64 | @base64d'
+    let stages = vec![
+        Stage::new(vec![Field::new("heredoc")]),
+        Stage::new(vec![Field::builtin(Builtin::Base64)]),
+    ];
+    let body = utilities::read_test_hcl().expect("hcl error");
+
+    let encoded = query_stages(stages.clone(), &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![String::from("\"Zmlyc3QgbGluZQogIHNlY29uZCBsaW5lCg==\"")],
+        encoded
+    );
+
+    let mut stages = stages;
+    stages.push(Stage::new(vec![Field::builtin(Builtin::Base64Decode)]));
+    let decoded = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_value())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![hcl::Value::from("first line\n  second line\n")],
+        decoded
+    );
+}
+
+#[test]
+fn base64_errors() {
+    let body = hcl::from_str("n = 1\ns = \"not base64!\"\n").expect("hcl error");
+    for (name, builtin) in [("n", Builtin::Base64), ("s", Builtin::Base64Decode)] {
+        // filter '.n | @base64' and '.s | @base64d'
+        let stages = vec![
+            Stage::new(vec![Field::new(name)]),
+            Stage::new(vec![Field::builtin(builtin)]),
+        ];
+        let result = query_stages(stages, &body, &QueryOptions::default());
+        assert!(result.is_err());
+    }
+}
+
+#[test]
+fn null_input() {
+    // filter '., length, .foo' applied to null, like `--null-input`
+    let stages = vec![Stage {
+        paths: vec![
+            vec![],
+            vec![Field::builtin(Builtin::Length)],
+            vec![Field::new("foo")],
+        ],
+    }];
+
+    let mut results = Vec::new();
+    query_stages_each_on(
+        &stages,
+        Path::default(),
+        QueryResult::Expr(hcl::Expression::Null),
+        &QueryOptions::default(),
+        |_, r| {
+            results.push(r.to_string().unwrap());
+            Ok::<(), Box<QueryError>>(())
+        },
+    )
+    .expect("query error");
+
+    assert_eq!(vec![String::from("null"), String::from("0")], results);
+}
+
+#[test]
+fn duplicates() {
+    // filter 'duplicates'
+    let fields = vec![Field::builtin(Builtin::Duplicates)];
+    let body = hcl::from_str(
+        r#"
+resource "aws_instance" "web" {}
+resource "aws_instance" "db" {}
+resource "aws_instance" "web" {}
+resource "aws_instance" "web" {}
+ingress {}
+ingress {}
+"#,
+    )
+    .expect("hcl error");
+
+    let results = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_value())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![hcl::Value::from_iter([hcl::Value::from_iter([
+            "resource",
+            "aws_instance",
+            "web"
+        ])])],
+        results
+    );
+
+    // the test document has no duplicates
+    let fields = vec![Field::builtin(Builtin::Duplicates)];
+    let body = utilities::read_test_hcl().expect("hcl error");
+    let results = query(&fields, &body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(vec![String::from("[]")], results);
+
+    // only the blocks directly in the input are compared
+    let body = hcl::from_str(
+        r#"
+resource "aws_instance" "web" {
+  provisioner "local-exec" {}
+  provisioner "local-exec" {}
+}
+"#,
+    )
+    .expect("hcl error");
+    let duplicates = |fields: Vec<Field>| {
+        query(&fields, &body)
+            .expect("query error")
+            .iter()
+            .map(|r| r.to_value())
+            .collect::<Vec<_>>()
+    };
+    // filter 'duplicates'
+    assert_eq!(
+        vec![hcl::Value::Array(Vec::new())],
+        duplicates(vec![Field::builtin(Builtin::Duplicates)])
+    );
+    // filter '.resource{"aws_instance","web"} | duplicates'
+    assert_eq!(
+        vec![hcl::Value::from_iter([hcl::Value::from_iter([
+            "provisioner",
+            "local-exec"
+        ])])],
+        duplicates(vec![
+            Field::labeled("resource", &["aws_instance", "web"]),
+            Field::builtin(Builtin::Duplicates)
+        ])
+    );
+}
+
+#[test]
+fn index_blocks() {
+    let body = hcl::from_str(
+        r#"
+ingress { port = 80 }
+ingress { port = 443 }
+ingress { port = 22 }
+ports = [8080, 8443]
+"#,
+    )
+    .expect("hcl error");
+    let results = |filter: &str| {
+        let stages = hq_rs::parse_filter(filter).expect("parse error");
+        query_stages(stages, &body, &QueryOptions::default()).map(|results| {
+            results
+                .iter()
+                .map(|r| r.to_string().unwrap())
+                .collect::<Vec<_>>()
+        })
+    };
+
+    assert_eq!(
+        vec![String::from("ingress {\n  port = 22\n}\n")],
+        results(".ingress[2]").expect("query error")
+    );
+    assert_eq!(
+        vec![String::from("443")],
+        results(".ingress[-2].port").expect("query error")
+    );
+    assert_eq!(
+        vec![String::from("80"), String::from("443")],
+        results(".ingress[:2].port").expect("query error")
+    );
+    assert_eq!(
+        vec![String::from("22"), String::from("80")],
+        results(".ingress[2,0].port").expect("query error")
+    );
+    // a list attribute is still indexed into
+    assert_eq!(
+        vec![String::from("8443")],
+        results(".ports[1]").expect("query error")
+    );
+
+    let e = results(".ingress[3]").expect_err("index should be out of bounds");
+    assert!(e
+        .to_string()
+        .contains("index 3 is out of bounds for 'ingress' (it matched 3 block(s))"));
+    assert!(results(".ingress[3]?").expect("query error").is_empty());
+}
+
+#[test]
+fn bracket_path() {
+    let body = hcl::from_str(
+        r#"
+resource "aws_instance" "web" {
+  ami = "ami-1"
+  tags = { "app.kubernetes.io/name" = "web" }
+}
+resource "aws_instance" "db" {
+  ami = "ami-2"
+}
+"#,
+    )
+    .expect("hcl error");
+    let results = |filter: &str| {
+        let stages = hq_rs::parse_filter(filter).expect("parse error");
+        query_stages(stages, &body, &QueryOptions::default()).map(|results| {
+            results
+                .iter()
+                .map(|r| r.to_string().unwrap())
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // filter '["resource"]["aws_instance"]["web"]["ami"]'
+    assert_eq!(
+        results(r#".resource{"aws_instance","web"}.ami"#).expect("query error"),
+        results(r#"["resource"]["aws_instance"]["web"]["ami"]"#).expect("query error")
+    );
+    // filter '["resource"]["aws_instance"].ami'
+    assert_eq!(
+        vec![String::from("\"ami-1\""), String::from("\"ami-2\"")],
+        results(r#"["resource"]["aws_instance"].ami"#).expect("query error")
+    );
+    // filter '["resource"]["aws_instance"]["web"]["tags"]["app.kubernetes.io/name"]'
+    assert_eq!(
+        vec![String::from("\"web\"")],
+        results(r#"["resource"]["aws_instance"]["web"]["tags"]["app.kubernetes.io/name"]"#)
+            .expect("query error")
+    );
+    // filter '["resource"]["aws_instance"]["cache"]'
+    assert!(results(r#"["resource"]["aws_instance"]["cache"]"#)
+        .expect("query error")
+        .is_empty());
+}
+
+#[test]
+fn max_depth() {
+    // `a = { b = { b = ... 1 } }`, with 100 levels of objects
+    let source = format!("a = {}1{}", "{ b = ".repeat(100), " }".repeat(100));
+    let body = hcl::from_str(&source).expect("hcl error");
+    // filter '..b'
+    let stages = vec![Stage::new(vec![Field {
+        recursive: true,
+        ..Field::new("b")
+    }])];
+
+    let options = QueryOptions {
+        max_depth: 64,
+        ..QueryOptions::default()
+    };
+    let e = query_stages(stages.clone(), &body, &options)
+        .map(|_| ())
+        .expect_err("'..' should go too deep");
+    let reason = e.to_string();
+    assert!(reason.contains("'..' went deeper than the maximum depth of 64 at '.a.b.b"));
+    assert!(reason.ends_with(".b'"));
+
+    let options = QueryOptions {
+        max_depth: 101,
+        ..QueryOptions::default()
+    };
+    let results = query_stages(stages, &body, &options).expect("query error");
+    assert_eq!(100, results.len());
+
+    // filter '.a.b.b..b'
+    // the depth is counted from where `..` starts, past `.a.b.b`
+    let stages = vec![Stage::new(vec![
+        Field::new("a"),
+        Field::new("b"),
+        Field::new("b"),
+        Field {
+            recursive: true,
+            ..Field::new("b")
+        },
+    ])];
+    let options = QueryOptions {
+        max_depth: 98,
+        ..QueryOptions::default()
+    };
+    let results = query_stages(stages.clone(), &body, &options).expect("query error");
+    assert_eq!(98, results.len());
+    let options = QueryOptions {
+        max_depth: 97,
+        ..QueryOptions::default()
+    };
+    assert!(query_stages(stages, &body, &options).is_err());
+}
+
+#[test]
+fn strict_labels() {
+    let strict = QueryOptions {
+        strict_labels: true,
+        ..QueryOptions::default()
+    };
+    let body =
+        hcl::from_str(r#"resource "aws_instance" "web" { ami = "ami-1" }"#).expect("hcl error");
+
+    // filter '.resource{"aws_instance","web"}'
+    let stages = vec![Stage::new(vec![Field::labeled(
+        "resource",
+        &["aws_instance", "web"],
+    )])];
+    let results = query_stages(stages, &body, &strict).expect("query error");
+    assert_eq!(1, results.len());
+
+    // filter '.resource{"aws_instance"}'
+    let body =
+        hcl::from_str(r#"resource "aws_instance" "web" { ami = "ami-1" }"#).expect("hcl error");
+    let stages = vec![Stage::new(vec![Field::labeled(
+        "resource",
+        &["aws_instance"],
+    )])];
+    let results =
+        query_stages(stages.clone(), &body, &QueryOptions::default()).expect("query error");
+    assert_eq!(1, results.len());
+    let e = query_stages(stages, &body, &strict)
+        .map(|_| ())
+        .expect_err("one label shouldn't match two");
+    assert!(e.to_string().contains(
+        "filter has 1 label(s) for 'resource' but the block at \
+         '.resource{\"aws_instance\",\"web\"}' has 2"
+    ));
+
+    // filter '.resource'
+    let stages = vec![Stage::new(vec![Field::new("resource")])];
+    let results = query_stages(stages, &body, &strict).expect("query error");
+    assert_eq!(1, results.len());
+}
+
+#[test]
+fn values_builtin() {
+    let body = utilities::read_test_hcl().expect("hcl error");
+    let values = |filter: &str| {
+        let stages = hq_rs::parse_filter(filter).expect("parse error");
+        query_stages_with_paths(stages, &body, &QueryOptions::default()).map(|results| {
+            results
+                .iter()
+                .map(|(path, r)| format!("{path}: {}", r.to_string().unwrap()))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    assert_eq!(
+        vec![
+            String::from(".settings.timeout: 30"),
+            String::from(".settings.mode: \"fast\""),
+        ],
+        values(".settings | values").expect("query error")
+    );
+    assert_eq!(
+        vec![
+            String::from(".tags[0]: \"alpha\""),
+            String::from(".tags[1]: \"beta\""),
+            String::from(".tags[2]: \"gamma\""),
+        ],
+        values(".tags | values").expect("query error")
+    );
+    // a block's values are its attribute values and blocks
+    assert_eq!(
+        vec![String::from(
+            ".variable{\"my_var\"}.default: \"my_default_value\""
+        )],
+        values(".variable{\"my_var\"} | values").expect("query error")
+    );
+
+    assert!(values(".version | values").is_err());
+    assert!(values(".version | values?")
+        .expect("query error")
+        .is_empty());
+}
+
+#[test]
+fn map_over_matches() {
+    let body = utilities::read_instances_hcl().expect("hcl error");
+    let results = |filter: &str| {
+        let stages = hq_rs::parse_filter(filter).expect("parse error");
+        query_stages_with_paths(stages, &body, &QueryOptions::default()).map(|results| {
+            results
+                .iter()
+                .map(|(path, r)| format!("{path}: {}", r.to_string().unwrap()))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // each segment is applied to every match of the one before it, and the
+    // matches that don't have it are skipped
+    // filter '.resource{"aws_instance"}.tags.Name'
+    assert_eq!(
+        vec![
+            String::from(".resource{\"aws_instance\",\"web\"}.tags.Name: \"web\""),
+            String::from(".resource{\"aws_instance\",\"cache\"}.tags.Name: \"cache\""),
+        ],
+        results(r#".resource{"aws_instance"}.tags.Name"#).expect("query error")
+    );
+    // filter '.resource{"aws_instance"}.ami'
+    assert_eq!(
+        vec![
+            String::from(".resource{\"aws_instance\",\"web\"}.ami: \"ami-web\""),
+            String::from(".resource{\"aws_instance\",\"db\"}.ami: \"ami-db\""),
+            String::from(".resource{\"aws_instance\",\"cache\"}.ami: \"ami-cache\""),
+        ],
+        results(r#".resource{"aws_instance"}.ami"#).expect("query error")
+    );
+    // filter '.resource{"aws_instance"} | .tags.Name' is the same
+    assert_eq!(
+        results(r#".resource{"aws_instance"}.tags.Name"#).expect("query error"),
+        results(r#".resource{"aws_instance"} | .tags.Name"#).expect("query error")
+    );
+    // filter '.resource.tags.Name'
+    assert_eq!(
+        3,
+        results(".resource.tags.Name").expect("query error").len()
+    );
+}
+
+#[test]
+fn func_call_args() {
+    let body = hcl::from_str(
+        r#"
+subnet = cidrsubnet(var.cidr, 8, 2)
+arn    = provider::aws::arn_parse("arn:aws:iam::123:role/x")
+"#,
+    )
+    .expect("hcl error");
+    let results = |filter: &str| {
+        let stages = hq_rs::parse_filter(filter).expect("parse error");
+        query_stages_with_paths(stages, &body, &QueryOptions::default()).map(|results| {
+            results
+                .iter()
+                .map(|(path, r)| format!("{path}: {}", hq_rs::output::to_hcl_compact(r).unwrap()))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // filter '.subnet.name'
+    assert_eq!(
+        vec![String::from(".subnet.name: \"cidrsubnet\"")],
+        results(".subnet.name").expect("query error")
+    );
+    // filter '.arn.name'
+    assert_eq!(
+        vec![String::from(".arn.name: \"provider::aws::arn_parse\"")],
+        results(".arn.name").expect("query error")
+    );
+    // arguments that aren't literals are their source
+    // filter '.subnet.args[]'
+    assert_eq!(
+        vec![
+            String::from(".subnet.args[0]: var.cidr"),
+            String::from(".subnet.args[1]: 8"),
+            String::from(".subnet.args[2]: 2"),
+        ],
+        results(".subnet.args[]").expect("query error")
+    );
+    // filter '.subnet.*'
+    assert_eq!(
+        vec![
+            String::from(".subnet.name: \"cidrsubnet\""),
+            String::from(".subnet.args: [var.cidr, 8, 2]"),
+        ],
+        results(".subnet.*").expect("query error")
+    );
+    // filter '.subnet.cidr'
+    assert!(results(".subnet.cidr").expect("query error").is_empty());
+}
+
+#[test]
+fn null_attr() {
+    let body = utilities::read_null_hcl().expect("hcl error");
+    let results = |filter: &str| {
+        let stages = hq_rs::parse_filter(filter).expect("parse error");
+        query_stages_with_paths(stages, &body, &QueryOptions::default()).map(|results| {
+            results
+                .iter()
+                .map(|(path, r)| format!("{path}: {}", r.to_string().unwrap()))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // an attribute that is null is a result
+    // filter '.description'
+    assert_eq!(
+        vec![String::from(".description: null")],
+        results(".description").expect("query error")
+    );
+    // filter '.settings.timeout'
+    assert_eq!(
+        vec![String::from(".settings.timeout: null")],
+        results(".settings.timeout").expect("query error")
+    );
+    // filter '.tags[1]'
+    assert_eq!(
+        vec![String::from(".tags[1]: null")],
+        results(".tags[1]").expect("query error")
+    );
+    // filter '.settings | keys'
+    assert_eq!(
+        vec![String::from(
+            ".settings | keys: [\n  \"retries\",\n  \"timeout\"\n]"
+        )],
+        results(".settings | keys").expect("query error")
+    );
+
+    // one that isn't there isn't
+    // filter '.summary'
+    assert!(results(".summary").expect("query error").is_empty());
+    // filter '.settings.delay'
+    assert!(results(".settings.delay").expect("query error").is_empty());
+    // filter '.tags[2]'
+    assert!(results(".tags[2]").is_err());
+    assert!(results(".tags[2]?").expect("query error").is_empty());
+}
+
+#[test]
+fn add_builtin() {
+    let body = hcl::from_str(
+        r#"
+ports = [80, 443, 8080]
+names = ["web", "-", "1"]
+groups = [["a"], null, ["b", "c"]]
+empty = []
+mixed = [80, "443"]
+"#,
+    )
+    .expect("hcl error");
+    let add = |filter: &str| {
+        let stages = hq_rs::parse_filter(filter).expect("parse error");
+        query_stages(stages, &body, &QueryOptions::default()).map(|results| {
+            results
+                .iter()
+                .map(|r| r.to_string().unwrap())
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // filter '.ports | add'
+    assert_eq!(
+        vec![String::from("8603")],
+        add(".ports | add").expect("query error")
+    );
+    // filter '.names | add'
+    assert_eq!(
+        vec![String::from("\"web-1\"")],
+        add(".names | add").expect("query error")
+    );
+    // filter '.groups | add'
+    assert_eq!(
+        vec![String::from("[\n  \"a\",\n  \"b\",\n  \"c\"\n]")],
+        add(".groups | add").expect("query error")
+    );
+    // filter '.empty | add'
+    assert_eq!(
+        vec![String::from("null")],
+        add(".empty | add").expect("query error")
+    );
+
+    let Err(e) = add(".mixed | add") else {
+        panic!("a string can't be added to a number");
+    };
+    assert!(e
+        .to_string()
+        .contains("'add' cannot add a string to a number"));
+    // each port is a number, not a list
+    assert!(add(".ports[] | add").is_err());
+}
+
+#[test]
+fn flatten_builtin() {
+    let body = hcl::from_str(
+        r#"
+ingress {
+  cidrs = [["10.0.0.0/8", ["10.1.0.0/16"]], ["192.168.0.0/16"]]
+}
+ports = 80
+"#,
+    )
+    .expect("hcl error");
+    let results = |filter: &str| {
+        let stages = hq_rs::parse_filter(filter).expect("parse error");
+        query_stages_with_paths(stages, &body, &QueryOptions::default()).map(|results| {
+            results
+                .iter()
+                .map(|(path, r)| format!("{path}: {}", hq_rs::output::to_hcl_compact(r).unwrap()))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // filter '.ingress.cidrs | flatten'
+    assert_eq!(
+        vec![String::from(
+            r#".ingress.cidrs | flatten: ["10.0.0.0/8", ["10.1.0.0/16"], "192.168.0.0/16"]"#
+        )],
+        results(".ingress.cidrs | flatten").expect("query error")
+    );
+    // filter '.ingress.cidrs | flatten(2)'
+    assert_eq!(
+        vec![String::from(
+            r#".ingress.cidrs | flatten(2): ["10.0.0.0/8", "10.1.0.0/16", "192.168.0.0/16"]"#
+        )],
+        results(".ingress.cidrs | flatten(2)").expect("query error")
+    );
+    // filter '.ingress.cidrs | flatten(0)'
+    assert_eq!(
+        vec![String::from(
+            r#".ingress.cidrs | flatten(0): [["10.0.0.0/8", ["10.1.0.0/16"]], ["192.168.0.0/16"]]"#
+        )],
+        results(".ingress.cidrs | flatten(0)").expect("query error")
+    );
+
+    assert!(results(".ports | flatten").is_err());
+    assert!(results(".ports | flatten?")
+        .expect("query error")
+        .is_empty());
+}
+
+#[test]
+fn repeated_block_paths() {
+    let body = utilities::read_repeated_hcl().expect("hcl error");
+
+    // filter 'leaf_paths'
+    let fields = vec![Field::builtin(Builtin::LeafPaths)];
+    let results = query(&fields, &body).expect("query error");
+    let hcl::Value::Array(leaf_paths) = results[0].to_value() else {
+        panic!("leaf_paths should be a list");
+    };
+    let leaf_paths: Vec<_> = leaf_paths
+        .into_iter()
+        .map(|path| path.as_str().expect("paths are strings").to_string())
+        .collect();
+
+    assert_eq!(
+        vec![
+            ".provider{\"aws\"}[0].region",
+            ".provider{\"aws\"}[1].alias",
+            ".provider{\"aws\"}[1].region",
+            ".resource{\"aws_security_group\",\"web\"}.ingress[0].port",
+            ".resource{\"aws_security_group\",\"web\"}.egress.port",
+            ".resource{\"aws_security_group\",\"web\"}.ingress[1].port",
+        ],
+        leaf_paths
+    );
+
+    // each path matches only the value it leads to
+    for path in &leaf_paths {
+        let stages = hq_rs::parse_filter(path).expect("parse error");
+        let results = query_stages(stages, &body, &QueryOptions::default()).expect("query error");
+        assert_eq!(1, results.len(), "{path}");
+    }
+
+    // filter '..port'
+    let mut field = Field::new("port");
+    field.recursive = true;
+    let stages = vec![Stage::new(vec![field])];
+    let results =
+        query_stages_with_paths(stages, &body, &QueryOptions::default()).expect("query error");
+    let shown: Vec<_> = results
+        .iter()
+        .map(|(path, result)| format!("{path}: {}", result.to_string().unwrap()))
+        .collect();
+
+    assert_eq!(
+        vec![
+            ".resource{\"aws_security_group\",\"web\"}.ingress[0].port: 80",
+            ".resource{\"aws_security_group\",\"web\"}.egress.port: 0",
+            ".resource{\"aws_security_group\",\"web\"}.ingress[1].port: 443",
+        ],
+        shown
+    );
+}
+
+#[test]
+fn paths_builtin() {
+    let body = hcl::from_str(
+        r#"
+version = "1"
+resource "aws_instance" "web" {
+  ami  = "ami-123"
+  tags = { Name = "web", "cost center" = 42 }
+  ports = [80, 443]
+}
+"#,
+    )
+    .expect("hcl error");
+    let paths = |builtin| {
+        // filter 'paths' or 'leaf_paths'
+        let fields = vec![Field::builtin(builtin)];
+        let results = query(&fields, &body).expect("query error");
+        let hcl::Value::Array(paths) = results[0].to_value() else {
+            panic!("paths should be a list");
+        };
+        paths
+            .into_iter()
+            .map(|path| path.as_str().expect("paths are strings").to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        vec![
+            ".version",
+            ".resource{\"aws_instance\",\"web\"}",
+            ".resource{\"aws_instance\",\"web\"}.ami",
+            ".resource{\"aws_instance\",\"web\"}.tags",
+            ".resource{\"aws_instance\",\"web\"}.tags.Name",
+            ".resource{\"aws_instance\",\"web\"}.tags.\"cost center\"",
+            ".resource{\"aws_instance\",\"web\"}.ports",
+            ".resource{\"aws_instance\",\"web\"}.ports[0]",
+            ".resource{\"aws_instance\",\"web\"}.ports[1]",
+        ],
+        paths(Builtin::Paths)
+    );
+
+    let leaf_paths = paths(Builtin::LeafPaths);
+    assert_eq!(
+        vec![
+            ".version",
+            ".resource{\"aws_instance\",\"web\"}.ami",
+            ".resource{\"aws_instance\",\"web\"}.tags.Name",
+            ".resource{\"aws_instance\",\"web\"}.tags.\"cost center\"",
+            ".resource{\"aws_instance\",\"web\"}.ports[0]",
+            ".resource{\"aws_instance\",\"web\"}.ports[1]",
+        ],
+        leaf_paths
+    );
+
+    // each path is a filter that matches the value it leads to
+    for path in leaf_paths {
+        let stages = hq_rs::parse_filter(&path).expect("parse error");
+        let results = query_stages(stages, &body, &QueryOptions::default()).expect("query error");
+        assert_eq!(1, results.len(), "{path}");
+    }
+
+    // filter '.resource{"aws_instance","web"}.tags | paths'
+    let stages = vec![
+        Stage::new(vec![
+            Field::labeled("resource", &["aws_instance", "web"]),
+            Field::new("tags"),
+        ]),
+        Stage::new(vec![Field::builtin(Builtin::Paths)]),
+    ];
+    let results = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_value())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![hcl::Value::from_iter([".Name", ".\"cost center\""])],
+        results
+    );
+}
+
+#[test]
+fn type_builtin() {
+    let body = utilities::read_test_hcl().expect("hcl error");
+    let types = |name: &str| {
+        // filter '.{name} | type'
+        let stages = vec![
+            Stage::new(vec![Field::new(name)]),
+            Stage::new(vec![Field::builtin(Builtin::Type)]),
+        ];
+        query_stages(stages, &body, &QueryOptions::default())
+            .expect("query error")
+            .iter()
+            .map(|r| r.to_string().unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(vec![String::from("\"string\"")], types("version"));
+    assert_eq!(vec![String::from("\"list\"")], types("tags"));
+    assert_eq!(vec![String::from("\"object\"")], types("settings"));
+    assert_eq!(vec![String::from("\"string\"")], types("heredoc"));
+    // blocks are objects
+    assert_eq!(
+        vec![String::from("\"object\""), String::from("\"object\"")],
+        types("variable")
+    );
+
+    let body = hcl::from_str("n = 1\nb = true\nz = null\nr = var.x\n").expect("hcl error");
+    // filter 'n, b, z, r | type'
+    let stages = vec![
+        Stage {
+            paths: ["n", "b", "z", "r"]
+                .iter()
+                .map(|name| vec![Field::new(name)])
+                .collect(),
+        },
+        Stage::new(vec![Field::builtin(Builtin::Type)]),
+    ];
+    let results = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            String::from("\"number\""),
+            String::from("\"bool\""),
+            String::from("\"null\""),
+            String::from("\"expression\""),
+        ],
+        results
+    );
+}
+
+#[test]
+fn compiled_filter() {
+    let filter = CompiledFilter::compile(".variable | keys").expect("parse error");
+    assert_eq!(2, filter.stages().len());
+
+    let body = utilities::read_test_hcl().expect("hcl error");
+    for _ in 0..2 {
+        let results: Vec<_> = filter
+            .apply(&body)
+            .expect("query error")
+            .iter()
+            .map(|r| r.to_string().unwrap())
+            .collect();
+        assert_eq!(2, results.len());
+    }
+
+    let filter = CompiledFilter::compile(".VERSION")
+        .expect("parse error")
+        .with_options(QueryOptions {
+            ignore_case: true,
+            ..QueryOptions::default()
+        });
+    let results: Vec<_> = filter
+        .apply(&body)
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+    assert_eq!(vec![String::from("\"test\"")], results);
+
+    assert!(matches!(
+        CompiledFilter::compile(".foo["),
+        Err(HqError::Filter(_))
+    ));
+}
+
+#[test]
+fn first_and_last() {
+    let body = utilities::read_test_hcl().expect("hcl error");
+    let results = |filter: &str| {
+        query_stages_with_paths(
+            hq_rs::parse_filter(filter).unwrap(),
+            &body,
+            &QueryOptions::default(),
+        )
+        .map(|matches| {
+            matches
+                .iter()
+                .map(|(path, r)| format!("{path}: {}", r.to_string().unwrap()))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // filter '.tags[] | first, last'
+    assert_eq!(
+        vec![
+            String::from(".tags[0]: \"alpha\""),
+            String::from(".tags[2]: \"gamma\""),
+        ],
+        results(".tags[] | first, last").expect("query error")
+    );
+    // filter '.variable | last | .default'
+    assert_eq!(
+        vec![String::from(
+            ".variable{\"another_var\"}.default: \"another_default_value\""
+        )],
+        results(".variable | last | .default").expect("query error")
+    );
+    // filter '.tags[5]? | first'
+    assert!(results(".tags[5]? | first")
+        .expect("query error")
+        .is_empty());
+
+    // `first` stops at the first result, so the error after it is never reached
+    // filter '.tags[], .version[0] | first'
+    assert_eq!(
+        vec![String::from(".tags[0]: \"alpha\"")],
+        results(".tags[], .version[0] | first").expect("query error")
+    );
+    assert!(results(".tags[], .version[0] | last").is_err());
+}
+
+#[test]
+fn required_providers() {
+    let body = utilities::read_test_hcl().expect("hcl error");
+    // `required_providers` is a block, but `aws` is a key of the object
+    // assigned to its `aws` attribute, not a block label
+
+    // filter '.terraform.required_providers.aws.version'
+    let stages = hq_rs::parse_filter(".terraform.required_providers.aws.version").unwrap();
+    let results: Vec<_> = query_stages_with_paths(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|(path, r)| format!("{path}: {}", r.to_string().unwrap()))
+        .collect();
+    assert_eq!(
+        vec![String::from(
+            ".terraform.required_providers.aws.version: \"~> 5.0\""
+        )],
+        results
+    );
+
+    // filter '.terraform.required_providers.aws.source'
+    let stages = hq_rs::parse_filter(".terraform.required_providers.aws.source").unwrap();
+    let results: Vec<_> = query_stages(stages, &body, &QueryOptions::default())
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+    assert_eq!(vec![String::from("\"hashicorp/aws\"")], results);
+}
+
+#[test]
+fn typed_wildcard() {
+    let body = hcl::from_str(
+        r#"
+name    = "web"
+count   = 3
+enabled = true
+ports   = [80, 443]
+tags    = { Name = "web", Port = 80 }
+nothing = null
+ami     = var.ami
+lifecycle {
+  prevent_destroy = true
+}
+"#,
+    )
+    .expect("hcl error");
+    let paths = |filter: &str| {
+        query_stages_with_paths(
+            hq_rs::parse_filter(filter).unwrap(),
+            &body,
+            &QueryOptions::default(),
+        )
+        .expect("query error")
+        .iter()
+        .map(|(path, _)| path.to_string())
+        .collect::<Vec<_>>()
+    };
+
+    // filter '.*:string'
+    assert_eq!(vec![".name"], paths(".*:string"));
+    // filter '.*:number'
+    assert_eq!(vec![".count"], paths(".*:number"));
+    // filter '.*:bool'
+    assert_eq!(vec![".enabled"], paths(".*:bool"));
+    // filter '.*:list'
+    assert_eq!(vec![".ports"], paths(".*:list"));
+    // blocks are objects too
+    // filter '.*:object'
+    assert_eq!(vec![".tags", ".lifecycle"], paths(".*:object"));
+    // filter '.*:null'
+    assert_eq!(vec![".nothing"], paths(".*:null"));
+    // filter '.*:expression'
+    assert_eq!(vec![".ami"], paths(".*:expression"));
+    // filter '..*:string' looks at every depth
+    assert_eq!(vec![".name", ".tags.Name"], paths("..*:string"));
+    // filter '.*:list[]' applies the rest to each match
+    assert_eq!(vec![".ports[0]", ".ports[1]"], paths(".*:list[]"));
+}
+
+#[test]
+fn wildcard_name() {
+    let body = utilities::read_test_hcl().expect("hcl error");
+    let paths = |filter: &str| {
+        query_stages_with_paths(
+            hq_rs::parse_filter(filter).unwrap(),
+            &body,
+            &QueryOptions::default(),
+        )
+        .expect("query error")
+        .iter()
+        .map(|(path, _)| path.to_string())
+        .collect::<Vec<_>>()
+    };
+
+    // filter '.*' lists every attribute and block in source order
+    let all = paths(".*");
+    assert_eq!(body.iter().count(), all.len());
+    assert_eq!(
+        vec![".version", ".options", ".tags"],
+        all.iter().take(3).map(String::as_str).collect::<Vec<_>>()
+    );
+    assert!(all.contains(&String::from(r#".variable{"my_var"}"#)));
+
+    // filter '.options.*' applies to the keys of an object
+    assert_eq!(
+        vec![".options.verbose", ".options.debug"],
+        paths(".options.*")
+    );
+
+    // filter '.variable.*' applies to each `variable` block
+    assert_eq!(
+        vec![
+            r#".variable{"my_var"}.default"#,
+            r#".variable{"another_var"}.default"#
+        ],
+        paths(".variable.*")
+    );
+
+    // filter '.*{*,"with_some_attrs"}' only matches blocks with enough labels
+    assert_eq!(
+        vec![
+            r#".data{"a_data_block","with_some_attrs"}"#,
+            r#".data{"another_data_block","with_some_attrs"}"#
+        ],
+        paths(r#".*{*,"with_some_attrs"}"#)
+    );
+}
+
+#[test]
+fn quoted_star_name() {
+    let body: hcl::Body = hcl::from_str(
+        r#"
+tags = { "*" = 1, a = 2 }
+rule "*" { port = 80 }
+rule "web" { port = 443 }
+"#,
+    )
+    .expect("hcl error");
+    let results = |filter: &str| {
+        query_stages(
+            hq_rs::parse_filter(filter).unwrap(),
+            &body,
+            &QueryOptions::default(),
+        )
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect::<Vec<_>>()
+    };
+
+    // filter '.tags."*"' and '.tags["*"]' match only the key named `*`
+    assert_eq!(vec!["1"], results(r#".tags."*""#));
+    assert_eq!(vec!["1"], results(r#".tags["*"]"#));
+    // filter '.tags.*' matches every key
+    assert_eq!(vec!["1", "2"], results(".tags.*"));
+    // filter '.rule{"*"}.port' matches only the block labeled `*`
+    assert_eq!(vec!["80"], results(r#".rule{"*"}.port"#));
+    // filter '.rule{*}.port' matches every label
+    assert_eq!(vec!["80", "443"], results(".rule{*}.port"));
+}
+
+#[test]
+fn locals() {
+    let body = utilities::read_test_hcl().expect("hcl error");
+    // `locals` is an unlabeled block, and each local is an attribute in it
+    let results = |filter: &str| {
+        query_stages_with_paths(
+            hq_rs::parse_filter(filter).unwrap(),
+            &body,
+            &QueryOptions::default(),
+        )
+        .expect("query error")
+        .iter()
+        .map(|(path, r)| format!("{path}: {}", r.to_string().unwrap()))
+        .collect::<Vec<_>>()
+    };
+
+    // filter '.locals'
+    assert_eq!(
+        vec![String::from(
+            ".locals: locals {\n  name_prefix = \"web\"\n  common_tags = {\n    Team = \"platform\"\n  }\n}\n"
+        )],
+        results(".locals")
+    );
+    // filter '.locals.name_prefix, .locals.common_tags.Team'
+    assert_eq!(
+        vec![
+            String::from(".locals.name_prefix: \"web\""),
+            String::from(".locals.common_tags.Team: \"platform\""),
+        ],
+        results(".locals.name_prefix, .locals.common_tags.Team")
+    );
+}
+
+#[test]
+fn ordering_predicates() {
+    let body: hcl::Body = hcl::from_str(
+        r#"
+resource "aws_instance" "small" {
+  cpu  = 2
+  name = "alpha"
+}
+resource "aws_instance" "medium" {
+  cpu  = 4
+  name = "beta"
+}
+resource "aws_instance" "large" {
+  cpu  = 8.5
+  name = "gamma"
+}
+resource "aws_instance" "quoted" {
+  cpu  = "8"
+  name = true
+}
+"#,
+    )
+    .expect("hcl error");
+    let names = |filter: &str| {
+        query_stages(
+            hq_rs::parse_filter(filter).unwrap(),
+            &body,
+            &QueryOptions::default(),
+        )
+        .expect("query error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect::<Vec<_>>()
+    };
+
+    // numbers are ordered by value
+    // filter '.resource[.cpu > 4].name'
+    assert_eq!(vec!["\"gamma\""], names(".resource[.cpu > 4].name"));
+    // filter '.resource[.cpu >= 4].name'
+    assert_eq!(
+        vec!["\"beta\"", "\"gamma\""],
+        names(".resource[.cpu >= 4].name")
+    );
+    // filter '.resource[.cpu < 4].name'
+    assert_eq!(vec!["\"alpha\""], names(".resource[.cpu < 4].name"));
+    // filter '.resource[.cpu <= 4].name'
+    assert_eq!(
+        vec!["\"alpha\"", "\"beta\""],
+        names(".resource[.cpu <= 4].name")
+    );
+    // filter '.resource[.cpu != 4].name'
+    assert_eq!(
+        vec!["\"alpha\"", "\"gamma\""],
+        names(".resource[.cpu != 4].name")
+    );
+    // filter '.resource[.cpu>8.4].name'
+    assert_eq!(vec!["\"gamma\""], names(".resource[.cpu>8.4].name"));
+
+    // strings are ordered lexically
+    // filter '.resource[.name < "beta"].cpu'
+    assert_eq!(vec!["2"], names(r#".resource[.name < "beta"].cpu"#));
+    // filter '.resource[.name >= "beta"].cpu'
+    assert_eq!(vec!["4", "8.5"], names(r#".resource[.name >= "beta"].cpu"#));
+
+    // values of another type (or that are missing) never match, not even `!=`
+    // filter '.resource[.cpu != "4"].name'
+    assert_eq!(vec!["true"], names(r#".resource[.cpu != "4"].name"#));
+    // filter '.resource[.name != false].cpu'
+    assert_eq!(vec!["\"8\""], names(".resource[.name != false].cpu"));
+    // filter '.resource[.name > 1]'
+    assert!(names(".resource[.name > 1]").is_empty());
+    // filter '.resource[.memory < 1]'
+    assert!(names(".resource[.memory < 1]").is_empty());
+    // filter '.resource[.name < true]'
+    assert!(names(".resource[.name < true]").is_empty());
+}
+
+#[test]
+fn contains_predicate() {
+    let body: hcl::Body = hcl::from_str(
+        r#"
+resource "aws_security_group" "open" {
+  cidr_blocks = ["10.0.0.0/8", "0.0.0.0/0"]
+  ports       = [22, 443]
+  description = "wide open"
+}
+resource "aws_security_group" "internal" {
+  cidr_blocks = ["10.0.0.0/8"]
+  ports       = [443]
+  description = "internal only"
+}
+"#,
+    )
+    .expect("hcl error");
+    let names = |filter: &str| {
+        query_stages_with_paths(
+            hq_rs::parse_filter(filter).unwrap(),
+            &body,
+            &QueryOptions::default(),
+        )
+        .expect("query error")
+        .iter()
+        .map(|(path, _)| path.to_string())
+        .collect::<Vec<_>>()
+    };
+    let open = r#".resource{"aws_security_group","open"}"#;
+    let internal = r#".resource{"aws_security_group","internal"}"#;
+
+    // lists contain their elements
+    assert_eq!(
+        vec![open],
+        names(r#".resource[.cidr_blocks contains "0.0.0.0/0"]"#)
+    );
+    assert_eq!(
+        vec![open, internal],
+        names(".resource[.ports contains 443]")
+    );
+    // of the same type only
+    assert!(names(r#".resource[.ports contains "443"]"#).is_empty());
+    // strings contain their substrings
+    assert_eq!(
+        vec![internal],
+        names(r#".resource[.description contains "internal"]"#)
+    );
+    // anything else contains nothing
+    assert!(names(r#".resource[.description contains 1]"#).is_empty());
+    assert_eq!(
+        vec![internal],
+        names(r#".resource[not .cidr_blocks contains "0.0.0.0/0"]"#)
+    );
+}
+
+#[test]
+fn many_paths_duplicate_names() {
+    let body: hcl::Body = hcl::from_str(
+        r#"
+rule { port = 80 }
+name = "first"
+rule { port = 443 }
+other { port = 22 }
+rule "labeled" { port = 8080 }
+"#,
+    )
+    .expect("hcl error");
+    let paths = |filter: &str| {
+        query_stages_with_paths(
+            hq_rs::parse_filter(filter).unwrap(),
+            &body,
+            &QueryOptions::default(),
+        )
+        .expect("query error")
+        .iter()
+        .map(|(path, r)| format!("{path}: {}", r.to_string().unwrap()))
+        .collect::<Vec<_>>()
+    };
+
+    // filter '.rule.port, .name, .other, .missing'
+    // every block with a name is matched, in source order, by each path
+    assert_eq!(
+        vec![
+            ".rule[0].port: 80",
+            ".rule[1].port: 443",
+            r#".rule{"labeled"}.port: 8080"#,
+            r#".name: "first""#,
+            ".other: other {\n  port = 22\n}\n",
+        ],
+        paths(".rule.port, .name, .other, .missing")
+    );
+    // filter '. | .other.port, .rule.port' is the same as each path on its own
+    let together = paths(". | .other.port, .rule.port");
+    let apart = [paths(".other.port"), paths(".rule.port")].concat();
+    assert_eq!(apart, together);
+    // filter '.rule | .port, .port'
+    assert_eq!(6, paths(".rule | .port, .port").len());
+}
+
+#[test]
+fn regex_names() {
+    let body: hcl::Body = hcl::from_str(
+        r#"
+aws_region = "us-east-1"
+resource "aws_instance" "web" {
+  ami = "ami-123"
+}
+resource "google_compute_instance" "web" {}
+data "aws_ami" "ubuntu" {}
+settings = {
+  aws_profile = "default"
+  backend     = "s3"
+}
+"#,
+    )
+    .expect("hcl error");
+    let paths = |filter: &str| {
+        query_stages_with_paths(
+            hq_rs::parse_filter(filter).unwrap(),
+            &body,
+            &QueryOptions::default(),
+        )
+        .expect("query error")
+        .iter()
+        .map(|(path, _)| path.to_string())
+        .collect::<Vec<_>>()
+    };
+
+    // filter '.~"^aws_"'
+    assert_eq!(vec![".aws_region"], paths(r#".~"^aws_""#));
+    // filter '.~"^(resource|data)$"{"aws_instance"}'
+    assert_eq!(
+        vec![r#".resource{"aws_instance","web"}"#],
+        paths(r#".~"^(resource|data)$"{"aws_instance"}"#)
+    );
+    // filter '..~"^aws_"'
+    assert_eq!(
+        vec![".aws_region", ".settings.aws_profile"],
+        paths(r#"..~"^aws_""#)
+    );
+    // filter '.settings.~"^b"'
+    assert_eq!(vec![".settings.backend"], paths(r#".settings.~"^b""#));
+    // filter '.~"^data$"{"aws_ami","ubuntu","extra"}' skips blocks with fewer labels
+    assert!(paths(r#".~"^data$"{"aws_ami","ubuntu","extra"}"#).is_empty());
+}
+
+#[test]
+fn variables() {
+    let body: hcl::Body = hcl::from_str(
+        r#"
+resource "aws_instance" "east" {
+  region = "us-east-1"
+  count  = 2
+}
+resource "aws_instance" "west" {
+  region = "us-west-2"
+  count  = 3
+}
+"#,
+    )
+    .expect("hcl error");
+    let options = QueryOptions {
+        variables: [
+            (
+                String::from("region"),
+                Literal::String(String::from("us-west-2")),
+            ),
+            (String::from("count"), Literal::Number(2.0)),
+        ]
+        .into(),
+        ..QueryOptions::default()
+    };
+    let names = |filter: &str| {
+        query_stages_with_paths(hq_rs::parse_filter(filter).unwrap(), &body, &options).map(
+            |results| {
+                results
+                    .iter()
+                    .map(|(path, _)| path.to_string())
+                    .collect::<Vec<_>>()
+            },
+        )
+    };
+
+    // filter '.resource[.region == $region]'
+    assert_eq!(
+        vec![r#".resource{"aws_instance","west"}"#],
+        names(".resource[.region == $region]").expect("query error")
+    );
+    // filter '.resource[.count == $count]'
+    assert_eq!(
+        vec![r#".resource{"aws_instance","east"}"#],
+        names(".resource[.count == $count]").expect("query error")
+    );
+    // filter '.resource[.region == $zone]'
+    let err = names(".resource[.region == $zone]").unwrap_err();
+    assert!(err.to_string().contains("variable '$zone' is not defined"));
+}
+
+#[test]
+fn empty_documents() {
+    // an empty file, and a blank one in the JSON syntax
+    for file in ["tests/empty.tf", "tests/blank.tf.json"] {
+        let source = std::fs::read_to_string(file).unwrap();
+        for body in [
+            hq_rs::parse_hcl(&source).expect("hcl error"),
+            hq_rs::json::from_str(&source).expect("hcl error"),
+        ] {
+            assert_eq!(0, body.iter().count());
+            let results = |filter: &str| {
+                query_stages(
+                    hq_rs::parse_filter(filter).unwrap(),
+                    &body,
+                    &QueryOptions::default(),
+                )
+                .expect("query error")
+                .iter()
+                .map(|r| r.to_string().unwrap())
+                .collect::<Vec<_>>()
+            };
+            // filter '.resource'
+            assert!(results(".resource").is_empty());
+            // filter '..name'
+            assert!(results("..name").is_empty());
+            // filter 'keys'
+            assert_eq!(vec!["[]"], results("keys"));
+        }
+    }
+}
+
+#[test]
+fn json_syntax() {
+    // the same configuration in the native and JSON syntax
+    let native = hq_rs::parse_hcl(&std::fs::read_to_string("tests/terraform.tf").unwrap())
+        .expect("hcl error");
+    let json = hq_rs::parse_hcl(&std::fs::read_to_string("tests/terraform.tf.json").unwrap())
+        .expect("hcl error");
+
+    for filter in [
+        ".",
+        ".terraform.required_providers.aws.version",
+        r#".terraform.backend{"s3"}.bucket"#,
+        ".variable",
+        ".locals.name_prefix",
+        r#".resource{"aws_instance"}"#,
+        r#".resource{"aws_instance","web"}.tags.Name"#,
+        ".output.value",
+    ] {
+        let results = |body: &hcl::Body| {
+            query_stages_with_paths(
+                hq_rs::parse_filter(filter).unwrap(),
+                body,
+                &QueryOptions::default(),
+            )
+            .expect("query error")
+            .iter()
+            .map(|(path, r)| format!("{path}: {}", r.to_string().unwrap()))
+            .collect::<Vec<_>>()
+        };
+        let expected = results(&native);
+        assert!(!expected.is_empty(), "{filter} matched nothing");
+        assert_eq!(expected, results(&json), "{filter}");
+    }
+}
+
+#[test]
+fn json_syntax_errors() {
+    assert!(matches!(
+        hq_rs::eval(r#"{"a": "#, ".a"),
+        Err(HqError::Json(_))
+    ));
+    assert!(matches!(
+        hq_rs::eval(r#"{"resource": {"aws_instance": 1}}"#, ".a"),
+        Err(HqError::Hcl(_))
+    ));
+}
+
+#[test]
+fn json_syntax_repeated_blocks() {
+    // an array at any level holds several blocks
+    let source = r#"{"resource": {"aws_instance": {"web": [{"ami": "a"}, {"ami": "b"}]}}}"#;
+    let results: Vec<_> = hq_rs::eval(source, ".resource.ami")
+        .expect("eval error")
+        .iter()
+        .map(|r| r.to_string().unwrap())
+        .collect();
+    assert_eq!(vec!["\"a\"", "\"b\""], results);
+}
@@ -0,0 +1,24 @@
+# blocks that share an identifier and labels, which paths tell apart by their
+# position among each other
+provider "aws" {
+  region = "us-east-1"
+}
+
+provider "aws" {
+  alias  = "west"
+  region = "us-west-2"
+}
+
+resource "aws_security_group" "web" {
+  ingress {
+    port = 80
+  }
+
+  egress {
+    port = 0
+  }
+
+  ingress {
+    port = 443
+  }
+}
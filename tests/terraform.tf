@@ -0,0 +1,36 @@
+terraform {
+  required_providers {
+    aws = {
+      source  = "hashicorp/aws"
+      version = "~> 5.0"
+    }
+  }
+  backend "s3" {
+    bucket = "state"
+  }
+}
+
+variable "region" {
+  default = "us-east-1"
+}
+
+locals {
+  name_prefix = "web-${var.region}"
+}
+
+resource "aws_instance" "web" {
+  ami           = "ami-123"
+  instance_type = "t3.micro"
+  tags = {
+    Name = "web"
+  }
+}
+
+resource "aws_instance" "db" {
+  ami   = "ami-456"
+  count = 2
+}
+
+output "ip" {
+  value = aws_instance.web.public_ip
+}
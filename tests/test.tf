@@ -3,6 +3,12 @@ options = {
     verbose = true
     debug = false
 }
+tags = ["alpha", "beta", "gamma"]
+escaped = { "weird\"key" = "odd" }
+rules = [
+    { port = 80, protocol = "tcp" },
+    { port = 443, protocol = "tcp" },
+]
 
 variable "my_var" { default = "my_default_value" }
 variable "another_var" { default = "another_default_value" }
@@ -19,3 +25,62 @@ data "another_data_block" "with_some_attrs" {
 module "cool-module" {
     version = "1.2.3"
 }
+
+resource "aws_security_group" "web" {
+    dynamic "ingress" {
+        for_each = var.ports
+        content {
+            from_port = ingress.value
+            dynamic "cidr" {
+                content {
+                    block = "0.0.0.0/0"
+                }
+            }
+        }
+    }
+}
+
+heredoc = <<EOF
+first line
+  second line
+EOF
+
+indented_heredoc = <<-EOT
+    first line
+
+      second line
+    EOT
+
+settings = { timeout = 30, mode = "fast" }
+
+nested_settings = {
+  retry = {
+    attempts = 3
+    backoff  = [1, 2, 4]
+  }
+}
+
+interpolated = "${var.prefix}-web"
+escaped_interpolation = "$${not_interpolated}"
+
+shared = "attribute"
+
+shared "label" {
+  value = "block"
+}
+
+terraform {
+  required_providers {
+    aws = {
+      source  = "hashicorp/aws"
+      version = "~> 5.0"
+    }
+  }
+}
+
+locals {
+  name_prefix = "web"
+  common_tags = {
+    Team = "platform"
+  }
+}
@@ -1,4 +1,7 @@
-use hq_rs::{parser::Field, write};
+use hq_rs::{
+    parser::{Field, ValueType},
+    write,
+};
 
 #[test]
 fn attr() {
@@ -48,3 +51,86 @@ fn labeled_block_attr() {
         body.to_string()
     );
 }
+
+#[test]
+fn multi_labeled_block_attr() {
+    // filter '.resource{"aws_instance"}.ami'
+    let fields = vec![
+        Field::labeled("resource", &["aws_instance"]),
+        Field::new("ami"),
+    ];
+
+    let mut body = utilities::edit_hcl("resource \"aws_instance\" \"web\" { ami = \"old\" }")
+        .expect("hcl error");
+
+    let value: hcl_edit::expr::Expression = "\"new\"".parse().expect("parse error");
+
+    write(fields, &mut body, &value).expect("write error");
+
+    assert_eq!(
+        "resource \"aws_instance\" \"web\" { ami = \"new\" }",
+        body.to_string()
+    );
+}
+
+#[test]
+fn wildcard_name() {
+    // filter '.options.*'
+    let fields = vec![Field::new("options"), Field::wildcard()];
+
+    let mut body = utilities::edit_hcl("options { enabled = false }").expect("hcl error");
+
+    let value: hcl_edit::expr::Expression = "true".parse().expect("parse error");
+
+    let Err(e) = write(fields, &mut body, &value) else {
+        panic!("writing with a wildcard name should fail");
+    };
+    assert_eq!(
+        "failed to write HCL: writing with wildcard names is not supported",
+        e.to_string()
+    );
+    assert_eq!("options { enabled = false }", body.to_string());
+}
+
+#[test]
+fn regex_name() {
+    // filter '.options.~"^en"'
+    let pattern = regex::Regex::new("^en").expect("regex error");
+    let fields = vec![Field::new("options"), Field::matching(pattern)];
+
+    let mut body = utilities::edit_hcl("options { enabled = false }").expect("hcl error");
+
+    let value: hcl_edit::expr::Expression = "true".parse().expect("parse error");
+
+    let Err(e) = write(fields, &mut body, &value) else {
+        panic!("writing with a regex name should fail");
+    };
+    assert_eq!(
+        "failed to write HCL: writing with regex names is not supported",
+        e.to_string()
+    );
+}
+
+#[test]
+fn value_type() {
+    // a field with a value type, like the wildcard in '.options.*:bool'
+    let fields = vec![
+        Field::new("options"),
+        Field {
+            value_type: Some(ValueType::Bool),
+            ..Field::new("enabled")
+        },
+    ];
+
+    let mut body = utilities::edit_hcl("options { enabled = false }").expect("hcl error");
+
+    let value: hcl_edit::expr::Expression = "true".parse().expect("parse error");
+
+    let Err(e) = write(fields, &mut body, &value) else {
+        panic!("writing with a value type should fail");
+    };
+    assert_eq!(
+        "failed to write HCL: writing with value types is not supported",
+        e.to_string()
+    );
+}
@@ -1,7 +1,23 @@
 use std::{error::Error, fs};
 
 pub fn read_test_hcl() -> Result<hcl::Body, Box<dyn Error>> {
-    let contents = fs::read_to_string("tests/test.tf")?;
+    read_hcl("tests/test.tf")
+}
+
+pub fn read_null_hcl() -> Result<hcl::Body, Box<dyn Error>> {
+    read_hcl("tests/null.tf")
+}
+
+pub fn read_instances_hcl() -> Result<hcl::Body, Box<dyn Error>> {
+    read_hcl("tests/instances.tf")
+}
+
+pub fn read_repeated_hcl() -> Result<hcl::Body, Box<dyn Error>> {
+    read_hcl("tests/repeated.tf")
+}
+
+fn read_hcl(path: &str) -> Result<hcl::Body, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
     let body: hcl::Body = hcl::from_str(&contents)?;
     Ok(body)
 }